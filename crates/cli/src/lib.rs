@@ -34,13 +34,13 @@ pub enum Command {
     },
     /// Update the configuration by introspecting the database, using the configuration options.
     Update,
-    // /// Upgrade the configuration to the latest version. This does not involve the database.
-    // Upgrade {
-    //     #[arg(long)]
-    //     dir_from: PathBuf,
-    //     #[arg(long)]
-    //     dir_to: PathBuf,
-    // },
+    /// Upgrade the configuration to the latest version. This does not involve the database.
+    Upgrade {
+        #[arg(long)]
+        dir_from: PathBuf,
+        #[arg(long)]
+        dir_to: PathBuf,
+    },
     // #[command(subcommand)]
     // NativeOperation(native_operations::Command),
 }
@@ -57,7 +57,7 @@ pub async fn run(command: Command, context: Context<impl Environment>) -> anyhow
     match command {
         Command::Initialize { with_metadata } => initialize(with_metadata, context).await?,
         Command::Update => update(context).await?,
-        // Command::Upgrade { dir_from, dir_to } => upgrade(dir_from, dir_to).await?,
+        Command::Upgrade { dir_from, dir_to } => upgrade(dir_from, dir_to).await?,
         // Command::NativeOperation(cmd) => native_operations::run(cmd, context).await?,
     };
     Ok(())
@@ -74,7 +74,7 @@ pub async fn run(command: Command, context: Context<impl Environment>) -> anyhow
 async fn initialize(with_metadata: bool, context: Context<impl Environment>) -> anyhow::Result<()> {
     let _ = context
         .context_path
-        .join(configuration::version1::CONFIGURATION_FILENAME);
+        .join(configuration::CONFIGURATION_FILENAME);
     fs::create_dir_all(&context.context_path).await?;
     // refuse to initialize the directory unless it is empty
     let mut items_in_dir = fs::read_dir(&context.context_path).await?;
@@ -175,15 +175,22 @@ async fn update(context: Context<impl Environment>) -> anyhow::Result<()> {
     ))
 }
 
-// /// Upgrade the configuration in a directory by trying to read it and then write it back
-// /// out to a different directory.
-// ///
-// async fn upgrade(dir_from: PathBuf, dir_to: PathBuf) -> anyhow::Result<()> {
-//     let old_configuration = configuration::parse_configuration(dir_from).await?;
-//     let upgraded_configuration = configuration::upgrade_to_latest_version(old_configuration);
-//     configuration::write_parsed_configuration(upgraded_configuration, dir_to).await?;
+/// Upgrade the configuration in a directory by trying to read it and then write it back
+/// out to a different directory.
+///
+async fn upgrade(dir_from: PathBuf, dir_to: PathBuf) -> anyhow::Result<()> {
+    let old_configuration = configuration::parse_configuration(dir_from).await?;
+    if let configuration::RawConfiguration::Version1(v1) = &old_configuration {
+        for setting in v1.pool_settings.unused_settings() {
+            eprintln!(
+                "WARNING: The '{setting}' pool setting is not used by the connector, and is dropped from the upgraded configuration."
+            );
+        }
+    }
+    let upgraded_configuration = configuration::upgrade_to_latest_version(old_configuration);
+    configuration::write_parsed_configuration(upgraded_configuration, dir_to).await?;
 
-//     eprintln!("Upgrade completed successfully. You may need to also run 'update'.");
+    eprintln!("Upgrade completed successfully. You may need to also run 'update'.");
 
-//     Ok(())
-// }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! Tests of the upgrade of configurations to the latest version of the format.
+
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+
+use ndc_bigquery_cli::*;
+use ndc_bigquery_configuration as configuration;
+use ndc_bigquery_configuration::connection_settings::DEFAULT_SERVICE_KEY_VARIABLE;
+use ndc_bigquery_configuration::environment::{EmptyEnvironment, FixedEnvironment};
+
+#[tokio::test]
+async fn upgrade_initial_version1_configuration() -> anyhow::Result<()> {
+    let upgraded_configuration = upgrade("version1-initial").await?;
+    insta::assert_snapshot!(upgraded_configuration);
+    Ok(())
+}
+
+#[tokio::test]
+async fn upgrade_version1_configuration_with_settings() -> anyhow::Result<()> {
+    let upgraded_configuration = upgrade("version1-with-settings").await?;
+    insta::assert_snapshot!(upgraded_configuration);
+    Ok(())
+}
+
+#[tokio::test]
+async fn upgrading_does_not_change_the_runtime_configuration() -> anyhow::Result<()> {
+    let dir_from = configuration_dir("version1-with-settings");
+    let dir_to = tempfile::tempdir()?;
+    run_upgrade(&dir_from, dir_to.path()).await?;
+
+    let upgraded = configuration::parse_configuration(dir_to.path()).await?;
+    assert!(matches!(
+        upgraded,
+        configuration::RawConfiguration::Version2(_)
+    ));
+
+    let environment =
+        FixedEnvironment::from([(DEFAULT_SERVICE_KEY_VARIABLE.into(), "{}".to_string())]);
+    let original = configuration::make_runtime_configuration(
+        configuration::parse_configuration(&dir_from).await?,
+        &environment,
+    )?;
+    let upgraded = configuration::make_runtime_configuration(upgraded, &environment)?;
+    assert_eq!(format!("{original:?}"), format!("{upgraded:?}"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn upgrading_the_latest_version_changes_nothing() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let upgraded_once = dir.path().join("once");
+    let upgraded_twice = dir.path().join("twice");
+    run_upgrade(&configuration_dir("version1-with-settings"), &upgraded_once).await?;
+    run_upgrade(&upgraded_once, &upgraded_twice).await?;
+
+    assert_eq!(
+        read_configuration(&upgraded_once).await?,
+        read_configuration(&upgraded_twice).await?
+    );
+    Ok(())
+}
+
+/// Upgrade one of the test configurations, and return the upgraded configuration file.
+async fn upgrade(name: &str) -> anyhow::Result<String> {
+    let dir_to = tempfile::tempdir()?;
+    run_upgrade(&configuration_dir(name), dir_to.path()).await?;
+    read_configuration(dir_to.path()).await
+}
+
+async fn run_upgrade(dir_from: &Path, dir_to: &Path) -> anyhow::Result<()> {
+    let context = Context {
+        context_path: dir_to.to_owned(),
+        environment: EmptyEnvironment,
+        release_version: None,
+    };
+    let command = Command::Upgrade {
+        dir_from: dir_from.to_owned(),
+        dir_to: dir_to.to_owned(),
+    };
+    run(command, context).await
+}
+
+async fn read_configuration(dir: &Path) -> anyhow::Result<String> {
+    Ok(fs::read_to_string(dir.join(configuration::CONFIGURATION_FILENAME)).await?)
+}
+
+fn configuration_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("configurations")
+        .join(name)
+}
@@ -131,6 +131,31 @@ unique_constraint_struct AS (
     ) AS unique_constraint
   FROM unique_constraint_data
   GROUP BY table_name, table_catalog, table_schema
+),
+partitioning_data AS (
+  SELECT
+    c.table_name,
+    c.table_catalog,
+    c.table_schema,
+    MAX(IF(c.is_partitioning_column = 'YES', c.column_name, NULL)) AS partition_column,
+    ARRAY_AGG(
+      IF(c.clustering_ordinal_position IS NULL, NULL, c.column_name)
+      IGNORE NULLS
+      ORDER BY c.clustering_ordinal_position
+    ) AS clustering_columns
+  FROM HASURA_DATABASE_NAME_PLACEHOLDER.INFORMATION_SCHEMA.COLUMNS AS c
+  WHERE c.table_schema = 'HASURA_DATABASE_SCHEMA_PLACEHOLDER'
+  GROUP BY c.table_name, c.table_catalog, c.table_schema
+),
+partition_filter_data AS (
+  SELECT
+    o.table_name,
+    o.table_catalog,
+    o.table_schema
+  FROM HASURA_DATABASE_NAME_PLACEHOLDER.INFORMATION_SCHEMA.TABLE_OPTIONS AS o
+  WHERE o.table_schema = 'HASURA_DATABASE_SCHEMA_PLACEHOLDER'
+    AND o.option_name = 'require_partition_filter'
+    AND o.option_value = 'true'
 )
 SELECT
   CONCAT('{', STRING_AGG(CONCAT(
@@ -146,9 +171,19 @@ SELECT
       '},',
       '"foreignRelations": {',
         coalesce(relationship_struct.relationships.relationships_json, ""),
-      '}'
+      '},',
+      '"partitionColumn": ',
+        coalesce(TO_JSON_STRING(partitioning_data.partition_column), 'null'),
+      ',',
+      '"requirePartitionFilter": ',
+        IF(partition_filter_data.table_name IS NULL, 'false', 'true'),
+      ',',
+      '"clusteringColumns": ',
+        TO_JSON_STRING(coalesce(partitioning_data.clustering_columns, [])),
     '}'
   )), '}') AS result
 FROM columns_struct 
 LEFT JOIN relationship_struct ON columns_struct.table_name = relationship_struct.table_name 
 LEFT JOIN unique_constraint_struct ON columns_struct.table_name = unique_constraint_struct.table_name
+LEFT JOIN partitioning_data ON columns_struct.table_name = partitioning_data.table_name
+LEFT JOIN partition_filter_data ON columns_struct.table_name = partition_filter_data.table_name
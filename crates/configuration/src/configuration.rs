@@ -1,26 +1,147 @@
 //! Configuration for the connector.
 
+use std::path::Path;
+
 use query_engine_metadata::metadata;
+use serde::Deserialize;
+use tokio::fs;
+
+use crate::environment::Environment;
+use crate::error::{ParseConfigurationError, WriteParsedConfigurationError};
+use crate::values::{AuditLogSettings, PoolSettings, QuerySettings};
+use crate::{version1, version2};
 
-use crate::values::PoolSettings;
+pub const CONFIGURATION_FILENAME: &str = "configuration.json";
+const CONFIGURATION_JSONSCHEMA_FILENAME: &str = "schema.json";
 
 /// The 'Configuration' type collects all the information necessary to serve queries at runtime.
 ///
-/// 'ParsedConfiguration' deals with a multitude of different concrete version formats, and each
+/// 'RawConfiguration' deals with a multitude of different concrete version formats, and each
 /// version is responsible for interpreting its serialized format into the current 'Configuration'.
-/// Values of this type are produced from a 'ParsedConfiguration' using
+/// Values of this type are produced from a 'RawConfiguration' using
 /// 'make_runtime_configuration'.
 ///
-/// Separating 'ParsedConfiguration' and 'Configuration' simplifies the main query translation
+/// Separating 'RawConfiguration' and 'Configuration' simplifies the main query translation
 /// logic by placing the responsibility of dealing with configuration format evolution in
-/// 'ParsedConfiguration.
+/// 'RawConfiguration'.
 ///
 #[derive(Debug)]
 pub struct Configuration {
     pub metadata: metadata::Metadata,
     pub pool_settings: PoolSettings,
+    pub query_settings: QuerySettings,
+    pub audit_log: Option<AuditLogSettings>,
     pub service_key: String,
     pub project_id: String,
     pub dataset_id: String,
     // pub mutations_version: Option<metadata::mutations::MutationsVersion>,
 }
+
+/// The configuration of the connector as it is written, in any version of the format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawConfiguration {
+    Version1(version1::ParsedConfiguration),
+    Version2(version2::ParsedConfiguration),
+}
+
+/// The version tag of a configuration, read before the rest of it to tell which version of the
+/// format to parse. Every version is tagged with its number.
+#[derive(Deserialize)]
+struct VersionTag {
+    version: serde_json::Value,
+}
+
+/// Upgrade a configuration to the latest version of the format.
+pub fn upgrade_to_latest_version(raw_config: RawConfiguration) -> version2::ParsedConfiguration {
+    match raw_config {
+        RawConfiguration::Version1(v1) => version2::upgrade_from_v1(v1),
+        RawConfiguration::Version2(v2) => v2,
+    }
+}
+
+/// Construct the deployment configuration by introspecting the database. The configuration is
+/// upgraded to the latest version of the format first.
+pub async fn configure(
+    args: &RawConfiguration,
+    environment: impl Environment,
+) -> anyhow::Result<version2::ParsedConfiguration> {
+    version2::configure(&upgrade_to_latest_version(args.clone()), environment).await
+}
+
+/// Parse the configuration format from a directory, in whichever version it is written.
+pub async fn parse_configuration(
+    configuration_dir: impl AsRef<Path> + Send,
+) -> Result<RawConfiguration, ParseConfigurationError> {
+    let configuration_file = configuration_dir.as_ref().join(CONFIGURATION_FILENAME);
+
+    let configuration_file_contents =
+        fs::read_to_string(&configuration_file)
+            .await
+            .map_err(|err| {
+                ParseConfigurationError::IoErrorButStringified(format!(
+                    "{}: {}",
+                    &configuration_file.display(),
+                    err
+                ))
+            })?;
+
+    let parse_error = |error: serde_json::Error| ParseConfigurationError::ParseError {
+        file_path: configuration_file.clone(),
+        line: error.line(),
+        column: error.column(),
+        message: error.to_string(),
+    };
+
+    let VersionTag { version } =
+        serde_json::from_str(&configuration_file_contents).map_err(parse_error)?;
+    match version {
+        serde_json::Value::Number(number) if number.as_u64() == Some(version1::VERSION.into()) => {
+            serde_json::from_str(&configuration_file_contents)
+                .map(RawConfiguration::Version1)
+                .map_err(parse_error)
+        }
+        serde_json::Value::Number(number) if number.as_u64() == Some(version2::VERSION.into()) => {
+            serde_json::from_str(&configuration_file_contents)
+                .map(RawConfiguration::Version2)
+                .map_err(parse_error)
+        }
+        version => Err(ParseConfigurationError::DidNotFindExpectedVersionTag(
+            version.to_string(),
+        )),
+    }
+}
+
+/// Write the parsed configuration into a directory on disk, in the latest version of the format.
+pub async fn write_parsed_configuration(
+    parsed_config: version2::ParsedConfiguration,
+    out_dir: impl AsRef<Path>,
+) -> Result<(), WriteParsedConfigurationError> {
+    let configuration_file = out_dir.as_ref().to_owned().join(CONFIGURATION_FILENAME);
+    fs::create_dir_all(out_dir.as_ref()).await?;
+
+    // create the configuration file
+    fs::write(
+        configuration_file,
+        serde_json::to_string_pretty(&parsed_config)
+            .map_err(|e| WriteParsedConfigurationError::IoError(e.into()))?
+            + "\n",
+    )
+    .await?;
+
+    // create the jsonschema file
+    let configuration_jsonschema_file_path = out_dir
+        .as_ref()
+        .to_owned()
+        .join(CONFIGURATION_JSONSCHEMA_FILENAME);
+
+    let output = schemars::schema_for!(version2::ParsedConfiguration);
+    fs::write(
+        &configuration_jsonschema_file_path,
+        serde_json::to_string_pretty(&output)
+            .map_err(|e| WriteParsedConfigurationError::IoError(e.into()))?
+            + "\n",
+    )
+    .await?;
+
+    Ok(())
+}
@@ -77,4 +77,9 @@ pub enum MakeRuntimeConfigurationError {
         file_path: std::path::PathBuf,
         message: String,
     },
+    #[error("routine {name} in {file_path} has the same name as a table or native operation")]
+    ConflictingRoutineName {
+        file_path: std::path::PathBuf,
+        name: String,
+    },
 }
@@ -2,22 +2,17 @@ pub mod configuration;
 pub mod connection_settings;
 pub mod environment;
 pub mod error;
+mod routines;
 pub mod to_runtime_configuration;
 pub mod values;
 pub mod version1;
+pub mod version2;
 
-pub use configuration::Configuration;
-pub use values::connection_info::ServiceKey;
-pub use version1::{
-    configure,
-    parse_configuration,
-    write_parsed_configuration,
-    // single_connection_uri, // for tests only
-    // validate_raw_configuration,
-    // Configuration,
-    // ConfigurationError,
-    // PoolSettings,
-    ParsedConfiguration,
+pub use configuration::{
+    configure, parse_configuration, upgrade_to_latest_version, write_parsed_configuration,
+    Configuration, RawConfiguration, CONFIGURATION_FILENAME,
 };
+pub use values::connection_info::ServiceKey;
+pub use version2::ParsedConfiguration;
 
 pub use to_runtime_configuration::make_runtime_configuration;
@@ -0,0 +1,382 @@
+//! Introspection of routines (table functions, scalar SQL functions and procedures).
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use gcp_bigquery_client::model::query_request::QueryRequest;
+use ndc_models as models;
+use serde::Deserialize;
+
+use crate::version2::scalar_type_name;
+use query_engine_metadata::metadata::{self, database};
+
+const ROUTINES_QUERY: &str = include_str!("routines.sql");
+
+/// A routine as described by `INFORMATION_SCHEMA.ROUTINES` and `INFORMATION_SCHEMA.PARAMETERS`.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct IntrospectedRoutine {
+    routine_name: String,
+    routine_type: String,
+    routine_body: Option<String>,
+    data_type: Option<String>,
+    #[serde(default)]
+    parameters: Vec<IntrospectedParameter>,
+}
+
+/// A parameter of an introspected routine.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct IntrospectedParameter {
+    name: Option<String>,
+    data_type: Option<String>,
+    mode: Option<String>,
+}
+
+/// Fetch the routines defined in the dataset.
+pub(crate) async fn introspect_routines(
+    bigquery_client: &gcp_bigquery_client::Client,
+    project_id: &str,
+    database_name: &str,
+    dataset_id: &str,
+) -> anyhow::Result<Vec<IntrospectedRoutine>> {
+    let routines_query = ROUTINES_QUERY
+        .replace("HASURA_DATABASE_NAME_PLACEHOLDER", database_name)
+        .replace("HASURA_DATABASE_SCHEMA_PLACEHOLDER", dataset_id);
+
+    let mut result_set = bigquery_client
+        .job()
+        .query(project_id, QueryRequest::new(routines_query))
+        .await?;
+
+    let mut routines = vec![];
+    while result_set.next_row() {
+        if let Some(routine) = result_set.get_string(0)? {
+            routines.push(serde_json::from_str(&routine)?);
+        }
+    }
+    Ok(routines)
+}
+
+/// The names of the scalar data types used by the routines' arguments and results,
+/// so that they can be added to the scalar types.
+pub(crate) fn data_type_names(routines: &[IntrospectedRoutine]) -> Vec<String> {
+    let mut names = vec![];
+    for routine in routines {
+        let parameter_types = routine
+            .parameters
+            .iter()
+            .filter_map(|parameter| parameter.data_type.as_deref());
+        let column_types = routine
+            .data_type
+            .as_deref()
+            .and_then(table_fields)
+            .into_iter()
+            .flatten()
+            .map(|(_, data_type)| data_type);
+        let result_type = routine
+            .data_type
+            .as_deref()
+            .filter(|data_type| strip_type_constructor(data_type, "TABLE").is_none());
+
+        for data_type in parameter_types.chain(column_types).chain(result_type) {
+            names.push(base_type_name(element_type(data_type)).to_string());
+        }
+    }
+    names
+}
+
+/// Build the routines metadata from the introspected routines.
+/// Routines whose arguments or result we cannot describe are skipped.
+pub(crate) fn get_routines(
+    routines: &[IntrospectedRoutine],
+    schema_name: &str,
+) -> metadata::Routines {
+    let mut result = metadata::Routines::empty();
+
+    for routine in routines {
+        let Some(arguments) = routine_arguments(routine) else {
+            continue;
+        };
+        match routine.routine_type.as_str() {
+            "TABLE FUNCTION" => {
+                let Some(fields) = routine.data_type.as_deref().and_then(table_fields) else {
+                    continue;
+                };
+                let columns = fields
+                    .into_iter()
+                    .map(|(name, data_type)| {
+                        (
+                            name.into(),
+                            metadata::ReadOnlyColumnInfo {
+                                name: name.to_string(),
+                                r#type: data_type_to_type(data_type),
+                                nullable: database::Nullable::Nullable,
+                                description: None,
+                            },
+                        )
+                    })
+                    .collect();
+                result.table_functions.insert(
+                    routine.routine_name.clone().into(),
+                    metadata::TableFunctionInfo {
+                        schema_name: schema_name.to_string(),
+                        function_name: routine.routine_name.clone(),
+                        arguments,
+                        columns,
+                        description: None,
+                    },
+                );
+            }
+            "SCALAR FUNCTION" if routine.routine_body.as_deref() == Some("SQL") => {
+                let Some(return_type) = routine.data_type.as_deref() else {
+                    continue;
+                };
+                result.scalar_functions.insert(
+                    routine.routine_name.clone().into(),
+                    metadata::ScalarFunctionInfo {
+                        schema_name: schema_name.to_string(),
+                        function_name: routine.routine_name.clone(),
+                        arguments,
+                        return_type: data_type_to_type(return_type),
+                        description: None,
+                    },
+                );
+            }
+            // we can only pass input arguments to procedures.
+            "PROCEDURE" if has_only_input_parameters(routine) => {
+                result.procedures.insert(
+                    routine.routine_name.clone().into(),
+                    metadata::ProcedureInfo {
+                        schema_name: schema_name.to_string(),
+                        procedure_name: routine.routine_name.clone(),
+                        arguments,
+                        description: None,
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+
+    result
+}
+
+/// The names of the routines which are also the name of a table or native operation.
+/// Table and scalar functions are queried like tables and native queries, and procedures are
+/// called like native mutations, so a routine with such a name would be shadowed.
+pub(crate) fn conflicting_routine_names(
+    routines: &metadata::Routines,
+    tables: &metadata::TablesInfo,
+    native_operations: &metadata::NativeOperations,
+) -> BTreeSet<String> {
+    let collection_names: BTreeSet<&str> = tables
+        .0
+        .keys()
+        .chain(native_operations.queries.0.keys())
+        .map(models::CollectionName::as_str)
+        .collect();
+    let function_names = routines
+        .table_functions
+        .keys()
+        .map(models::CollectionName::as_str)
+        .chain(
+            routines
+                .scalar_functions
+                .keys()
+                .map(models::FunctionName::as_str),
+        )
+        .filter(|name| collection_names.contains(name));
+    let procedure_names = routines
+        .procedures
+        .keys()
+        .filter(|name| native_operations.mutations.0.contains_key(*name))
+        .map(models::ProcedureName::as_str);
+
+    function_names
+        .chain(procedure_names)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Remove the routines whose names conflict with a table or native operation, with a warning.
+pub(crate) fn remove_conflicting_routines(
+    routines: &mut metadata::Routines,
+    tables: &metadata::TablesInfo,
+    native_operations: &metadata::NativeOperations,
+) {
+    for name in conflicting_routine_names(routines, tables, native_operations) {
+        tracing::warn!(
+            routine = name,
+            "skipping routine with the same name as a table or native operation"
+        );
+        routines.table_functions.remove(name.as_str());
+        routines.scalar_functions.remove(name.as_str());
+        routines.procedures.remove(name.as_str());
+    }
+}
+
+/// Whether all the parameters of a routine are `IN` parameters.
+fn has_only_input_parameters(routine: &IntrospectedRoutine) -> bool {
+    routine
+        .parameters
+        .iter()
+        .all(|parameter| parameter.mode.as_deref().unwrap_or("IN") == "IN")
+}
+
+/// The arguments of a routine, in order. Fails if a parameter has no name.
+fn routine_arguments(routine: &IntrospectedRoutine) -> Option<Vec<metadata::RoutineArgument>> {
+    routine
+        .parameters
+        .iter()
+        .map(|parameter| {
+            Some(metadata::RoutineArgument {
+                name: parameter.name.as_deref()?.into(),
+                r#type: data_type_to_type(parameter.data_type.as_deref().unwrap_or_default()),
+                description: None,
+            })
+        })
+        .collect()
+}
+
+/// Translate a BigQuery data type such as `INT64` or `ARRAY<STRING>` to a metadata type.
+fn data_type_to_type(data_type: &str) -> database::Type {
+    match strip_type_constructor(data_type, "ARRAY") {
+        Some(element_type) => database::Type::ArrayType(Box::new(data_type_to_type(element_type))),
+        None => database::Type::ScalarType(scalar_type_name(base_type_name(data_type)).into()),
+    }
+}
+
+/// The innermost element type of a (possibly nested) array type.
+fn element_type(data_type: &str) -> &str {
+    match strip_type_constructor(data_type, "ARRAY") {
+        Some(element_type) => self::element_type(element_type),
+        None => data_type,
+    }
+}
+
+/// Drop the parameters of a parameterized type, e.g. `NUMERIC(10, 2)` becomes `NUMERIC`.
+fn base_type_name(data_type: &str) -> &str {
+    data_type.split('(').next().unwrap_or(data_type).trim()
+}
+
+/// Strip a type constructor, e.g. `ARRAY<INT64>` becomes `INT64` for the `ARRAY` constructor.
+fn strip_type_constructor<'a>(data_type: &'a str, constructor: &str) -> Option<&'a str> {
+    let data_type = data_type.trim();
+    let prefix = data_type.get(..constructor.len())?;
+    if !prefix.eq_ignore_ascii_case(constructor) {
+        return None;
+    }
+    data_type[constructor.len()..]
+        .trim_start()
+        .strip_prefix('<')?
+        .strip_suffix('>')
+}
+
+/// Parse the fields of a table type such as `TABLE<a INT64, b STRUCT<c STRING, d INT64>>`
+/// into their names and data types.
+fn table_fields(data_type: &str) -> Option<Vec<(&str, &str)>> {
+    let fields = strip_type_constructor(data_type, "TABLE")?;
+
+    // split on the commas which are not nested in another type.
+    let mut depth = 0_usize;
+    let mut start = 0;
+    let mut field_definitions = vec![];
+    for (index, character) in fields.char_indices() {
+        match character {
+            '<' | '(' => depth += 1,
+            '>' | ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                field_definitions.push(&fields[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    field_definitions.push(&fields[start..]);
+
+    field_definitions
+        .into_iter()
+        .map(|field| {
+            let (name, data_type) = field.trim().split_once(char::is_whitespace)?;
+            Some((name.trim_matches('`'), data_type.trim()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing_table_types() {
+        assert_eq!(
+            table_fields("TABLE<id INT64, tags ARRAY<STRING>, price NUMERIC(10, 2)>"),
+            Some(vec![
+                ("id", "INT64"),
+                ("tags", "ARRAY<STRING>"),
+                ("price", "NUMERIC(10, 2)")
+            ])
+        );
+        assert_eq!(table_fields("INT64"), None);
+    }
+
+    #[test]
+    fn test_converting_data_types() {
+        assert_eq!(
+            data_type_to_type("ARRAY<INT64>"),
+            database::Type::ArrayType(Box::new(database::Type::ScalarType("bigint".into())))
+        );
+        assert_eq!(
+            data_type_to_type("STRING(10)"),
+            database::Type::ScalarType("string".into())
+        );
+    }
+
+    #[test]
+    fn test_finding_routines_with_the_names_of_tables_or_native_operations() {
+        let metadata: metadata::Metadata = serde_json::from_value(serde_json::json!({
+            "nativeOperations": { "queries": {}, "mutations": {} },
+            "scalarTypes": {},
+            "tables": {
+                "albums": {
+                    "schemaName": "dataset",
+                    "tableName": "albums",
+                    "columns": {},
+                    "uniquenessConstraints": {},
+                    "foreignRelations": {}
+                }
+            },
+            "routines": {
+                "tableFunctions": {
+                    "albums": {
+                        "schemaName": "dataset",
+                        "functionName": "albums",
+                        "arguments": [],
+                        "columns": {}
+                    },
+                    "top_albums": {
+                        "schemaName": "dataset",
+                        "functionName": "top_albums",
+                        "arguments": [],
+                        "columns": {}
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        let mut routines = metadata.routines;
+
+        assert_eq!(
+            conflicting_routine_names(&routines, &metadata.tables, &metadata.native_operations),
+            BTreeSet::from(["albums".to_string()])
+        );
+
+        remove_conflicting_routines(&mut routines, &metadata.tables, &metadata.native_operations);
+        let table_functions: Vec<&str> = routines
+            .table_functions
+            .keys()
+            .map(models::CollectionName::as_str)
+            .collect();
+        assert_eq!(table_functions, vec!["top_albums"]);
+    }
+}
@@ -0,0 +1,27 @@
+WITH parameters AS (
+  SELECT
+    p.specific_name,
+    ARRAY_AGG(
+      STRUCT(
+        p.parameter_name AS name,
+        p.data_type AS dataType,
+        p.parameter_mode AS mode
+      )
+      ORDER BY p.ordinal_position
+    ) AS parameters
+  FROM HASURA_DATABASE_NAME_PLACEHOLDER.INFORMATION_SCHEMA.PARAMETERS AS p
+  WHERE p.specific_schema = 'HASURA_DATABASE_SCHEMA_PLACEHOLDER'
+    AND p.is_result = 'NO'
+  GROUP BY p.specific_name
+)
+SELECT
+  TO_JSON_STRING(STRUCT(
+    r.routine_name AS routineName,
+    r.routine_type AS routineType,
+    r.routine_body AS routineBody,
+    r.data_type AS dataType,
+    coalesce(p.parameters, []) AS parameters
+  )) AS result
+FROM HASURA_DATABASE_NAME_PLACEHOLDER.INFORMATION_SCHEMA.ROUTINES AS r
+LEFT JOIN parameters AS p ON p.specific_name = r.specific_name
+WHERE r.routine_schema = 'HASURA_DATABASE_SCHEMA_PLACEHOLDER'
@@ -3,25 +3,28 @@
 
 use std::collections::BTreeMap;
 
-use super::version1::ParsedConfiguration;
+use crate::configuration::{upgrade_to_latest_version, RawConfiguration, CONFIGURATION_FILENAME};
 use crate::environment::Environment;
 use crate::error::MakeRuntimeConfigurationError;
+use crate::routines;
 use crate::values::{DatasetId, ProjectId, Secret, ServiceKey};
 use query_engine_metadata::{self, metadata};
 // use crate::VersionTag;
 
 /// Convert the parsed configuration metadata to internal engine metadata
-/// That can be used by the connector at runtime.
+/// That can be used by the connector at runtime. Configurations in older versions of the format
+/// are upgraded to the latest version first.
 pub fn make_runtime_configuration(
-    parsed_config: ParsedConfiguration,
+    raw_config: RawConfiguration,
     environment: impl Environment,
 ) -> Result<crate::Configuration, MakeRuntimeConfigurationError> {
+    let parsed_config = upgrade_to_latest_version(raw_config);
     let service_key = match parsed_config.connection_settings.service_key {
         ServiceKey(Secret::Plain(key)) => Ok(key),
         ServiceKey(Secret::FromEnvironment { variable }) => {
             environment.read(&variable).map_err(|error| {
                 MakeRuntimeConfigurationError::MissingEnvironmentVariable {
-                    file_path: super::version1::CONFIGURATION_FILENAME.into(),
+                    file_path: CONFIGURATION_FILENAME.into(),
                     message: error.to_string(),
                 }
             })
@@ -33,7 +36,7 @@ pub fn make_runtime_configuration(
         ProjectId(Secret::FromEnvironment { variable }) => {
             environment.read(&variable).map_err(|error| {
                 MakeRuntimeConfigurationError::MissingEnvironmentVariable {
-                    file_path: super::version1::CONFIGURATION_FILENAME.into(),
+                    file_path: CONFIGURATION_FILENAME.into(),
                     message: error.to_string(),
                 }
             })
@@ -44,15 +47,30 @@ pub fn make_runtime_configuration(
         DatasetId(Secret::FromEnvironment { variable }) => {
             environment.read(&variable).map_err(|error| {
                 MakeRuntimeConfigurationError::MissingEnvironmentVariable {
-                    file_path: super::version1::CONFIGURATION_FILENAME.into(),
+                    file_path: CONFIGURATION_FILENAME.into(),
                     message: error.to_string(),
                 }
             })
         }
     }?;
+    let metadata = &parsed_config.metadata;
+    if let Some(name) = routines::conflicting_routine_names(
+        &metadata.routines,
+        &metadata.tables,
+        &metadata.native_operations,
+    )
+    .pop_first()
+    {
+        return Err(MakeRuntimeConfigurationError::ConflictingRoutineName {
+            file_path: CONFIGURATION_FILENAME.into(),
+            name,
+        });
+    }
     Ok(crate::Configuration {
         metadata: convert_metadata(parsed_config.metadata),
-        pool_settings: parsed_config.pool_settings,
+        pool_settings: parsed_config.pool_settings.into(),
+        query_settings: parsed_config.query_settings,
+        audit_log: parsed_config.audit_log,
         service_key,
         project_id,
         dataset_id,
@@ -68,6 +86,8 @@ pub fn convert_metadata(metadata: metadata::Metadata) -> query_engine_metadata::
         scalar_types: convert_scalar_types(metadata.scalar_types),
         // composite_types: convert_composite_types(metadata.composite_types),
         native_operations: convert_native_operations(metadata.native_operations),
+        routines: metadata.routines,
+        request_parameters: metadata.request_parameters,
     }
 }
 
@@ -148,6 +168,7 @@ fn convert_native_query_info(
             .map(|(k, v)| (k, convert_read_only_column_info(v)))
             .collect(),
         description: native_query_info.description,
+        volatile: native_query_info.volatile,
     }
 }
 
@@ -411,6 +432,10 @@ fn convert_table_info(
         uniqueness_constraints: (table_info.uniqueness_constraints),
         foreign_relations: convert_foreign_relations(table_info.foreign_relations),
         description: table_info.description,
+        partition_column: table_info.partition_column,
+        require_partition_filter: table_info.require_partition_filter,
+        clustering_columns: table_info.clustering_columns,
+        is_wildcard: table_info.is_wildcard,
     }
 }
 
@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Settings of the audit log, which records one JSON line per request: the request, the SQL
+/// it ran, the BigQuery jobs running it and what they cost, and its outcome.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogSettings {
+    /// Where to write the audit log.
+    pub destination: AuditLogDestination,
+    /// Replace the literal values of requests (arguments, comparison values and variables)
+    /// with a placeholder.
+    #[serde(default = "redact_literals_default")]
+    pub redact_literals: bool,
+}
+
+/// Where to write the audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditLogDestination {
+    /// Write to the standard output.
+    Stdout,
+    /// Append to a file, which is created if it does not exist.
+    File(PathBuf),
+}
+
+// for serde default //
+fn redact_literals_default() -> bool {
+    true
+}
@@ -0,0 +1,18 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Options which control how the database is introspected.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct IntrospectionOptions {
+    /// Group date-sharded tables (e.g. `events_20240101`, `events_20240102`, ...) into a single
+    /// wildcard collection (`events_*`), whose columns are taken from the latest shard.
+    #[serde(default)]
+    pub group_sharded_tables: bool,
+}
+
+impl IntrospectionOptions {
+    pub fn is_default(&self) -> bool {
+        *self == IntrospectionOptions::default()
+    }
+}
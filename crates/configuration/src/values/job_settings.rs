@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Settings of the BigQuery jobs running queries.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct JobSettings {
+    /// Fail queries which would bill more bytes than this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maximum_bytes_billed: Option<u64>,
+    /// Whether to look for results in the query cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_query_cache: Option<bool>,
+    /// The location where jobs run, e.g. `US` or `europe-west2`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// Cancel jobs which run for longer than this, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_timeout_ms: Option<u64>,
+    /// The priority of jobs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<JobPriority>,
+    /// Labels attached to jobs. In values, `{collection}` is replaced by the name of the
+    /// collection being queried, and `{request_id}` by a unique identifier of the request.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl JobSettings {
+    pub fn is_default(&self) -> bool {
+        *self == JobSettings::default()
+    }
+
+    /// These settings, with the settings which are set in `overrides` replacing them.
+    /// Labels are merged.
+    #[must_use]
+    pub fn overridden_by(&self, overrides: &JobSettings) -> JobSettings {
+        let mut labels = self.labels.clone();
+        labels.extend(overrides.labels.clone());
+        JobSettings {
+            maximum_bytes_billed: overrides.maximum_bytes_billed.or(self.maximum_bytes_billed),
+            use_query_cache: overrides.use_query_cache.or(self.use_query_cache),
+            location: overrides.location.clone().or_else(|| self.location.clone()),
+            job_timeout_ms: overrides.job_timeout_ms.or(self.job_timeout_ms),
+            priority: overrides.priority.or(self.priority),
+            labels,
+        }
+    }
+}
+
+/// The priority of a BigQuery job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum JobPriority {
+    /// Run queries as soon as possible.
+    Interactive,
+    /// Queue queries until resources are available. Batch queries do not count towards the
+    /// concurrent rate limit.
+    Batch,
+}
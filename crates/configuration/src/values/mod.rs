@@ -1,7 +1,19 @@
+mod audit_log;
 pub mod connection_info;
+mod introspection_options;
+mod job_settings;
 mod pool_settings;
+mod query_settings;
 mod secret;
 
+pub use audit_log::{AuditLogDestination, AuditLogSettings};
 pub use connection_info::{DatasetId, ProjectId, ServiceKey};
+pub use introspection_options::IntrospectionOptions;
+pub use job_settings::{JobPriority, JobSettings};
 pub use pool_settings::PoolSettings;
+pub use query_settings::{
+    DryRunSettings, ForeachSettings, ForeachStrategy, ParameterRedaction, PlanCacheSettings,
+    QuerySettings, ResponseCacheSettings, RetrySettings, StorageReadMode, StorageReadSettings,
+    TracingSettings,
+};
 pub use secret::Secret;
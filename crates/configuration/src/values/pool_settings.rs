@@ -1,21 +1,15 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-/// Settings for the BigQuery connection pool
+/// Settings limiting the BigQuery jobs the connector runs at once.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PoolSettings {
-    /// maximum number of pool connections
+    /// maximum number of BigQuery jobs running at once
     #[serde(default = "max_connection_default")]
     pub max_connections: u32,
-    /// timeout for acquiring a connection from the pool (seconds)
+    /// timeout for a job waiting for the others to complete before it can run (seconds)
     #[serde(default = "pool_timeout_default")]
     pub pool_timeout: u64,
-    /// idle timeout for releasing a connection from the pool (seconds)
-    #[serde(default = "idle_timeout_default")]
-    pub idle_timeout: Option<u64>,
-    /// maximum lifetime for an individual connection (seconds)
-    #[serde(default = "connection_lifetime_default")]
-    pub connection_lifetime: Option<u64>,
 }
 
 impl PoolSettings {
@@ -30,8 +24,6 @@ impl Default for PoolSettings {
         PoolSettings {
             max_connections: 50,
             pool_timeout: 600,
-            idle_timeout: Some(180),
-            connection_lifetime: Some(600),
         }
     }
 }
@@ -43,9 +35,3 @@ fn max_connection_default() -> u32 {
 fn pool_timeout_default() -> u64 {
     PoolSettings::default().pool_timeout
 }
-fn idle_timeout_default() -> Option<u64> {
-    PoolSettings::default().idle_timeout
-}
-fn connection_lifetime_default() -> Option<u64> {
-    PoolSettings::default().connection_lifetime
-}
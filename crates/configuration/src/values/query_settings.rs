@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::JobSettings;
+
+/// Settings which control how the results of queries are read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QuerySettings {
+    /// The maximum number of rows to fetch in each page of results. BigQuery chooses the
+    /// page size when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_size: Option<u32>,
+    /// The maximum number of rows a query may return. Queries returning more rows fail.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rows: Option<u64>,
+    /// The maximum size of the response to a query, in bytes. Queries whose response would
+    /// be larger fail.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_result_size: Option<u64>,
+    /// Cancel queries which do not complete within this many seconds, including the time
+    /// taken to read their results. Queries which time out fail.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+    /// Read large results through the BigQuery Storage Read API.
+    #[serde(default, skip_serializing_if = "StorageReadSettings::is_default")]
+    pub storage_read: StorageReadSettings,
+    /// Settings of the BigQuery jobs running queries.
+    #[serde(default, skip_serializing_if = "JobSettings::is_default")]
+    pub job_settings: JobSettings,
+    /// Settings of the BigQuery jobs running queries on particular collections, which override
+    /// `jobSettings`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub collection_job_settings: BTreeMap<String, JobSettings>,
+    /// Dry-run queries before running them, to enforce a budget of bytes processed.
+    #[serde(default, skip_serializing_if = "DryRunSettings::is_default")]
+    pub dry_run: DryRunSettings,
+    /// Retry requests to BigQuery which fail transiently.
+    #[serde(default, skip_serializing_if = "RetrySettings::is_default")]
+    pub retry: RetrySettings,
+    /// What to include in traces about the SQL statements we run.
+    #[serde(default, skip_serializing_if = "TracingSettings::is_default")]
+    pub tracing: TracingSettings,
+    /// Cache the plans of queries, by the shape of their requests.
+    #[serde(default, skip_serializing_if = "PlanCacheSettings::is_default")]
+    pub plan_cache: PlanCacheSettings,
+    /// Cache the responses to queries in memory.
+    #[serde(default, skip_serializing_if = "ResponseCacheSettings::is_default")]
+    pub response_cache: ResponseCacheSettings,
+    /// How queries are run on their sets of variables, e.g. for remote relationships.
+    #[serde(default, skip_serializing_if = "ForeachSettings::is_default")]
+    pub foreach: ForeachSettings,
+}
+
+impl QuerySettings {
+    pub fn is_default(&self) -> bool {
+        *self == QuerySettings::default()
+    }
+
+    /// The settings of the jobs running queries on a collection.
+    pub fn job_settings_for(&self, collection: &str) -> JobSettings {
+        match self.collection_job_settings.get(collection) {
+            None => self.job_settings.clone(),
+            Some(overrides) => self.job_settings.overridden_by(overrides),
+        }
+    }
+}
+
+/// Settings which control how queries are dry-run before they are run. A dry run estimates
+/// the number of bytes a query would process, without running it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunSettings {
+    /// Dry-run every query, and reject the queries which would process more bytes than this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maximum_bytes_processed: Option<u64>,
+    /// Cache the result of dry runs for this many seconds, for each SQL statement and set of
+    /// parameters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_ttl_seconds: Option<u64>,
+}
+
+impl DryRunSettings {
+    pub fn is_default(&self) -> bool {
+        *self == DryRunSettings::default()
+    }
+}
+
+/// Settings which control how requests to BigQuery which fail transiently (because a quota or
+/// rate limit was reached, or because of an error in BigQuery) are retried, with exponential
+/// backoff and jitter. Queries are always safe to retry; procedure calls are retried with the
+/// same request id, so that they run at most once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RetrySettings {
+    /// The maximum number of attempts of a request, including the first one. Set it to 1 to
+    /// disable retries.
+    #[serde(default = "max_attempts_default")]
+    pub max_attempts: u32,
+    /// The backoff before the first retry, in milliseconds. It doubles with each retry.
+    #[serde(default = "initial_backoff_ms_default")]
+    pub initial_backoff_ms: u64,
+    /// The maximum backoff between two attempts, in milliseconds.
+    #[serde(default = "max_backoff_ms_default")]
+    pub max_backoff_ms: u64,
+    /// Do not retry a request once this many seconds have passed since its first attempt.
+    #[serde(default = "deadline_seconds_default")]
+    pub deadline_seconds: Option<u64>,
+}
+
+impl RetrySettings {
+    pub fn is_default(&self) -> bool {
+        *self == RetrySettings::default()
+    }
+}
+
+impl Default for RetrySettings {
+    fn default() -> RetrySettings {
+        RetrySettings {
+            max_attempts: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 8_000,
+            deadline_seconds: Some(30),
+        }
+    }
+}
+
+/// Settings of the cache of query plans. Requests which differ only by the values they compare
+/// columns with have the same shape, and share the same plan, which binds these values as
+/// parameters. The least recently used plans are evicted when the cache is full.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanCacheSettings {
+    /// The maximum number of plans in the cache. Set it to 0 to disable the cache.
+    #[serde(default = "plan_cache_capacity_default")]
+    pub capacity: usize,
+}
+
+impl PlanCacheSettings {
+    pub fn is_default(&self) -> bool {
+        *self == PlanCacheSettings::default()
+    }
+}
+
+impl Default for PlanCacheSettings {
+    fn default() -> PlanCacheSettings {
+        PlanCacheSettings { capacity: 1024 }
+    }
+}
+
+/// Settings of the cache of the responses to queries, keyed by their SQL, their parameters, and
+/// the configuration. Responses are never cached for queries reading tables as of a point in
+/// time (`as_of`) or volatile native queries, nor for requests forwarding a `Cache-Control:
+/// no-cache` or `no-store` header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseCacheSettings {
+    /// Cache the responses to queries for this many seconds. Responses are not cached when
+    /// unset, unless their collection has its own time to live.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<u64>,
+    /// The number of seconds the responses to queries on particular collections are cached,
+    /// which overrides `ttlSeconds`. Set it to 0 to not cache the responses of a collection.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub collection_ttl_seconds: BTreeMap<String, u64>,
+    /// The maximum total size of the cached responses, in bytes. The responses closest to
+    /// expiring are evicted to make room for new ones.
+    #[serde(default = "max_size_bytes_default")]
+    pub max_size_bytes: u64,
+}
+
+impl ResponseCacheSettings {
+    pub fn is_default(&self) -> bool {
+        *self == ResponseCacheSettings::default()
+    }
+
+    /// Whether the responses of some collections are cached.
+    pub fn is_enabled(&self) -> bool {
+        self.ttl_seconds.is_some() || !self.collection_ttl_seconds.is_empty()
+    }
+
+    /// The number of seconds the responses to queries on a collection are cached, if they
+    /// are.
+    pub fn ttl_seconds_for(&self, collection: &str) -> Option<u64> {
+        self.collection_ttl_seconds
+            .get(collection)
+            .copied()
+            .or(self.ttl_seconds)
+            .filter(|ttl_seconds| *ttl_seconds > 0)
+    }
+}
+
+impl Default for ResponseCacheSettings {
+    fn default() -> ResponseCacheSettings {
+        ResponseCacheSettings {
+            ttl_seconds: None,
+            collection_ttl_seconds: BTreeMap::new(),
+            max_size_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Settings which control how queries are run on their sets of variables.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ForeachSettings {
+    /// How the sets of variables are split between queries.
+    #[serde(default)]
+    pub strategy: ForeachStrategy,
+    /// In `chunked` mode, the maximum number of sets of variables of each query.
+    #[serde(default = "chunk_size_default")]
+    pub chunk_size: usize,
+    /// In `chunked` mode, the maximum number of queries to run in parallel.
+    #[serde(default = "max_concurrency_default")]
+    pub max_concurrency: usize,
+}
+
+impl ForeachSettings {
+    pub fn is_default(&self) -> bool {
+        *self == ForeachSettings::default()
+    }
+}
+
+impl Default for ForeachSettings {
+    fn default() -> ForeachSettings {
+        ForeachSettings {
+            strategy: ForeachStrategy::default(),
+            chunk_size: 100,
+            max_concurrency: 4,
+        }
+    }
+}
+
+/// How the sets of variables of a query are split between queries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ForeachStrategy {
+    /// Run a single query on every set of variables.
+    #[default]
+    Batched,
+    /// Run a query on each chunk of sets of variables, in parallel.
+    Chunked,
+}
+
+/// Settings which control what is included in traces about the SQL statements we run. The
+/// SQL and its parameters are attached to the span of each BigQuery job at debug level.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TracingSettings {
+    /// How the values of query parameters are redacted.
+    #[serde(default)]
+    pub parameter_redaction: ParameterRedaction,
+}
+
+impl TracingSettings {
+    pub fn is_default(&self) -> bool {
+        *self == TracingSettings::default()
+    }
+}
+
+/// How the values of query parameters, which may hold personal data, are redacted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ParameterRedaction {
+    /// Include the names, types and values of parameters.
+    None,
+    /// Include the names and types of parameters, but not their values.
+    #[default]
+    Values,
+    /// Leave parameters out entirely.
+    All,
+}
+
+/// Settings which control when the results of queries are read through the BigQuery Storage
+/// Read API, which streams the table holding the results of a query in parallel, instead of
+/// paging through them with `getQueryResults`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageReadSettings {
+    /// When to use the Storage Read API.
+    #[serde(default)]
+    pub mode: StorageReadMode,
+    /// In `automatic` mode, the number of rows above which results are read through the
+    /// Storage Read API.
+    #[serde(default = "row_threshold_default")]
+    pub row_threshold: u64,
+    /// The maximum number of streams to read in parallel.
+    #[serde(default = "max_streams_default")]
+    pub max_streams: u32,
+}
+
+impl StorageReadSettings {
+    pub fn is_default(&self) -> bool {
+        *self == StorageReadSettings::default()
+    }
+}
+
+impl Default for StorageReadSettings {
+    fn default() -> StorageReadSettings {
+        StorageReadSettings {
+            mode: StorageReadMode::default(),
+            row_threshold: 100_000,
+            max_streams: 4,
+        }
+    }
+}
+
+/// When to read the results of queries through the Storage Read API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageReadMode {
+    /// Always page through the results with `getQueryResults`.
+    #[default]
+    Disabled,
+    /// Use the Storage Read API when a query returns more rows than the threshold.
+    Automatic,
+    /// Always use the Storage Read API.
+    Always,
+}
+
+// for serde default //
+fn row_threshold_default() -> u64 {
+    StorageReadSettings::default().row_threshold
+}
+fn max_streams_default() -> u32 {
+    StorageReadSettings::default().max_streams
+}
+fn max_attempts_default() -> u32 {
+    RetrySettings::default().max_attempts
+}
+fn initial_backoff_ms_default() -> u64 {
+    RetrySettings::default().initial_backoff_ms
+}
+fn max_backoff_ms_default() -> u64 {
+    RetrySettings::default().max_backoff_ms
+}
+fn deadline_seconds_default() -> Option<u64> {
+    RetrySettings::default().deadline_seconds
+}
+fn plan_cache_capacity_default() -> usize {
+    PlanCacheSettings::default().capacity
+}
+fn max_size_bytes_default() -> u64 {
+    ResponseCacheSettings::default().max_size_bytes
+}
+fn chunk_size_default() -> usize {
+    ForeachSettings::default().chunk_size
+}
+fn max_concurrency_default() -> usize {
+    ForeachSettings::default().max_concurrency
+}
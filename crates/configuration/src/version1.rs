@@ -1,52 +1,17 @@
-//! Internal Configuration and state for our connector.
+//! Version 1 of the configuration format.
+//!
+//! Version 1 configurations are still read, and upgraded to the latest version when the
+//! connector starts, or by the `upgrade` command of the CLI.
 
 use crate::connection_settings;
-use crate::environment::Environment;
-use crate::error::WriteParsedConfigurationError;
-use crate::values::{DatasetId, PoolSettings, ProjectId, Secret, ServiceKey};
+use crate::values::{self, AuditLogSettings, IntrospectionOptions, QuerySettings};
 
-use super::error::ParseConfigurationError;
-use gcp_bigquery_client::model::query_request::QueryRequest;
-use gcp_bigquery_client::model::table_cell::TableCell;
-use gcp_bigquery_client::model::table_row::TableRow;
-use ndc_models::{AggregateFunctionName, ComparisonOperatorName, ScalarTypeName, TypeName};
+use query_engine_metadata::metadata;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
-use std::collections::BTreeMap;
-use std::path::Path;
-use tokio::fs;
 
-//TODO(PY): temp, needs to be removed from the crate
-// use ndc_sdk::connector;
-
-use query_engine_metadata::metadata::{self, database, TablesInfo};
-
-const CURRENT_VERSION: u32 = 1;
-pub const CONFIGURATION_FILENAME: &str = "configuration.json";
-pub const DEFAULT_SERVICE_KEY_VARIABLE: &str = "HASURA_BIGQUERY_SERVICE_KEY";
-pub const DEFAULT_PROJECT_ID_VARIABLE: &str = "HASURA_BIGQUERY_PROJECT_ID";
-pub const DEFAULT_DATASET_ID_VARIABLE: &str = "HASURA_BIGQUERY_DATASET_ID";
-const CONFIGURATION_QUERY: &str = include_str!("config2.sql");
-const CONFIGURATION_JSONSCHEMA_FILENAME: &str = "schema.json";
-
-const CHARACTER_STRINGS: [&str; 3] = ["character", "text", "string"];
-const UNICODE_CHARACTER_STRINGS: [&str; 3] = ["nchar", "ntext", "nvarchar"];
-const CANNOT_COMPARE: [&str; 3] = ["text", "ntext", "image"];
-const EXACT_NUMERICS: [&str; 9] = [
-    "bigint",
-    "bit",
-    "decimal",
-    "int",
-    "money",
-    "numeric",
-    "smallint",
-    "smallmoney",
-    "tinyint",
-];
-const APPROX_NUMERICS: [&str; 3] = ["float", "real", "float64"];
-const NOT_COUNTABLE: [&str; 3] = ["image", "ntext", "text"];
-const NOT_APPROX_COUNTABLE: [&str; 4] = ["image", "sql_variant", "ntext", "text"];
+/// The version tag of version 1.
+pub const VERSION: u32 = 1;
 
 /// Initial configuration, just enough to connect to a database and elaborate a full
 /// 'Configuration'.
@@ -59,475 +24,53 @@ pub struct ParsedConfiguration {
     #[serde(skip_serializing_if = "PoolSettings::is_default")]
     #[serde(default)]
     pub pool_settings: PoolSettings,
+    #[serde(skip_serializing_if = "IntrospectionOptions::is_default")]
+    #[serde(default)]
+    pub introspection_options: IntrospectionOptions,
+    #[serde(skip_serializing_if = "QuerySettings::is_default")]
+    #[serde(default)]
+    pub query_settings: QuerySettings,
+    /// Write an audit log of the requests served by the connector.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub audit_log: Option<AuditLogSettings>,
     #[serde(default)]
     pub metadata: metadata::Metadata,
     // #[serde(default)]
     // pub aggregate_functions: metadata::AggregateFunctions,
 }
 
-impl ParsedConfiguration {
-    pub fn initial() -> Self {
-        ParsedConfiguration::empty()
-    }
-}
-
-#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize, JsonSchema)]
-pub enum Version {
-    #[serde(rename = "1")]
-    This,
+/// The pool settings of version 1, named in snake_case. They include settings for the idle
+/// timeout and lifetime of connections, which are not used: the BigQuery client manages its
+/// own HTTP connections.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PoolSettings {
+    #[serde(flatten)]
+    pub settings: values::PoolSettings,
+    /// idle timeout for connections (seconds), not used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub idle_timeout: Option<u64>,
+    /// maximum lifetime of connections (seconds), not used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub connection_lifetime: Option<u64>,
 }
 
-impl ParsedConfiguration {
-    pub fn empty() -> Self {
-        Self {
-            version: CURRENT_VERSION,
-            connection_settings: connection_settings::DatabaseConnectionSettings::empty(),
-            pool_settings: PoolSettings::default(),
-            metadata: metadata::Metadata::default(),
-            // aggregate_functions: metadata::AggregateFunctions::default(),
-        }
+impl PoolSettings {
+    pub fn is_default(&self) -> bool {
+        *self == PoolSettings::default()
     }
-}
-
-/// Construct the deployment configuration by introspecting the database.
-pub async fn configure(
-    args: &ParsedConfiguration,
-    environment: impl Environment,
-) -> anyhow::Result<ParsedConfiguration> {
-    let service_key = match &args.connection_settings.service_key {
-        ServiceKey(Secret::Plain(value)) => Cow::Borrowed(value),
-        ServiceKey(Secret::FromEnvironment { variable }) => Cow::Owned(environment.read(variable)?),
-    };
-
-    let project_id_ = match &args.connection_settings.project_id {
-        ProjectId(Secret::Plain(value)) => Cow::Borrowed(value),
-        ProjectId(Secret::FromEnvironment { variable }) => Cow::Owned(environment.read(variable)?),
-    };
-
-    let dataset_id_ = match &args.connection_settings.dataset_id {
-        DatasetId(Secret::Plain(value)) => Cow::Borrowed(value),
-        DatasetId(Secret::FromEnvironment { variable }) => Cow::Owned(environment.read(variable)?),
-    };
-
-    let service_account_key = yup_oauth2::parse_service_account_key(service_key.as_str()).unwrap();
-
-    let project_id = project_id_.as_str();
-    let dataset_id = dataset_id_.as_str();
-
-    let schema_name = format!("{project_id}.{dataset_id}");
-    let database_name = schema_name.clone();
 
-    // Init BigQuery client
-    let bigquery_client =
-        gcp_bigquery_client::Client::from_service_account_key(service_account_key, false)
-            .await
-            .unwrap();
-
-    // get scalar_types
-
-    let types_query = format!(
-        "select coalesce(data_type, '') as data_type from {project_id}.{dataset_id}.INFORMATION_SCHEMA.COLUMN_FIELD_PATHS"
-    );
-
-    let types_row = bigquery_client
-        .job()
-        .query(project_id, QueryRequest::new(types_query))
-        .await
-        .unwrap();
-
-    let types_query_response = types_row.query_response().clone();
-    let empty_tablerow = vec![TableRow::default()];
-    let empty_tablecell = &vec![TableCell::default()];
-    let empty_string_value = &serde_json::Value::String(String::new());
-
-    // let types_query = types_query_response.rows.unwrap_or_default();
-
-    //TODO(PY): too many unwraps!
-    let types = types_query_response
-        .rows
-        .as_ref()
-        .unwrap_or_else(|| empty_tablerow.as_ref())
-        .iter()
-        .map(|row| TypeItem {
-            name: serde_json::from_value(
-                row.columns
-                    .as_ref()
-                    .unwrap_or(empty_tablecell)
-                    .iter()
-                    .next()
-                    .unwrap()
-                    .value
-                    .as_ref()
-                    .unwrap_or(empty_string_value)
-                    .to_owned(),
-            )
-            .unwrap(),
-        })
-        .collect::<Vec<_>>();
-
-    let scalar_types = get_scalar_types(&types, schema_name);
-
-    // get tables_info
-
-    let config_query_string = CONFIGURATION_QUERY.to_string();
-
-    let config_query_string_with_database_name: String =
-        config_query_string.replace("HASURA_DATABASE_NAME_PLACEHOLDER", database_name.as_str()); //TODO(PY): what is a safe name to provide as a variable name?
-
-    let config_query_with_schema_name = config_query_string_with_database_name
-        .replace("HASURA_DATABASE_SCHEMA_PLACEHOLDER", dataset_id);
-
-    let tables_query_request = QueryRequest::new(config_query_with_schema_name);
-
-    let tables_result = bigquery_client
-        .job()
-        .query(project_id, tables_query_request)
-        .await
-        .unwrap();
-
-    let table_rows = tables_result.query_response().clone();
-
-    let mut tables_info = TablesInfo::empty();
-
-    for row in table_rows.rows.unwrap_or_default() {
-        let configuration_table_info = if let Some(columns) = row.columns {
-            if let Some(column) = columns.into_iter().next() {
-                if let Some(value) = column.value {
-                    if let serde_json::Value::String(str) = value {
-                        if let Ok(table_info_map) = serde_json::from_str::<TablesInfo>(&str) {
-                            // tables_info.merge(table_info_map);
-                            Ok(table_info_map)
-                        } else {
-                            Err(format!("Failed to deserialize TablesInfo from JSON: {str}"))
-                        }
-                    } else {
-                        Err(format!("Expected a string value, found: {value:?}"))
-                    }
-                } else {
-                    Err("Missing value in columns".to_string())
-                }
-            } else {
-                Err("Empty columns".to_string())
-            }
-        } else {
-            Err("Empty rows".to_string())
-        };
-        if let Ok(table_info_map) = configuration_table_info {
-            tables_info.merge(table_info_map);
+    /// The names of the settings which are set but not used.
+    pub fn unused_settings(&self) -> Vec<&'static str> {
+        let mut unused_settings = vec![];
+        if self.idle_timeout.is_some() {
+            unused_settings.push("idle_timeout");
         }
+        if self.connection_lifetime.is_some() {
+            unused_settings.push("connection_lifetime");
+        }
+        unused_settings
     }
-
-    Ok(ParsedConfiguration {
-        version: 1,
-        connection_settings: connection_settings::DatabaseConnectionSettings {
-            service_key: args.connection_settings.service_key.clone(),
-            project_id: args.connection_settings.project_id.clone(),
-            dataset_id: args.connection_settings.dataset_id.clone(),
-        },
-        pool_settings: args.pool_settings.clone(),
-        metadata: metadata::Metadata {
-            tables: tables_info,
-            native_operations: args.metadata.native_operations.clone(),
-            scalar_types,
-            // composite_types: CompositeTypes::empty(),
-        },
-        // aggregate_functions,
-    })
-}
-
-/// Parse the configuration format from a directory.
-pub async fn parse_configuration(
-    configuration_dir: impl AsRef<Path> + Send,
-) -> Result<ParsedConfiguration, ParseConfigurationError> {
-    let configuration_file = configuration_dir.as_ref().join(CONFIGURATION_FILENAME);
-
-    let configuration_file_contents =
-        fs::read_to_string(&configuration_file)
-            .await
-            .map_err(|err| {
-                ParseConfigurationError::IoErrorButStringified(format!(
-                    "{}: {}",
-                    &configuration_file.display(),
-                    err
-                ))
-            })?;
-
-    let parsed_config: ParsedConfiguration = serde_json::from_str(&configuration_file_contents)
-        .map_err(|error| ParseConfigurationError::ParseError {
-            file_path: configuration_file.clone(),
-            line: error.line(),
-            column: error.column(),
-            message: error.to_string(),
-        })?;
-
-    Ok(parsed_config)
-}
-
-/// Write the parsed configuration into a directory on disk.
-pub async fn write_parsed_configuration(
-    parsed_config: ParsedConfiguration,
-    out_dir: impl AsRef<Path>,
-) -> Result<(), WriteParsedConfigurationError> {
-    let configuration_file = out_dir.as_ref().to_owned().join(CONFIGURATION_FILENAME);
-    fs::create_dir_all(out_dir.as_ref()).await?;
-
-    // create the configuration file
-    fs::write(
-        configuration_file,
-        serde_json::to_string_pretty(&parsed_config)
-            .map_err(|e| WriteParsedConfigurationError::IoError(e.into()))?
-            + "\n",
-    )
-    .await?;
-
-    // create the jsonschema file
-    let configuration_jsonschema_file_path = out_dir
-        .as_ref()
-        .to_owned()
-        .join(CONFIGURATION_JSONSCHEMA_FILENAME);
-
-    let output = schemars::schema_for!(ParsedConfiguration);
-    fs::write(
-        &configuration_jsonschema_file_path,
-        serde_json::to_string_pretty(&output)
-            .map_err(|e| WriteParsedConfigurationError::IoError(e.into()))?
-            + "\n",
-    )
-    .await?;
-
-    Ok(())
-}
-
-#[derive(Deserialize, Debug)]
-struct TypeItem {
-    name: ScalarTypeName,
-}
-
-// we hard code these, essentially
-// we look up available types in `sys.types` but hard code their behaviour by looking them up below
-// taken from https://learn.microsoft.com/en-us/sql/t-sql/functions/aggregate-functions-transact-sql?view=sql-server-ver16
-fn get_aggregate_functions_for_type(
-    type_name: &ndc_models::ScalarTypeName,
-) -> BTreeMap<AggregateFunctionName, database::AggregateFunction> {
-    let mut aggregate_functions = BTreeMap::new();
-
-    if !NOT_APPROX_COUNTABLE.contains(&type_name.as_str()) {
-        aggregate_functions.insert(
-            AggregateFunctionName::new("APPROX_COUNT_DISTINCT".into()),
-            database::AggregateFunction {
-                return_type: TypeName::new("bigint".to_string().into()),
-            },
-        );
-    }
-
-    if !NOT_COUNTABLE.contains(&type_name.as_str()) {
-        aggregate_functions.insert(
-            AggregateFunctionName::new("COUNT".into()),
-            database::AggregateFunction {
-                return_type: TypeName::new("bigint".to_string().into()),
-            },
-        );
-    }
-
-    if type_name.as_str() != "bit"
-        && (EXACT_NUMERICS.contains(&type_name.as_str())
-            || APPROX_NUMERICS.contains(&type_name.as_str())
-            || CHARACTER_STRINGS.contains(&type_name.as_str())
-            || type_name.as_str() == "date"
-            || type_name.as_str() == "datetime"
-            || type_name.as_str() == "uuid")
-    {
-        aggregate_functions.insert(
-            AggregateFunctionName::new("MIN".into()),
-            database::AggregateFunction {
-                return_type: TypeName::new(type_name.as_str().to_string().into()),
-            },
-        );
-        aggregate_functions.insert(
-            AggregateFunctionName::new("MAX".into()),
-            database::AggregateFunction {
-                return_type: TypeName::new(type_name.as_str().to_string().into()),
-            },
-        );
-    }
-
-    if let Some(precise_return_type) = match type_name.as_str() {
-        "tinyint" | "smallint" | "int16" => Some("smallint"),
-        "int" | "int32" => Some("integer"),
-        "bigint" | "int64" => Some("bigint"),
-        "float" | "real" => Some("float"),
-        _ => None,
-    } {
-        aggregate_functions.insert(
-            AggregateFunctionName::new("AVG".into()),
-            database::AggregateFunction {
-                return_type: TypeName::new(precise_return_type.to_string().into()),
-            },
-        );
-        aggregate_functions.insert(
-            AggregateFunctionName::new("SUM".into()),
-            database::AggregateFunction {
-                return_type: TypeName::new(precise_return_type.to_string().into()),
-            },
-        );
-    };
-
-    aggregate_functions
-}
-
-// we lookup all types in sys.types, then use our hardcoded ideas about each one to attach
-// comparison operators
-fn get_scalar_types(type_names: &Vec<TypeItem>, schema_name: String) -> database::ScalarTypes {
-    let mut scalar_types = BTreeMap::new();
-    let schema = if schema_name.is_empty() {
-        None
-    } else {
-        Some(schema_name)
-    };
-
-    for type_item in type_names {
-        let type_name = match type_item.name.as_str().to_lowercase().as_str() {
-            "bool" | "boolean" => "boolean",
-            "int16" | "smallint" => "smallint",
-            "int" | "int32" | "integer" => "integer",
-            "int64" | "bigint" => "bigint",
-            "numeric" => "numeric",
-            "float64" | "float" => "float",
-            "real" => "real",
-            "double precision" => "double precision",
-            "text" => "text",
-            "string" => "string",
-            "character" => "character",
-            "json" => "json",
-            "jsonb" => "jsonb",
-            "date" => "date",
-            "timetz" | "time with time zone" => "timetz",
-            "time" | "time without time zone" => "time",
-            "timestamptz" | "timestamp with time zone" => "timestamptz",
-            "timestamp" | "timestamp without time zone" => "timestamp",
-            "uuid" => "uuid",
-            _ => "any",
-        };
-        let type_name_scalar = ScalarTypeName::new(type_name.into());
-        scalar_types.insert(
-            type_name_scalar.clone(),
-            database::ScalarType {
-                type_name: type_name_scalar.clone(),
-                schema_name: schema.clone(),
-                comparison_operators: get_comparison_operators_for_type(&type_name_scalar),
-                aggregate_functions: get_aggregate_functions_for_type(&type_name_scalar),
-                description: None,
-                type_representation: None,
-            },
-            // get_comparison_operators_for_type(&type_name.name),
-        );
-    }
-
-    database::ScalarTypes(scalar_types)
-}
-
-// we hard code these, essentially
-// we look up available types in `sys.types` but hard code their behaviour by looking them up below
-// categories taken from https://learn.microsoft.com/en-us/sql/t-sql/data-types/data-types-transact-sql
-fn get_comparison_operators_for_type(
-    type_name: &ndc_models::ScalarTypeName,
-) -> BTreeMap<ComparisonOperatorName, database::ComparisonOperator> {
-    let mut comparison_operators = BTreeMap::new();
-
-    // in ndc-spec, all things can be `==`
-    comparison_operators.insert(
-        ComparisonOperatorName::new("_eq".into()),
-        database::ComparisonOperator {
-            operator_name: "=".to_string(),
-            argument_type: type_name.clone(),
-            operator_kind: database::OperatorKind::Equal,
-            is_infix: true,
-        },
-    );
-
-    comparison_operators.insert(
-        ComparisonOperatorName::new("_in".into()),
-        database::ComparisonOperator {
-            operator_name: "IN".to_string(),
-            argument_type: type_name.clone(),
-            operator_kind: database::OperatorKind::In,
-            is_infix: true,
-        },
-    );
-
-    // include LIKE and NOT LIKE for string-ish types
-    if CHARACTER_STRINGS.contains(&type_name.as_str())
-        || UNICODE_CHARACTER_STRINGS.contains(&type_name.as_str())
-    {
-        comparison_operators.insert(
-            ComparisonOperatorName::new("_like".into()),
-            database::ComparisonOperator {
-                operator_name: "LIKE".to_string(),
-                argument_type: type_name.clone(),
-                operator_kind: database::OperatorKind::Custom,
-                is_infix: true,
-            },
-        );
-        comparison_operators.insert(
-            ComparisonOperatorName::new("_nlike".into()),
-            database::ComparisonOperator {
-                operator_name: "NOT LIKE".to_string(),
-                argument_type: type_name.clone(),
-                operator_kind: database::OperatorKind::Custom,
-                is_infix: true,
-            },
-        );
-    }
-
-    // include comparison operators for types that are comparable, according to
-    // https://learn.microsoft.com/en-us/sql/t-sql/language-elements/comparison-operators-transact-sql?view=sql-server-ver16
-    if !CANNOT_COMPARE.contains(&type_name.as_str()) {
-        comparison_operators.insert(
-            ComparisonOperatorName::new("_neq".into()),
-            database::ComparisonOperator {
-                operator_name: "!=".to_string(),
-                argument_type: type_name.clone(),
-                operator_kind: database::OperatorKind::Custom,
-                is_infix: true,
-            },
-        );
-        comparison_operators.insert(
-            ComparisonOperatorName::new("_lt".into()),
-            database::ComparisonOperator {
-                operator_name: "<".to_string(),
-                argument_type: type_name.clone(),
-                operator_kind: database::OperatorKind::Custom,
-                is_infix: true,
-            },
-        );
-        comparison_operators.insert(
-            ComparisonOperatorName::new("_gt".into()),
-            database::ComparisonOperator {
-                operator_name: ">".to_string(),
-                argument_type: type_name.clone(),
-                operator_kind: database::OperatorKind::Custom,
-                is_infix: true,
-            },
-        );
-
-        comparison_operators.insert(
-            ComparisonOperatorName::new("_gte".into()),
-            database::ComparisonOperator {
-                operator_name: ">=".to_string(),
-                argument_type: type_name.clone(),
-                operator_kind: database::OperatorKind::Custom,
-                is_infix: true,
-            },
-        );
-        comparison_operators.insert(
-            ComparisonOperatorName::new("_lte".into()),
-            database::ComparisonOperator {
-                operator_name: "<=".to_string(),
-                argument_type: type_name.clone(),
-                operator_kind: database::OperatorKind::Custom,
-                is_infix: true,
-            },
-        );
-    }
-    comparison_operators
 }
@@ -0,0 +1,822 @@
+//! Version 2 of the configuration format, and the introspection of the database filling it in.
+//!
+//! Version 2 names the pool settings in camelCase, like every other setting, and drops the pool
+//! settings of version 1 which are not used.
+
+use crate::connection_settings;
+use crate::environment::Environment;
+use crate::routines;
+use crate::values::{
+    self, AuditLogSettings, DatasetId, IntrospectionOptions, ProjectId, QuerySettings, Secret,
+    ServiceKey,
+};
+use crate::version1;
+
+use gcp_bigquery_client::model::query_request::QueryRequest;
+use gcp_bigquery_client::model::table_cell::TableCell;
+use gcp_bigquery_client::model::table_row::TableRow;
+use ndc_models::{AggregateFunctionName, ComparisonOperatorName, ScalarTypeName, TypeName};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use query_engine_metadata::metadata::{self, database, TablesInfo};
+
+/// The version tag of version 2.
+pub const VERSION: u32 = 2;
+
+const CONFIGURATION_QUERY: &str = include_str!("config2.sql");
+
+const CHARACTER_STRINGS: [&str; 3] = ["character", "text", "string"];
+const UNICODE_CHARACTER_STRINGS: [&str; 3] = ["nchar", "ntext", "nvarchar"];
+const CANNOT_COMPARE: [&str; 3] = ["text", "ntext", "image"];
+const EXACT_NUMERICS: [&str; 9] = [
+    "bigint",
+    "bit",
+    "decimal",
+    "int",
+    "money",
+    "numeric",
+    "smallint",
+    "smallmoney",
+    "tinyint",
+];
+const APPROX_NUMERICS: [&str; 3] = ["float", "real", "float64"];
+const NOT_COUNTABLE: [&str; 3] = ["image", "ntext", "text"];
+const NOT_APPROX_COUNTABLE: [&str; 4] = ["image", "sql_variant", "ntext", "text"];
+
+/// Initial configuration, just enough to connect to a database and elaborate a full
+/// 'Configuration'.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedConfiguration {
+    // Which version of the configuration format are we using
+    pub version: u32,
+    pub connection_settings: connection_settings::DatabaseConnectionSettings,
+    #[serde(skip_serializing_if = "PoolSettings::is_default")]
+    #[serde(default)]
+    pub pool_settings: PoolSettings,
+    #[serde(skip_serializing_if = "IntrospectionOptions::is_default")]
+    #[serde(default)]
+    pub introspection_options: IntrospectionOptions,
+    #[serde(skip_serializing_if = "QuerySettings::is_default")]
+    #[serde(default)]
+    pub query_settings: QuerySettings,
+    /// Write an audit log of the requests served by the connector.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub audit_log: Option<AuditLogSettings>,
+    #[serde(default)]
+    pub metadata: metadata::Metadata,
+}
+
+/// Settings limiting the BigQuery jobs the connector runs at once.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct PoolSettings {
+    /// maximum number of BigQuery jobs running at once
+    pub max_connections: u32,
+    /// timeout for a job waiting for the others to complete before it can run (seconds)
+    pub pool_timeout: u64,
+}
+
+impl PoolSettings {
+    pub fn is_default(&self) -> bool {
+        *self == PoolSettings::default()
+    }
+}
+
+impl Default for PoolSettings {
+    fn default() -> PoolSettings {
+        values::PoolSettings::default().into()
+    }
+}
+
+impl From<values::PoolSettings> for PoolSettings {
+    fn from(pool_settings: values::PoolSettings) -> PoolSettings {
+        let values::PoolSettings {
+            max_connections,
+            pool_timeout,
+        } = pool_settings;
+        PoolSettings {
+            max_connections,
+            pool_timeout,
+        }
+    }
+}
+
+impl From<PoolSettings> for values::PoolSettings {
+    fn from(pool_settings: PoolSettings) -> values::PoolSettings {
+        let PoolSettings {
+            max_connections,
+            pool_timeout,
+        } = pool_settings;
+        values::PoolSettings {
+            max_connections,
+            pool_timeout,
+        }
+    }
+}
+
+impl ParsedConfiguration {
+    pub fn initial() -> Self {
+        ParsedConfiguration::empty()
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            version: VERSION,
+            connection_settings: connection_settings::DatabaseConnectionSettings::empty(),
+            pool_settings: PoolSettings::default(),
+            introspection_options: IntrospectionOptions::default(),
+            query_settings: QuerySettings::default(),
+            audit_log: None,
+            metadata: metadata::Metadata::default(),
+        }
+    }
+}
+
+/// Upgrade a version 1 configuration to version 2. Every setting is carried over as it is, so
+/// the upgraded configuration serves the same schema and runs the same queries, except for the
+/// unused pool settings of version 1, which are dropped: see
+/// [`version1::PoolSettings::unused_settings`].
+pub fn upgrade_from_v1(v1: version1::ParsedConfiguration) -> ParsedConfiguration {
+    let version1::ParsedConfiguration {
+        version: _,
+        connection_settings,
+        pool_settings,
+        introspection_options,
+        query_settings,
+        audit_log,
+        metadata,
+    } = v1;
+    ParsedConfiguration {
+        version: VERSION,
+        connection_settings,
+        pool_settings: pool_settings.settings.into(),
+        introspection_options,
+        query_settings,
+        audit_log,
+        metadata,
+    }
+}
+
+/// Construct the deployment configuration by introspecting the database.
+pub async fn configure(
+    args: &ParsedConfiguration,
+    environment: impl Environment,
+) -> anyhow::Result<ParsedConfiguration> {
+    let service_key = match &args.connection_settings.service_key {
+        ServiceKey(Secret::Plain(value)) => Cow::Borrowed(value),
+        ServiceKey(Secret::FromEnvironment { variable }) => Cow::Owned(environment.read(variable)?),
+    };
+
+    let project_id_ = match &args.connection_settings.project_id {
+        ProjectId(Secret::Plain(value)) => Cow::Borrowed(value),
+        ProjectId(Secret::FromEnvironment { variable }) => Cow::Owned(environment.read(variable)?),
+    };
+
+    let dataset_id_ = match &args.connection_settings.dataset_id {
+        DatasetId(Secret::Plain(value)) => Cow::Borrowed(value),
+        DatasetId(Secret::FromEnvironment { variable }) => Cow::Owned(environment.read(variable)?),
+    };
+
+    let service_account_key = yup_oauth2::parse_service_account_key(service_key.as_str()).unwrap();
+
+    let project_id = project_id_.as_str();
+    let dataset_id = dataset_id_.as_str();
+
+    let schema_name = format!("{project_id}.{dataset_id}");
+    let database_name = schema_name.clone();
+
+    // Init BigQuery client
+    let bigquery_client =
+        gcp_bigquery_client::Client::from_service_account_key(service_account_key, false)
+            .await
+            .unwrap();
+
+    // get scalar_types
+
+    let types_query = format!(
+        "select coalesce(data_type, '') as data_type from {project_id}.{dataset_id}.INFORMATION_SCHEMA.COLUMN_FIELD_PATHS"
+    );
+
+    let types_row = bigquery_client
+        .job()
+        .query(project_id, QueryRequest::new(types_query))
+        .await
+        .unwrap();
+
+    let types_query_response = types_row.query_response().clone();
+    let empty_tablerow = vec![TableRow::default()];
+    let empty_tablecell = &vec![TableCell::default()];
+    let empty_string_value = &serde_json::Value::String(String::new());
+
+    // let types_query = types_query_response.rows.unwrap_or_default();
+
+    //TODO(PY): too many unwraps!
+    let mut types = types_query_response
+        .rows
+        .as_ref()
+        .unwrap_or_else(|| empty_tablerow.as_ref())
+        .iter()
+        .map(|row| TypeItem {
+            name: serde_json::from_value(
+                row.columns
+                    .as_ref()
+                    .unwrap_or(empty_tablecell)
+                    .iter()
+                    .next()
+                    .unwrap()
+                    .value
+                    .as_ref()
+                    .unwrap_or(empty_string_value)
+                    .to_owned(),
+            )
+            .unwrap(),
+        })
+        .collect::<Vec<_>>();
+
+    // get routines, whose argument and result types must be available as scalar types too
+
+    let introspected_routines =
+        routines::introspect_routines(&bigquery_client, project_id, &database_name, dataset_id)
+            .await?;
+    let mut routines = routines::get_routines(&introspected_routines, &schema_name);
+    types.extend(
+        routines::data_type_names(&introspected_routines)
+            .into_iter()
+            .map(|name| TypeItem { name: name.into() }),
+    );
+
+    let scalar_types = get_scalar_types(&types, schema_name);
+
+    // get tables_info
+
+    let config_query_string = CONFIGURATION_QUERY.to_string();
+
+    let config_query_string_with_database_name: String =
+        config_query_string.replace("HASURA_DATABASE_NAME_PLACEHOLDER", database_name.as_str()); //TODO(PY): what is a safe name to provide as a variable name?
+
+    let config_query_with_schema_name = config_query_string_with_database_name
+        .replace("HASURA_DATABASE_SCHEMA_PLACEHOLDER", dataset_id);
+
+    let tables_query_request = QueryRequest::new(config_query_with_schema_name);
+
+    let tables_result = bigquery_client
+        .job()
+        .query(project_id, tables_query_request)
+        .await
+        .unwrap();
+
+    let table_rows = tables_result.query_response().clone();
+
+    let mut tables_info = TablesInfo::empty();
+
+    for row in table_rows.rows.unwrap_or_default() {
+        let configuration_table_info = if let Some(columns) = row.columns {
+            if let Some(column) = columns.into_iter().next() {
+                if let Some(value) = column.value {
+                    if let serde_json::Value::String(str) = value {
+                        if let Ok(table_info_map) = serde_json::from_str::<TablesInfo>(&str) {
+                            // tables_info.merge(table_info_map);
+                            Ok(table_info_map)
+                        } else {
+                            Err(format!("Failed to deserialize TablesInfo from JSON: {str}"))
+                        }
+                    } else {
+                        Err(format!("Expected a string value, found: {value:?}"))
+                    }
+                } else {
+                    Err("Missing value in columns".to_string())
+                }
+            } else {
+                Err("Empty columns".to_string())
+            }
+        } else {
+            Err("Empty rows".to_string())
+        };
+        if let Ok(table_info_map) = configuration_table_info {
+            tables_info.merge(table_info_map);
+        }
+    }
+
+    if args.introspection_options.group_sharded_tables {
+        tables_info = group_sharded_tables(tables_info);
+    }
+
+    routines::remove_conflicting_routines(
+        &mut routines,
+        &tables_info,
+        &args.metadata.native_operations,
+    );
+
+    Ok(ParsedConfiguration {
+        version: VERSION,
+        connection_settings: connection_settings::DatabaseConnectionSettings {
+            service_key: args.connection_settings.service_key.clone(),
+            project_id: args.connection_settings.project_id.clone(),
+            dataset_id: args.connection_settings.dataset_id.clone(),
+        },
+        pool_settings: args.pool_settings.clone(),
+        introspection_options: args.introspection_options.clone(),
+        query_settings: args.query_settings.clone(),
+        audit_log: args.audit_log.clone(),
+        metadata: metadata::Metadata {
+            tables: tables_info,
+            native_operations: args.metadata.native_operations.clone(),
+            scalar_types,
+            routines,
+            request_parameters: args.metadata.request_parameters.clone(),
+            // composite_types: CompositeTypes::empty(),
+        },
+        // aggregate_functions,
+    })
+}
+
+/// Group date-sharded tables (`events_20240101`, `events_20240102`, ...) into a single
+/// wildcard table (`events_*`). The wildcard table takes its columns from the latest shard.
+///
+/// A wildcard table reads every table whose name starts with its prefix, so shards are left
+/// as they are when other tables share their prefix (such as `events_archive`). The keys of a
+/// shard do not hold across shards, so the wildcard table has no uniqueness constraints or
+/// foreign keys.
+fn group_sharded_tables(tables: TablesInfo) -> TablesInfo {
+    let table_names: Vec<String> = tables
+        .0
+        .values()
+        .map(|table_info| table_info.table_name.clone())
+        .collect();
+
+    let mut grouped = TablesInfo::empty();
+    // The shards of each wildcard, by prefix.
+    let mut shards: BTreeMap<String, Vec<(ndc_models::CollectionName, database::TableInfo)>> =
+        BTreeMap::new();
+    for (collection_name, table_info) in tables.0 {
+        match split_shard_suffix(&table_info.table_name) {
+            Some((prefix, _)) => shards
+                .entry(prefix.to_string())
+                .or_default()
+                .push((collection_name, table_info)),
+            None => {
+                grouped.0.insert(collection_name, table_info);
+            }
+        }
+    }
+
+    for (prefix, shards) in shards {
+        let reads_other_tables = table_names.iter().any(|table_name| {
+            table_name.starts_with(&prefix)
+                && split_shard_suffix(table_name).map(|(shard_prefix, _)| shard_prefix)
+                    != Some(prefix.as_str())
+        });
+        if reads_other_tables {
+            grouped.0.extend(shards);
+            continue;
+        }
+        // shards share their prefix, so the latest shard has the greatest name.
+        let Some((_, latest_shard)) = shards
+            .into_iter()
+            .max_by(|(_, left), (_, right)| left.table_name.cmp(&right.table_name))
+        else {
+            continue;
+        };
+        let wildcard_name = format!("{prefix}*");
+        grouped.0.insert(
+            wildcard_name.clone().into(),
+            database::TableInfo {
+                table_name: wildcard_name,
+                uniqueness_constraints: database::UniquenessConstraints(BTreeMap::new()),
+                foreign_relations: database::ForeignRelations(BTreeMap::new()),
+                is_wildcard: true,
+                ..latest_shard
+            },
+        );
+    }
+
+    grouped
+}
+
+/// Split a date-sharded table name such as `events_20240101` into
+/// its prefix (`events_`) and its `YYYYMMDD` suffix (`20240101`).
+fn split_shard_suffix(table_name: &str) -> Option<(&str, &str)> {
+    const SUFFIX_LENGTH: usize = "YYYYMMDD".len();
+    let split_at = table_name.len().checked_sub(SUFFIX_LENGTH)?;
+    if split_at == 0 || !table_name.is_char_boundary(split_at) {
+        return None;
+    }
+    let (prefix, suffix) = table_name.split_at(split_at);
+    if !suffix.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let month: u32 = suffix[4..6].parse().ok()?;
+    let day: u32 = suffix[6..8].parse().ok()?;
+    ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some((prefix, suffix))
+}
+
+#[derive(Deserialize, Debug)]
+struct TypeItem {
+    name: ScalarTypeName,
+}
+
+// we hard code these, essentially
+// we look up available types in `sys.types` but hard code their behaviour by looking them up below
+// taken from https://learn.microsoft.com/en-us/sql/t-sql/functions/aggregate-functions-transact-sql?view=sql-server-ver16
+fn get_aggregate_functions_for_type(
+    type_name: &ndc_models::ScalarTypeName,
+) -> BTreeMap<AggregateFunctionName, database::AggregateFunction> {
+    let mut aggregate_functions = BTreeMap::new();
+
+    if !NOT_APPROX_COUNTABLE.contains(&type_name.as_str()) {
+        aggregate_functions.insert(
+            AggregateFunctionName::new("APPROX_COUNT_DISTINCT".into()),
+            database::AggregateFunction {
+                return_type: TypeName::new("bigint".to_string().into()),
+            },
+        );
+    }
+
+    if !NOT_COUNTABLE.contains(&type_name.as_str()) {
+        aggregate_functions.insert(
+            AggregateFunctionName::new("COUNT".into()),
+            database::AggregateFunction {
+                return_type: TypeName::new("bigint".to_string().into()),
+            },
+        );
+    }
+
+    if type_name.as_str() != "bit"
+        && (EXACT_NUMERICS.contains(&type_name.as_str())
+            || APPROX_NUMERICS.contains(&type_name.as_str())
+            || CHARACTER_STRINGS.contains(&type_name.as_str())
+            || type_name.as_str() == "date"
+            || type_name.as_str() == "datetime"
+            || type_name.as_str() == "uuid")
+    {
+        aggregate_functions.insert(
+            AggregateFunctionName::new("MIN".into()),
+            database::AggregateFunction {
+                return_type: TypeName::new(type_name.as_str().to_string().into()),
+            },
+        );
+        aggregate_functions.insert(
+            AggregateFunctionName::new("MAX".into()),
+            database::AggregateFunction {
+                return_type: TypeName::new(type_name.as_str().to_string().into()),
+            },
+        );
+    }
+
+    if let Some(precise_return_type) = match type_name.as_str() {
+        "tinyint" | "smallint" | "int16" => Some("smallint"),
+        "int" | "int32" => Some("integer"),
+        "bigint" | "int64" => Some("bigint"),
+        "float" | "real" => Some("float"),
+        _ => None,
+    } {
+        aggregate_functions.insert(
+            AggregateFunctionName::new("AVG".into()),
+            database::AggregateFunction {
+                return_type: TypeName::new(precise_return_type.to_string().into()),
+            },
+        );
+        aggregate_functions.insert(
+            AggregateFunctionName::new("SUM".into()),
+            database::AggregateFunction {
+                return_type: TypeName::new(precise_return_type.to_string().into()),
+            },
+        );
+    };
+
+    aggregate_functions
+}
+
+/// Map a BigQuery data type to the name of the scalar type we expose for it.
+pub(crate) fn scalar_type_name(data_type: &str) -> &'static str {
+    match data_type.to_lowercase().as_str() {
+        "bool" | "boolean" => "boolean",
+        "int16" | "smallint" => "smallint",
+        "int" | "int32" | "integer" => "integer",
+        "int64" | "bigint" => "bigint",
+        "numeric" => "numeric",
+        "float64" | "float" => "float",
+        "real" => "real",
+        "double precision" => "double precision",
+        "text" => "text",
+        "string" => "string",
+        "character" => "character",
+        "json" => "json",
+        "jsonb" => "jsonb",
+        "date" => "date",
+        "timetz" | "time with time zone" => "timetz",
+        "time" | "time without time zone" => "time",
+        "timestamptz" | "timestamp with time zone" => "timestamptz",
+        "timestamp" | "timestamp without time zone" => "timestamp",
+        "uuid" => "uuid",
+        _ => "any",
+    }
+}
+
+// we lookup all types in sys.types, then use our hardcoded ideas about each one to attach
+// comparison operators
+fn get_scalar_types(type_names: &Vec<TypeItem>, schema_name: String) -> database::ScalarTypes {
+    let mut scalar_types = BTreeMap::new();
+    let schema = if schema_name.is_empty() {
+        None
+    } else {
+        Some(schema_name)
+    };
+
+    for type_item in type_names {
+        let type_name = scalar_type_name(type_item.name.as_str());
+        let type_name_scalar = ScalarTypeName::new(type_name.into());
+        scalar_types.insert(
+            type_name_scalar.clone(),
+            database::ScalarType {
+                type_name: type_name_scalar.clone(),
+                schema_name: schema.clone(),
+                comparison_operators: get_comparison_operators_for_type(&type_name_scalar),
+                aggregate_functions: get_aggregate_functions_for_type(&type_name_scalar),
+                description: None,
+                type_representation: type_representation(type_name),
+            },
+            // get_comparison_operators_for_type(&type_name.name),
+        );
+    }
+
+    database::ScalarTypes(scalar_types)
+}
+
+/// How the values of a scalar type are represented in query results. 64-bit integers and
+/// decimals are cast to strings, so that they do not lose precision, whether the rows are
+/// selected as JSON or as typed columns.
+fn type_representation(type_name: &str) -> Option<database::TypeRepresentation> {
+    match type_name {
+        "bigint" => Some(database::TypeRepresentation::Int64AsString),
+        "numeric" => Some(database::TypeRepresentation::BigDecimalAsString),
+        _ => None,
+    }
+}
+
+// we hard code these, essentially
+// we look up available types in `sys.types` but hard code their behaviour by looking them up below
+// categories taken from https://learn.microsoft.com/en-us/sql/t-sql/data-types/data-types-transact-sql
+fn get_comparison_operators_for_type(
+    type_name: &ndc_models::ScalarTypeName,
+) -> BTreeMap<ComparisonOperatorName, database::ComparisonOperator> {
+    let mut comparison_operators = BTreeMap::new();
+
+    // in ndc-spec, all things can be `==`
+    comparison_operators.insert(
+        ComparisonOperatorName::new("_eq".into()),
+        database::ComparisonOperator {
+            operator_name: "=".to_string(),
+            argument_type: type_name.clone(),
+            operator_kind: database::OperatorKind::Equal,
+            is_infix: true,
+        },
+    );
+
+    comparison_operators.insert(
+        ComparisonOperatorName::new("_in".into()),
+        database::ComparisonOperator {
+            operator_name: "IN".to_string(),
+            argument_type: type_name.clone(),
+            operator_kind: database::OperatorKind::In,
+            is_infix: true,
+        },
+    );
+
+    // include LIKE and NOT LIKE for string-ish types
+    if CHARACTER_STRINGS.contains(&type_name.as_str())
+        || UNICODE_CHARACTER_STRINGS.contains(&type_name.as_str())
+    {
+        comparison_operators.insert(
+            ComparisonOperatorName::new("_like".into()),
+            database::ComparisonOperator {
+                operator_name: "LIKE".to_string(),
+                argument_type: type_name.clone(),
+                operator_kind: database::OperatorKind::Custom,
+                is_infix: true,
+            },
+        );
+        comparison_operators.insert(
+            ComparisonOperatorName::new("_nlike".into()),
+            database::ComparisonOperator {
+                operator_name: "NOT LIKE".to_string(),
+                argument_type: type_name.clone(),
+                operator_kind: database::OperatorKind::Custom,
+                is_infix: true,
+            },
+        );
+    }
+
+    // include comparison operators for types that are comparable, according to
+    // https://learn.microsoft.com/en-us/sql/t-sql/language-elements/comparison-operators-transact-sql?view=sql-server-ver16
+    if !CANNOT_COMPARE.contains(&type_name.as_str()) {
+        comparison_operators.insert(
+            ComparisonOperatorName::new("_neq".into()),
+            database::ComparisonOperator {
+                operator_name: "!=".to_string(),
+                argument_type: type_name.clone(),
+                operator_kind: database::OperatorKind::Custom,
+                is_infix: true,
+            },
+        );
+        comparison_operators.insert(
+            ComparisonOperatorName::new("_lt".into()),
+            database::ComparisonOperator {
+                operator_name: "<".to_string(),
+                argument_type: type_name.clone(),
+                operator_kind: database::OperatorKind::Custom,
+                is_infix: true,
+            },
+        );
+        comparison_operators.insert(
+            ComparisonOperatorName::new("_gt".into()),
+            database::ComparisonOperator {
+                operator_name: ">".to_string(),
+                argument_type: type_name.clone(),
+                operator_kind: database::OperatorKind::Custom,
+                is_infix: true,
+            },
+        );
+
+        comparison_operators.insert(
+            ComparisonOperatorName::new("_gte".into()),
+            database::ComparisonOperator {
+                operator_name: ">=".to_string(),
+                argument_type: type_name.clone(),
+                operator_kind: database::OperatorKind::Custom,
+                is_infix: true,
+            },
+        );
+        comparison_operators.insert(
+            ComparisonOperatorName::new("_lte".into()),
+            database::ComparisonOperator {
+                operator_name: "<=".to_string(),
+                argument_type: type_name.clone(),
+                operator_kind: database::OperatorKind::Custom,
+                is_infix: true,
+            },
+        );
+    }
+    comparison_operators
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn table(table_name: &str, column_name: &str) -> database::TableInfo {
+        database::TableInfo {
+            schema_name: "project.dataset".to_string(),
+            table_name: table_name.to_string(),
+            columns: BTreeMap::from([(
+                column_name.into(),
+                database::ColumnInfo {
+                    name: column_name.to_string(),
+                    r#type: database::Type::ScalarType("string".into()),
+                    nullable: database::Nullable::Nullable,
+                    has_default: database::HasDefault::NoDefault,
+                    is_identity: database::IsIdentity::NotIdentity,
+                    is_generated: database::IsGenerated::NotGenerated,
+                    description: None,
+                },
+            )]),
+            uniqueness_constraints: database::UniquenessConstraints(BTreeMap::new()),
+            foreign_relations: database::ForeignRelations(BTreeMap::new()),
+            description: None,
+            partition_column: None,
+            require_partition_filter: false,
+            clustering_columns: vec![],
+            is_wildcard: false,
+        }
+    }
+
+    #[test]
+    fn test_upgrading_from_v1_drops_the_unused_pool_settings() {
+        let v1: version1::ParsedConfiguration = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "connectionSettings": {
+                "serviceKey": { "variable": "HASURA_BIGQUERY_SERVICE_KEY" },
+                "projectId": "project",
+                "datasetId": "dataset"
+            },
+            "poolSettings": {
+                "max_connections": 10,
+                "idle_timeout": 120,
+                "connection_lifetime": 300
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            v1.pool_settings.unused_settings(),
+            vec!["idle_timeout", "connection_lifetime"]
+        );
+
+        let v2 = upgrade_from_v1(v1);
+        assert_eq!(v2.version, VERSION);
+        assert_eq!(
+            v2.pool_settings,
+            PoolSettings {
+                max_connections: 10,
+                pool_timeout: 600,
+            }
+        );
+    }
+
+    #[test]
+    fn test_splitting_shard_suffixes() {
+        assert_eq!(
+            split_shard_suffix("events_20240101"),
+            Some(("events_", "20240101"))
+        );
+        assert_eq!(split_shard_suffix("events"), None);
+        assert_eq!(split_shard_suffix("events_2024010a"), None);
+        assert_eq!(split_shard_suffix("20240101"), None);
+        assert_eq!(split_shard_suffix("events_20241301"), None);
+        assert_eq!(split_shard_suffix("events_20240132"), None);
+        assert_eq!(split_shard_suffix("events_12345678"), None);
+    }
+
+    #[test]
+    fn test_grouping_sharded_tables_uses_the_latest_shard() {
+        let tables = TablesInfo(BTreeMap::from([
+            (
+                "events_20240102".into(),
+                table("events_20240102", "new_column"),
+            ),
+            (
+                "events_20240101".into(),
+                table("events_20240101", "old_column"),
+            ),
+            ("users".into(), table("users", "id")),
+        ]));
+
+        let grouped = group_sharded_tables(tables);
+
+        assert_eq!(grouped.0.len(), 2);
+        assert_eq!(grouped.0.get("users"), Some(&table("users", "id")));
+        let wildcard = grouped.0.get("events_*").unwrap();
+        assert!(wildcard.is_wildcard);
+        assert_eq!(wildcard.table_name, "events_*");
+        assert!(wildcard.columns.contains_key("new_column"));
+    }
+
+    #[test]
+    fn test_grouping_sharded_tables_leaves_tables_sharing_their_prefix() {
+        let tables = TablesInfo(BTreeMap::from([
+            ("events_20240101".into(), table("events_20240101", "name")),
+            ("events_20240102".into(), table("events_20240102", "name")),
+            ("events_archive".into(), table("events_archive", "name")),
+            ("logs_20240101".into(), table("logs_20240101", "message")),
+        ]));
+
+        let grouped = group_sharded_tables(tables);
+
+        let collections: Vec<&str> = grouped
+            .0
+            .keys()
+            .map(ndc_models::CollectionName::as_str)
+            .collect();
+        assert_eq!(
+            collections,
+            vec![
+                "events_20240101",
+                "events_20240102",
+                "events_archive",
+                "logs_*"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grouping_sharded_tables_drops_their_keys() {
+        let mut shard = table("events_20240101", "user_id");
+        shard.uniqueness_constraints = database::UniquenessConstraints(BTreeMap::from([(
+            "events_pkey".to_string(),
+            database::UniquenessConstraint(BTreeSet::from(["user_id".into()])),
+        )]));
+        shard.foreign_relations = database::ForeignRelations(BTreeMap::from([(
+            "events_user_id_fkey".to_string(),
+            database::ForeignRelation {
+                foreign_schema: None,
+                foreign_table: "users".to_string(),
+                column_mapping: BTreeMap::from([("user_id".into(), "id".into())]),
+            },
+        )]));
+
+        let grouped = group_sharded_tables(TablesInfo(BTreeMap::from([(
+            "events_20240101".into(),
+            shard,
+        )])));
+
+        let wildcard = grouped.0.get("events_*").unwrap();
+        assert!(wildcard.uniqueness_constraints.0.is_empty());
+        assert!(wildcard.foreign_relations.0.is_empty());
+    }
+}
@@ -0,0 +1,433 @@
+//! Record which requests ran which BigQuery SQL, and what it cost.
+//!
+//! The audit log has one JSON line per request, holding the normalized request, the SQL
+//! statements it ran and the types of their parameters, the BigQuery jobs running them and the
+//! bytes they billed, how long the request took, and whether it succeeded, failed or was
+//! cancelled. The literal values of requests can be redacted, so that the log does not hold user
+//! data: they are then bound as parameters in the statements the requests run, whose values are
+//! redacted in the log.
+
+use std::io;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use gcp_bigquery_client::model::query_request::QueryRequest;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+use ndc_bigquery_configuration::values::{AuditLogDestination, AuditLogSettings};
+use query_engine_execution::query::CompletedJob;
+use query_engine_execution::trace;
+
+/// The placeholder replacing redacted values.
+const REDACTED: &str = "<redacted>";
+
+/// Where audit log entries are written.
+#[derive(Clone)]
+pub struct AuditLog {
+    writer: Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>>,
+    redact_literals: bool,
+}
+
+impl AuditLog {
+    /// Open the destination of the audit log.
+    pub async fn new(settings: &AuditLogSettings) -> io::Result<AuditLog> {
+        let writer: Box<dyn AsyncWrite + Send + Unpin> = match &settings.destination {
+            AuditLogDestination::Stdout => Box::new(tokio::io::stdout()),
+            AuditLogDestination::File(path) => Box::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?,
+            ),
+        };
+        Ok(AuditLog {
+            writer: Arc::new(Mutex::new(writer)),
+            redact_literals: settings.redact_literals,
+        })
+    }
+
+    /// Whether the literal values of requests are redacted. The statements of requests should
+    /// then bind every literal as a parameter.
+    pub fn redacts_literals(&self) -> bool {
+        self.redact_literals
+    }
+
+    /// Write a line, without blocking the runtime. Lines are written one at a time.
+    async fn write_line(&self, line: &Value) {
+        let line = format!("{line}\n");
+        let mut writer = self.writer.lock().await;
+        let written = async {
+            writer.write_all(line.as_bytes()).await?;
+            writer.flush().await
+        }
+        .await;
+        if let Err(err) = written {
+            tracing::error!("unable to write to the audit log: {err}");
+        }
+    }
+}
+
+/// The audit log entry of a request, filled in as the request runs, and written when it is
+/// finished. A request dropped before it finishes (e.g. because the client disconnected) is
+/// logged as cancelled. It does nothing if there is no audit log.
+pub struct AuditEntry {
+    entry: Option<Entry>,
+}
+
+struct Entry {
+    log: AuditLog,
+    started: Instant,
+    timestamp_ms: u128,
+    request_id: String,
+    collection: String,
+    request: Value,
+    statements: Vec<Value>,
+    jobs: Vec<CompletedJob>,
+    error: Option<String>,
+}
+
+impl AuditEntry {
+    /// Start the entry of a request on a collection. The collection of a mutation is the
+    /// comma-separated list of the procedures it calls.
+    pub fn start(
+        log: Option<&AuditLog>,
+        request_id: &str,
+        collection: String,
+        request: &impl Serialize,
+    ) -> AuditEntry {
+        let entry = log.map(|log| {
+            let mut request = serde_json::to_value(request).unwrap_or(Value::Null);
+            if log.redact_literals {
+                redact_request(&mut request);
+            }
+            normalize_request(&mut request);
+            Entry {
+                log: log.clone(),
+                started: Instant::now(),
+                timestamp_ms: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |timestamp| timestamp.as_millis()),
+                request_id: request_id.to_string(),
+                collection,
+                request,
+                statements: vec![],
+                jobs: vec![],
+                error: None,
+            }
+        });
+        AuditEntry { entry }
+    }
+
+    /// Record a SQL statement run by the request, and the types of its parameters.
+    pub fn record_statement(&mut self, query_request: &QueryRequest) {
+        if let Some(entry) = &mut self.entry {
+            let redaction = if entry.log.redact_literals {
+                trace::ParameterRedaction::Values
+            } else {
+                trace::ParameterRedaction::None
+            };
+            entry.statements.push(json!({
+                "sql": query_request.query,
+                "parameters": query_request
+                    .query_parameters
+                    .as_deref()
+                    .and_then(|parameters| trace::describe_parameters(parameters, redaction))
+                    .unwrap_or_else(|| Value::Array(vec![])),
+            }));
+        }
+    }
+
+    /// Record the BigQuery jobs which ran the request.
+    pub fn record_jobs(&mut self, jobs: Vec<CompletedJob>) {
+        if let Some(entry) = &mut self.entry {
+            entry.jobs.extend(jobs);
+        }
+    }
+
+    /// Record that the request failed.
+    pub fn record_error(&mut self, error: &impl std::fmt::Display) {
+        if let Some(entry) = &mut self.entry {
+            entry.error = Some(error.to_string());
+        }
+    }
+
+    /// Write the entry of a finished request.
+    pub fn finish(mut self) {
+        self.write(false);
+    }
+
+    /// Write the entry, unless it is written already. The bytes billed by the jobs are known
+    /// once their statistics are fetched, so the entry is written in the background.
+    fn write(&mut self, cancelled: bool) {
+        let Some(entry) = self.entry.take() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let duration_ms = entry.started.elapsed().as_millis();
+        runtime.spawn(async move {
+            let mut job_ids = vec![];
+            let mut bytes_billed = None;
+            for job in entry.jobs {
+                job_ids.push(job.job_id);
+                if let Ok(Some(statistics)) = job.statistics.await {
+                    if let Some(billed) = statistics.bytes_billed {
+                        bytes_billed = Some(bytes_billed.unwrap_or(0) + billed);
+                    }
+                }
+            }
+            let outcome = match entry.error {
+                Some(error) => json!({ "status": "error", "error": error }),
+                None if cancelled => json!({ "status": "cancelled" }),
+                None => json!({ "status": "success" }),
+            };
+            entry
+                .log
+                .write_line(&json!({
+                    "timestamp": entry.timestamp_ms,
+                    "requestId": entry.request_id,
+                    "collection": entry.collection,
+                    "request": entry.request,
+                    "statements": entry.statements,
+                    "jobIds": job_ids,
+                    "bytesBilled": bytes_billed,
+                    "durationMs": duration_ms,
+                    "outcome": outcome,
+                }))
+                .await;
+        });
+    }
+}
+
+impl Drop for AuditEntry {
+    fn drop(&mut self) {
+        self.write(true);
+    }
+}
+
+/// The fields of NDC requests which are optional, and left out of normalized requests when they
+/// are not set.
+const OPTIONAL_FIELDS: &[&str] = &[
+    "aggregates",
+    "fields",
+    "limit",
+    "offset",
+    "order_by",
+    "predicate",
+    "variables",
+];
+
+/// Normalize a request, so that equal requests are logged the same way: the keys of objects are
+/// sorted, and optional fields which are not set are left out. Values (of literals, variables
+/// and procedure arguments) are kept as they are.
+fn normalize_request(request: &mut Value) {
+    match request {
+        Value::Object(object) => {
+            let (is_procedure, holds_value) = match object.get("type").and_then(Value::as_str) {
+                Some("procedure") => (true, false),
+                Some("literal" | "scalar") => (false, true),
+                _ => (false, false),
+            };
+            let mut fields: Vec<(String, Value)> = std::mem::take(object)
+                .into_iter()
+                .filter(|(key, value)| {
+                    !(value.is_null() && OPTIONAL_FIELDS.contains(&key.as_str()))
+                })
+                .collect();
+            fields.sort_by(|(key, _), (other_key, _)| key.cmp(other_key));
+            for (key, value) in &mut fields {
+                let is_value = (holds_value && key == "value")
+                    || key == "variables"
+                    || (is_procedure && key == "arguments");
+                if !is_value {
+                    normalize_request(value);
+                }
+            }
+            *object = fields.into_iter().collect();
+        }
+        Value::Array(values) => values.iter_mut().for_each(normalize_request),
+        _ => {}
+    }
+}
+
+/// Redact the literal values of a request: literal arguments and comparison values, variables,
+/// and the arguments of procedure calls.
+pub(crate) fn redact_request(request: &mut Value) {
+    if let Some(Value::Array(variable_sets)) = request.get_mut("variables") {
+        variable_sets.iter_mut().for_each(redact_values);
+    }
+    if let Some(Value::Array(operations)) = request.get_mut("operations") {
+        for operation in operations {
+            if let Some(arguments) = operation.get_mut("arguments") {
+                redact_values(arguments);
+            }
+        }
+    }
+    redact_literal_values(request);
+}
+
+/// Redact every value of an object.
+fn redact_values(object: &mut Value) {
+    if let Value::Object(object) = object {
+        for value in object.values_mut() {
+            *value = Value::String(REDACTED.to_string());
+        }
+    }
+}
+
+/// Redact the values of literal arguments and of scalar comparison values, wherever they are.
+fn redact_literal_values(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            if matches!(
+                object.get("type").and_then(Value::as_str),
+                Some("literal" | "scalar")
+            ) {
+                if let Some(value) = object.get_mut("value") {
+                    *value = Value::String(REDACTED.to_string());
+                }
+            }
+            object.values_mut().for_each(redact_literal_values);
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_literal_values),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    #[tokio::test]
+    async fn logs_requests_dropped_before_they_finish_as_cancelled() {
+        let (writer, reader) = tokio::io::duplex(64 * 1024);
+        let log = AuditLog {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+            redact_literals: false,
+        };
+        let request = async {
+            let _audit_entry = AuditEntry::start(
+                Some(&log),
+                "request_1",
+                "albums".to_string(),
+                &json!({ "collection": "albums" }),
+            );
+            std::future::pending::<()>().await;
+        };
+
+        // the request is dropped before it finishes, as when the client disconnects.
+        let dropped = tokio::time::timeout(Duration::from_millis(10), request).await;
+        assert!(dropped.is_err());
+
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await.unwrap();
+        let entry: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(entry["requestId"], "request_1");
+        assert_eq!(entry["collection"], "albums");
+        assert_eq!(entry["outcome"], json!({ "status": "cancelled" }));
+    }
+
+    #[test]
+    fn redacts_literal_values_of_requests() {
+        let mut request = json!({
+            "collection": "Album",
+            "arguments": {
+                "id": { "type": "literal", "value": 1 },
+                "name": { "type": "variable", "name": "name" },
+            },
+            "query": {
+                "predicate": {
+                    "type": "binary_comparison_operator",
+                    "column": { "type": "column", "name": "Title", "path": [] },
+                    "operator": "_eq",
+                    "value": { "type": "scalar", "value": "Let There Be Rock" },
+                },
+            },
+            "variables": [{ "name": "AC/DC" }],
+        });
+        redact_request(&mut request);
+        assert_eq!(request["arguments"]["id"]["value"], "<redacted>");
+        assert_eq!(request["arguments"]["name"]["name"], "name");
+        assert_eq!(request["query"]["predicate"]["column"]["name"], "Title");
+        assert_eq!(
+            request["query"]["predicate"]["value"]["value"],
+            "<redacted>"
+        );
+        assert_eq!(request["variables"][0]["name"], "<redacted>");
+    }
+
+    #[test]
+    fn normalizes_requests() {
+        let mut request = json!({
+            "query": {
+                "predicate": {
+                    "value": { "value": { "a": 1, "b": null }, "type": "scalar" },
+                    "type": "binary_comparison_operator",
+                },
+                "limit": null,
+                "fields": { "Title": { "type": "column", "column": "Title", "fields": null } },
+            },
+            "collection": "Album",
+            "variables": null,
+        });
+        normalize_request(&mut request);
+        assert_eq!(
+            request.to_string(),
+            json!({
+                "collection": "Album",
+                "query": {
+                    "fields": { "Title": { "column": "Title", "type": "column" } },
+                    "predicate": {
+                        "type": "binary_comparison_operator",
+                        "value": { "type": "scalar", "value": { "a": 1, "b": null } },
+                    },
+                },
+            })
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn keeps_null_values_of_variables_and_procedure_arguments() {
+        let mut request = json!({
+            "operations": [{
+                "type": "procedure",
+                "name": "insert_artist",
+                "arguments": { "name": null },
+                "fields": null,
+            }],
+            "variables": [{ "limit": null }],
+        });
+        normalize_request(&mut request);
+        assert_eq!(
+            request["operations"][0]["arguments"],
+            json!({ "name": null })
+        );
+        assert!(request["operations"][0].get("fields").is_none());
+        assert_eq!(request["variables"], json!([{ "limit": null }]));
+    }
+
+    #[test]
+    fn redacts_arguments_of_procedure_calls() {
+        let mut request = json!({
+            "operations": [{
+                "type": "procedure",
+                "name": "insert_artist",
+                "arguments": { "name": "AC/DC" },
+                "fields": null,
+            }],
+            "collection_relationships": {},
+        });
+        redact_request(&mut request);
+        assert_eq!(request["operations"][0]["name"], "insert_artist");
+        assert_eq!(request["operations"][0]["arguments"]["name"], "<redacted>");
+    }
+}
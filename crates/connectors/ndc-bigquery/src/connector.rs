@@ -18,7 +18,7 @@ use ndc_bigquery_configuration as configuration;
 use ndc_bigquery_configuration::environment::Environment;
 
 use super::capabilities;
-// use super::mutation;
+use super::mutation;
 use super::query;
 use super::schema;
 use super::state;
@@ -37,12 +37,12 @@ impl Connector for BigQuery {
     /// Note: some metrics can be updated directly, and do not
     /// need to be updated here. This function can be useful to
     /// query metrics which cannot be updated directly, e.g.
-    /// the number of idle connections in a connection pool
-    /// can be polled but not updated directly.
+    /// the cache hit ratio of jobs is computed from counters.
     fn fetch_metrics(
         _configuration: &Arc<ndc_bigquery_configuration::Configuration>,
-        _state: &Self::State,
+        state: &Self::State,
     ) -> Result<()> {
+        state.metrics.update_cache_hit_ratio();
         Ok(())
     }
 
@@ -107,11 +107,23 @@ impl Connector for BigQuery {
     /// This function implements the [mutation endpoint](https://hasura.github.io/ndc-spec/specification/mutations/index.html)
     /// from the NDC specification.
     async fn mutation(
-        _configuration: &Self::Configuration,
-        _state: &Self::State,
-        _request: models::MutationRequest,
+        configuration: &Self::Configuration,
+        state: &Self::State,
+        request: models::MutationRequest,
     ) -> Result<JsonResponse<models::MutationResponse>> {
-        todo!("mutation is currently not implemented")
+        mutation::mutation(configuration, state, request)
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    meta.signal_type = "log",
+                    event.domain = "ndc",
+                    event.name = "Mutation error",
+                    name = "Mutation error",
+                    body = %err,
+                    error = true,
+                );
+                err
+            })
     }
 
     /// Execute a query
@@ -234,6 +246,21 @@ impl<Env: Environment + Send + Sync> ConnectorSetup for BigQuerySetup<Env> {
                     message,
                 },
             ])),
+            configuration::error::MakeRuntimeConfigurationError::ConflictingRoutineName {
+                file_path,
+                name,
+            } => connector::ParseError::ValidateError(connector::InvalidNodes(vec![
+                connector::InvalidNode {
+                    file_path,
+                    node_path: vec![
+                        connector::KeyOrIndex::Key("metadata".into()),
+                        connector::KeyOrIndex::Key("routines".into()),
+                    ],
+                    message: format!(
+                        "routine {name} has the same name as a table or native operation"
+                    ),
+                },
+            ])),
         }
                 })?;
 
@@ -254,7 +281,6 @@ impl<Env: Environment + Send + Sync> ConnectorSetup for BigQuerySetup<Env> {
     ) -> Result<<Self::Connector as Connector>::State> {
         state::create_state(
             configuration,
-            // &configuration.pool_settings,
             metrics,
             // configuration.configuration_version_tag,
         )
@@ -0,0 +1,150 @@
+//! Cache the number of bytes queries would process, as estimated by dry runs.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use gcp_bigquery_client::model::query_request::QueryRequest;
+
+use query_engine_execution::query;
+
+/// The maximum number of entries in the cache. When it is full, expired entries are evicted,
+/// and if none expired, the cache is cleared.
+const CAPACITY: usize = 1024;
+
+/// A cache of dry run results, keyed by SQL statement, parameters and job settings. Entries
+/// expire after a time to live, as the bytes a query processes change with the data.
+#[derive(Debug, Clone)]
+pub struct DryRunCache {
+    time_to_live: Duration,
+    entries: Arc<Mutex<HashMap<String, (Instant, u64)>>>,
+}
+
+impl DryRunCache {
+    pub fn new(time_to_live: Duration) -> DryRunCache {
+        DryRunCache {
+            time_to_live,
+            entries: Arc::default(),
+        }
+    }
+
+    /// The key of the dry run of a query: its SQL, its parameters, and the settings of its job,
+    /// such as the location it runs in. The labels of the job are left out, as they may hold
+    /// the id of the request. The SQL is prefixed with its length, so that the SQL of one query
+    /// and the parameters of another cannot run together into the same key.
+    pub fn key(query_request: &QueryRequest, job_settings: &query::JobSettings) -> String {
+        let query::JobSettings {
+            maximum_bytes_billed,
+            use_query_cache,
+            location,
+            job_timeout_ms,
+            priority,
+            labels: _,
+        } = job_settings;
+        let job_settings = format!(
+            "{location:?} {maximum_bytes_billed:?} {use_query_cache:?} {job_timeout_ms:?} {priority:?}"
+        );
+        format!(
+            "{job_settings}\n{}\n{}{}",
+            query_request.query.len(),
+            query_request.query,
+            serde_json::to_string(&query_request.query_parameters).unwrap_or_default()
+        )
+    }
+
+    /// The bytes processed by a query, if it was dry-run recently.
+    pub fn get(&self, key: &str) -> Option<u64> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(key)
+            .filter(|(inserted, _)| inserted.elapsed() < self.time_to_live)
+            .map(|(_, bytes_processed)| *bytes_processed)
+    }
+
+    pub fn insert(&self, key: String, bytes_processed: u64) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() >= CAPACITY {
+                entries.retain(|_, (inserted, _)| inserted.elapsed() < self.time_to_live);
+                if entries.len() >= CAPACITY {
+                    entries.clear();
+                }
+            }
+            entries.insert(key, (Instant::now(), bytes_processed));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job_settings(location: &str) -> query::JobSettings {
+        query::JobSettings {
+            location: Some(location.to_string()),
+            ..query::JobSettings::default()
+        }
+    }
+
+    #[test]
+    fn keys_differ_by_sql_parameters_and_job_settings() {
+        let query_request = QueryRequest::new("SELECT 1");
+        let mut with_parameters = query_request.clone();
+        with_parameters.query_parameters = Some(vec![]);
+        let key = DryRunCache::key(&query_request, &job_settings("EU"));
+
+        assert_ne!(
+            key,
+            DryRunCache::key(&QueryRequest::new("SELECT 2"), &job_settings("EU"))
+        );
+        assert_ne!(key, DryRunCache::key(&with_parameters, &job_settings("EU")));
+        assert_ne!(key, DryRunCache::key(&query_request, &job_settings("US")));
+        assert_ne!(
+            key,
+            DryRunCache::key(
+                &query_request,
+                &query::JobSettings {
+                    priority: Some(query::JobPriority::Batch),
+                    ..job_settings("EU")
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn keys_do_not_depend_on_job_labels() {
+        let query_request = QueryRequest::new("SELECT 1");
+        let labelled = query::JobSettings {
+            labels: [("request_id".to_string(), "f81d4fae".to_string())].into(),
+            ..job_settings("EU")
+        };
+        assert_eq!(
+            DryRunCache::key(&query_request, &job_settings("EU")),
+            DryRunCache::key(&query_request, &labelled)
+        );
+    }
+
+    #[test]
+    fn returns_cached_results_until_they_expire() {
+        let cache = DryRunCache::new(Duration::from_secs(60));
+        cache.insert("a".to_string(), 1000);
+        assert_eq!(cache.get("a"), Some(1000));
+        assert_eq!(cache.get("b"), None);
+
+        let cache = DryRunCache::new(Duration::ZERO);
+        cache.insert("a".to_string(), 1000);
+        assert_eq!(cache.get("a"), None);
+    }
+
+    #[test]
+    fn clears_the_cache_when_it_is_full_of_results_which_have_not_expired() {
+        let cache = DryRunCache::new(Duration::from_secs(60));
+        for index in 0..CAPACITY {
+            cache.insert(index.to_string(), 1000);
+        }
+        assert_eq!(cache.get("0"), Some(1000));
+
+        cache.insert("full".to_string(), 2000);
+        assert_eq!(cache.get("0"), None);
+        assert_eq!(cache.get("full"), Some(2000));
+    }
+}
@@ -1,5 +1,6 @@
 //! Functions to convert between internal error types and the error types exposed by ndc-sdk.
 
+use http::StatusCode;
 use ndc_sdk::connector::{self, ErrorResponse};
 
 /// Convert an error from [query_engine_execution] to [ErrorResponse].
@@ -10,22 +11,58 @@ pub fn execution_error_to_response(error: query_engine_execution::error::Error)
             QueryError::VariableNotFound(_) => {
                 connector::QueryError::new_invalid_request(&query_error.to_string()).into()
             }
-            QueryError::NotSupported(_) => {
+            QueryError::NotSupported(_) | QueryError::UnsupportedSql(_) => {
                 connector::QueryError::new_unsupported_operation(&query_error.to_string()).into()
             }
-            QueryError::DBError(_) => {
+            QueryError::TooManyRows { .. }
+            | QueryError::ResultTooLarge { .. }
+            | QueryError::BytesBilledLimitExceeded(_)
+            | QueryError::BytesProcessedBudgetExceeded { .. }
+            | QueryError::TimedOut { .. } => {
                 connector::QueryError::new_unprocessable_content(&query_error.to_string()).into()
             }
-            QueryError::DBConstraintError(_) | QueryError::MutationConstraintFailed => {
+            QueryError::MutationConstraintFailed => {
                 connector::MutationError::new_constraint_not_met(&query_error.to_string()).into()
             }
+            QueryError::JobSlotUnavailable { .. } => {
+                let message = query_error.to_string();
+                let details = serde_json::json!({
+                    "message": message,
+                    "retryable": true,
+                });
+                ErrorResponse::new(StatusCode::SERVICE_UNAVAILABLE, message, details)
+            }
         },
-        Error::DB(_) => {
+        Error::BigQuery(bigquery_error) => bigquery_error_to_response(&bigquery_error),
+        Error::StorageRead(_) | Error::UnexpectedResult(_) => {
             ErrorResponse::new_internal_with_details(serde_json::Value::String(error.to_string()))
         }
     }
 }
 
+/// Convert an error from BigQuery to [ErrorResponse], according to the reason it failed.
+/// Errors which may succeed if retried are marked as retryable in their details.
+fn bigquery_error_to_response(
+    error: &query_engine_execution::error::BigQueryError,
+) -> ErrorResponse {
+    use query_engine_execution::error::Reason;
+    let message = error.to_string();
+    let details = serde_json::json!({
+        "message": message,
+        "reason": error.reason.as_str(),
+        "retryable": error.reason.is_retryable(),
+    });
+    match error.reason {
+        Reason::InvalidQuery => connector::QueryError::new_unprocessable_content(&message).into(),
+        Reason::NotFound => connector::QueryError::new_invalid_request(&message).into(),
+        Reason::AccessDenied => ErrorResponse::new(StatusCode::FORBIDDEN, message, details),
+        Reason::QuotaExceeded => {
+            ErrorResponse::new(StatusCode::TOO_MANY_REQUESTS, message, details)
+        }
+        Reason::BackendError | Reason::Other => ErrorResponse::new_internal_with_details(details),
+    }
+}
+
 /// Convert an error from [query_engine_translation] to [connector::QueryError].
 pub fn translation_error_to_response(
     error: &query_engine_translation::translation::error::Error,
@@ -9,16 +9,30 @@ pub fn execution_error(error: &query_engine_execution::error::Error, metrics: &m
     match error {
         Error::Query(err) => match &err {
             QueryError::VariableNotFound(_)
-            | QueryError::DBError(_)
             | QueryError::MutationConstraintFailed
-            | QueryError::DBConstraintError(_) => {
+            | QueryError::TooManyRows { .. }
+            | QueryError::ResultTooLarge { .. }
+            | QueryError::BytesBilledLimitExceeded(_)
+            | QueryError::BytesProcessedBudgetExceeded { .. }
+            | QueryError::TimedOut { .. } => {
                 metrics.error_metrics.record_invalid_request();
             }
-            QueryError::NotSupported(_) => {
+            QueryError::NotSupported(_) | QueryError::UnsupportedSql(_) => {
                 metrics.error_metrics.record_unsupported_feature();
             }
+            QueryError::JobSlotUnavailable { .. } => {
+                metrics.error_metrics.record_job_slot_acquisition_error();
+            }
+        },
+        Error::BigQuery(err) => match err.reason {
+            Reason::InvalidQuery | Reason::AccessDenied | Reason::NotFound => {
+                metrics.error_metrics.record_invalid_request();
+            }
+            Reason::QuotaExceeded | Reason::BackendError | Reason::Other => {
+                metrics.error_metrics.record_database_error();
+            }
         },
-        Error::DB(_) => {
+        Error::StorageRead(_) | Error::UnexpectedResult(_) => {
             metrics.error_metrics.record_database_error();
         }
     }
@@ -1,9 +1,14 @@
-//! A Hasura v3 PostgreSQL Native Data Connector.
+//! A Hasura v3 BigQuery Native Data Connector.
 
+pub mod audit_log;
 pub mod capabilities;
 pub mod connector;
+pub mod dry_run_cache;
 pub mod error;
 pub mod health;
+pub mod mutation;
+pub mod plan_cache;
 pub mod query;
+pub mod response_cache;
 pub mod schema;
 pub mod state;
@@ -0,0 +1,128 @@
+//! Implement the `/mutation` endpoint to call stored procedures.
+//! See the Hasura
+//! [Native Data Connector Specification](https://hasura.github.io/ndc-spec/specification/mutations/index.html)
+//! for further details.
+
+use tracing::{info_span, Instrument};
+
+use ndc_sdk::connector;
+use ndc_sdk::json_response::JsonResponse;
+use ndc_sdk::models;
+use query_engine_sql::sql;
+use query_engine_translation::translation;
+
+use ndc_bigquery_configuration as configuration;
+
+use crate::audit_log::AuditEntry;
+use crate::error::convert;
+use crate::error::record;
+use crate::state;
+
+/// Execute a mutation
+///
+/// This function implements the [mutation endpoint](https://hasura.github.io/ndc-spec/specification/mutations/index.html)
+/// from the NDC specification.
+pub async fn mutation(
+    configuration: &configuration::Configuration,
+    state: &state::State,
+    request: models::MutationRequest,
+) -> Result<JsonResponse<models::MutationResponse>, connector::ErrorResponse> {
+    async move {
+        let request_id = uuid::Uuid::new_v4().to_string();
+        crate::query::trace_request(configuration, &request_id, &request);
+        let procedures = request
+            .operations
+            .iter()
+            .map(|operation| match operation {
+                models::MutationOperation::Procedure { name, .. } => name.as_str(),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut audit_entry =
+            AuditEntry::start(state.audit_log.as_ref(), &request_id, procedures, &request);
+
+        let result = async {
+            let plan = async {
+                plan_mutation(configuration, state, request).map_err(|err| {
+                    record::translation_error(&err, &state.metrics);
+                    audit_entry.record_error(&err);
+                    convert::translation_error_to_response(&err)
+                })
+            }
+            .instrument(info_span!("Plan mutation"))
+            .await?;
+
+            // the audit log records the statements we run.
+            let bind_literals = crate::query::bind_literals(state);
+            for mutation in &plan.query {
+                if let Ok(statement) =
+                    query_engine_execution::mutation::call_request(mutation, bind_literals)
+                {
+                    audit_entry.record_statement(&statement);
+                }
+            }
+
+            let result = async {
+                execute_mutation(configuration, state, &request_id, plan)
+                    .await
+                    .map_err(|err| {
+                        record::execution_error(&err, &state.metrics);
+                        audit_entry.record_error(&err);
+                        convert::execution_error_to_response(err)
+                    })
+            }
+            .instrument(info_span!("Execute mutation"))
+            .await?;
+
+            state.metrics.record_successful_mutation();
+            audit_entry.record_jobs(result.jobs);
+            Ok::<_, connector::ErrorResponse>(JsonResponse::Value(result.response))
+        }
+        .await;
+
+        audit_entry.finish();
+        result
+    }
+    .instrument(info_span!("/mutation"))
+    .await
+}
+
+fn plan_mutation(
+    configuration: &configuration::Configuration,
+    state: &state::State,
+    request: models::MutationRequest,
+) -> Result<
+    sql::execution_plan::ExecutionPlan<Vec<sql::execution_plan::Mutation>>,
+    translation::error::Error,
+> {
+    let timer = state.metrics.time_mutation_plan();
+    let result = translation::mutation::translate(&configuration.metadata, request);
+    timer.complete_with(result)
+}
+
+async fn execute_mutation(
+    configuration: &configuration::Configuration,
+    state: &state::State,
+    request_id: &str,
+    plan: sql::execution_plan::ExecutionPlan<Vec<sql::execution_plan::Mutation>>,
+) -> Result<query_engine_execution::mutation::MutationResult, query_engine_execution::error::Error>
+{
+    let timer = state.metrics.time_mutation_execution();
+    let settings = query_engine_execution::query::Settings {
+        retry: crate::query::retry_policy(configuration),
+        parameter_redaction: crate::query::parameter_redaction(configuration),
+        job_slots: state.job_slots.clone(),
+        bind_literals: crate::query::bind_literals(state),
+        ..Default::default()
+    };
+    let result = query_engine_execution::mutation::execute(
+        &state.bigquery_client,
+        &state.metrics,
+        &state.project_id,
+        &settings,
+        |procedure| crate::query::job_settings(configuration, procedure, request_id),
+        plan,
+    )
+    .await;
+    timer.complete_with(result)
+}
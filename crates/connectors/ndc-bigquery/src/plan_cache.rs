@@ -0,0 +1,287 @@
+//! Cache the plans of queries, by the shape of their requests.
+//!
+//! Requests which differ only by the values they compare columns with have the same shape. The
+//! literals of a request are lifted out of it before it is translated, so that its plan binds
+//! them as parameters, and can run with the literals of any request of the same shape.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use ndc_sdk::models;
+use query_engine_execution::metrics;
+use query_engine_metadata::metadata;
+use query_engine_sql::sql;
+use query_engine_translation::translation;
+use sha2::{Digest, Sha256};
+
+type Plan = sql::execution_plan::ExecutionPlan<sql::execution_plan::Query>;
+
+/// A cache of query plans, keyed by a hash of the shape of their requests. When it is full,
+/// the least recently used plan is evicted.
+#[derive(Debug, Clone)]
+pub struct PlanCache {
+    capacity: usize,
+    entries: Arc<Mutex<Entries>>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Incremented on each use of the cache, to tell when each plan was last used.
+    clock: u64,
+    plans: HashMap<Vec<u8>, (u64, Plan)>,
+    /// The shapes of the cached plans, by when they were last used.
+    recently_used: BTreeMap<u64, Vec<u8>>,
+}
+
+impl PlanCache {
+    pub fn new(capacity: usize) -> PlanCache {
+        PlanCache {
+            capacity,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Plan a query with the cached plan of its shape, or translate it and cache its plan.
+    pub fn plan(
+        &self,
+        metadata: &metadata::Metadata,
+        metrics: &metrics::Metrics,
+        query_request: models::QueryRequest,
+    ) -> Result<Plan, translation::error::Error> {
+        let mut shape = query_request.clone();
+        let Some(literals) = translation::query::literals::lift(&mut shape) else {
+            return translation::query::translate(metadata, query_request);
+        };
+        // plans do not depend on the values of variables, which are bound when they run.
+        let variables = shape.variables.as_mut().map(std::mem::take);
+        let shape_hash = shape_hash(&shape);
+
+        if let Some(mut plan) = self.get(&shape_hash) {
+            metrics.record_query_plan_cache_hit();
+            plan.query.literals = literals;
+            plan.query.variables = variables;
+            return Ok(plan);
+        }
+        metrics.record_query_plan_cache_miss();
+
+        match translation::query::translate_lifted(metadata, shape, literals) {
+            Ok((mut plan, reusable)) => {
+                if reusable {
+                    self.insert(shape_hash, plan.clone());
+                }
+                plan.query.variables = variables;
+                Ok(plan)
+            }
+            // errors may mention the markers of lifted literals, so we report the errors of the
+            // request as it is.
+            Err(_) => translation::query::translate(metadata, query_request),
+        }
+    }
+
+    /// The plan of requests of a shape, if it was cached. Its literals are those of the
+    /// request it was translated for.
+    pub fn get(&self, shape: &[u8]) -> Option<Plan> {
+        let mut entries = self.entries.lock().ok()?;
+        let clock = entries.tick();
+        let (last_used, plan) = entries.plans.get_mut(shape)?;
+        let previously_used = std::mem::replace(last_used, clock);
+        let plan = plan.clone();
+        let shape = entries.recently_used.remove(&previously_used)?;
+        entries.recently_used.insert(clock, shape);
+        Some(plan)
+    }
+
+    pub fn insert(&self, shape: Vec<u8>, plan: Plan) {
+        if let Ok(mut entries) = self.entries.lock() {
+            let clock = entries.tick();
+            if let Some((previously_used, _)) = entries.plans.remove(&shape) {
+                entries.recently_used.remove(&previously_used);
+            } else if entries.plans.len() >= self.capacity {
+                if let Some((_, least_recently_used)) = entries.recently_used.pop_first() {
+                    entries.plans.remove(&least_recently_used);
+                }
+            }
+            entries.recently_used.insert(clock, shape.clone());
+            entries.plans.insert(shape, (clock, plan));
+        }
+    }
+}
+
+impl Entries {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+/// A hash of the shape of a request whose literals were lifted.
+pub fn shape_hash(request: &models::QueryRequest) -> Vec<u8> {
+    let shape = serde_json::to_vec(request).unwrap_or_default();
+    Sha256::digest(shape).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use query_engine_execution::query;
+    use serde_json::json;
+
+    fn metadata() -> metadata::Metadata {
+        serde_json::from_value(json!({
+            "tables": {
+                "albums": {
+                    "schemaName": "test_project.test_dataset",
+                    "tableName": "albums",
+                    "columns": {
+                        "Title": {
+                            "name": "Title",
+                            "type": { "scalarType": "string" },
+                            "nullable": "nullable",
+                        },
+                    },
+                    "uniquenessConstraints": {},
+                    "foreignRelations": {},
+                    "description": null,
+                },
+            },
+            "scalarTypes": {
+                "string": {
+                    "typeName": "string",
+                    "schemaName": null,
+                    "description": null,
+                    "aggregateFunctions": {},
+                    "comparisonOperators": {
+                        "_eq": {
+                            "operatorName": "=",
+                            "operatorKind": "equal",
+                            "argumentType": "string",
+                            "isInfix": true,
+                        },
+                        "_in": {
+                            "operatorName": "IN",
+                            "operatorKind": "in",
+                            "argumentType": "string",
+                            "isInfix": true,
+                        },
+                    },
+                    "typeRepresentation": null,
+                },
+            },
+            "nativeOperations": { "queries": {}, "mutations": {} },
+        }))
+        .unwrap()
+    }
+
+    fn metrics() -> metrics::Metrics {
+        metrics::Metrics::initialize(&mut prometheus::Registry::new()).unwrap()
+    }
+
+    /// A request for the albums whose title compares with a value.
+    fn request(operator: &str, value: serde_json::Value) -> models::QueryRequest {
+        serde_json::from_value(json!({
+            "collection": "albums",
+            "query": {
+                "fields": { "Title": { "type": "column", "column": "Title" } },
+                "predicate": {
+                    "type": "binary_comparison_operator",
+                    "column": { "type": "column", "name": "Title", "path": [] },
+                    "operator": operator,
+                    "value": { "type": "scalar", "value": value },
+                },
+            },
+            "arguments": {},
+            "collection_relationships": {},
+        }))
+        .unwrap()
+    }
+
+    fn parameter_values(plan: &Plan) -> Vec<Option<String>> {
+        query::query_request(&plan.query, false)
+            .unwrap()
+            .query_parameters
+            .unwrap_or_default()
+            .into_iter()
+            .map(|parameter| parameter.parameter_value.and_then(|value| value.value))
+            .collect()
+    }
+
+    fn cached_plans(cache: &PlanCache) -> usize {
+        cache.entries.lock().unwrap().plans.len()
+    }
+
+    #[test]
+    fn reuses_the_plan_of_a_shape_with_the_literals_of_each_request() {
+        let (metadata, metrics, cache) = (metadata(), metrics(), PlanCache::new(16));
+        let translated = cache
+            .plan(
+                &metadata,
+                &metrics,
+                request("_in", json!(["Facelift", "Jagged Little Pill"])),
+            )
+            .unwrap();
+        assert_eq!(cached_plans(&cache), 1);
+
+        let reused = cache
+            .plan(
+                &metadata,
+                &metrics,
+                request("_in", json!(["Let There Be Rock", "Back in Black"])),
+            )
+            .unwrap();
+        assert_eq!(cached_plans(&cache), 1);
+        assert_eq!(
+            query::query_request(&reused.query, false).unwrap().query,
+            query::query_request(&translated.query, false)
+                .unwrap()
+                .query
+        );
+        assert_eq!(
+            parameter_values(&translated),
+            [
+                Some("Facelift".to_string()),
+                Some("Jagged Little Pill".to_string())
+            ]
+        );
+        assert_eq!(
+            parameter_values(&reused),
+            [
+                Some("Let There Be Rock".to_string()),
+                Some("Back in Black".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn does_not_cache_plans_holding_the_literals_of_their_request() {
+        let (metadata, metrics, cache) = (metadata(), metrics(), PlanCache::new(16));
+        // an array compared with `_eq` is translated as a constant, not as parameters.
+        cache
+            .plan(
+                &metadata,
+                &metrics,
+                request("_eq", json!(["Facelift", "Jagged Little Pill"])),
+            )
+            .unwrap();
+        assert_eq!(cached_plans(&cache), 0);
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_plan_when_full() {
+        let plan =
+            translation::query::translate(&metadata(), request("_eq", json!("Facelift"))).unwrap();
+        let cache = PlanCache::new(2);
+        cache.insert(b"a".to_vec(), plan.clone());
+        cache.insert(b"b".to_vec(), plan.clone());
+        assert!(cache.get(b"a").is_some());
+        cache.insert(b"c".to_vec(), plan.clone());
+        assert!(cache.get(b"b").is_none());
+        assert!(cache.get(b"a").is_some());
+        assert!(cache.get(b"c").is_some());
+
+        // replacing a cached plan does not evict another one.
+        cache.insert(b"a".to_vec(), plan);
+        assert_eq!(cached_plans(&cache), 2);
+        assert!(cache.get(b"c").is_some());
+    }
+}
@@ -1,8 +1,10 @@
-//! Implement the `/query` endpoint to run a query against postgres.
+//! Implement the `/query` endpoint to run a query against BigQuery.
 //! See the Hasura
 //! [Native Data Connector Specification](https://hasura.github.io/ndc-spec/specification/queries/index.html)
 //! for further details.
 
+use std::time::Duration;
+
 use tracing::{info_span, Instrument};
 
 // pub use explain::explain;
@@ -14,9 +16,19 @@ use query_engine_translation::translation;
 
 // use super::configuration;
 use ndc_bigquery_configuration as configuration;
+use ndc_bigquery_configuration::values::{
+    ForeachStrategy, JobPriority, ParameterRedaction, StorageReadMode,
+};
+use query_engine_execution::query;
+use query_engine_execution::retry;
+use query_engine_execution::storage_read;
+use query_engine_execution::trace;
 
+use crate::audit_log::{self, AuditEntry};
+use crate::dry_run_cache::DryRunCache;
 use crate::error::convert;
 use crate::error::record;
+use crate::response_cache;
 use crate::state;
 
 /// Execute a query
@@ -32,31 +44,91 @@ pub async fn query(
 
     // See https://docs.rs/tracing/0.1.29/tracing/span/struct.Span.html#in-asynchronous-code
     async move {
-        tracing::info!(
-            query_request_json = serde_json::to_string(&query_request).unwrap(),
-            query_request = ?query_request
+        let request_id = uuid::Uuid::new_v4().to_string();
+        trace_request(configuration, &request_id, &query_request);
+        let settings = query_settings(
+            configuration,
+            state,
+            query_request.collection.as_str(),
+            &request_id,
+        );
+        let collection = query_request.collection.clone();
+        let mut audit_entry = AuditEntry::start(
+            state.audit_log.as_ref(),
+            &request_id,
+            collection.clone(),
+            &query_request,
         );
 
-        let plan = async {
-            plan_query(configuration, state, query_request).map_err(|err| {
-                record::translation_error(&err, &state.metrics);
-                convert::translation_error_to_response(&err)
-            })
-        }
-        .instrument(info_span!("Plan query"))
-        .await?;
+        let cache_ttl = response_cache_ttl(configuration, state, &query_request);
 
         let result = async {
-            execute_query(state, plan).await.map_err(|err| {
-                record::execution_error(&err, &state.metrics);
-                convert::execution_error_to_response(err)
-            })
+            let plan = async {
+                plan_query(configuration, state, query_request).map_err(|err| {
+                    record::translation_error(&err, &state.metrics);
+                    audit_entry.record_error(&err);
+                    convert::translation_error_to_response(&err)
+                })
+            }
+            .instrument(info_span!("Plan query"))
+            .await?;
+
+            // the audit log records the statement we run.
+            let query_request = query::query_request(&plan.query, settings.bind_literals).ok();
+            if let Some(query_request) = &query_request {
+                audit_entry.record_statement(query_request);
+            }
+
+            let cache_key = cache_ttl
+                .zip(state.response_cache.as_ref())
+                .zip(query_request.as_ref())
+                .map(|((ttl, cache), query_request)| (ttl, cache, cache.key(query_request)));
+            if let Some((_, cache, key)) = &cache_key {
+                if let Some((response, size_bytes)) = cache.get(key) {
+                    state.metrics.record_query_response_cache_hit(&collection);
+                    state.metrics.set_query_response_cache_size(size_bytes);
+                    state.metrics.record_successful_query();
+                    return Ok(JsonResponse::Serialized(response));
+                }
+                state.metrics.record_query_response_cache_miss(&collection);
+            }
+
+            async {
+                check_bytes_processed_budget(configuration, state, &settings, &plan)
+                    .await
+                    .map_err(|err| {
+                        record::execution_error(&err, &state.metrics);
+                        audit_entry.record_error(&err);
+                        convert::execution_error_to_response(err)
+                    })
+            }
+            .instrument(info_span!("Dry-run query"))
+            .await?;
+
+            let result = async {
+                execute_query(state, &settings, plan).await.map_err(|err| {
+                    record::execution_error(&err, &state.metrics);
+                    audit_entry.record_error(&err);
+                    convert::execution_error_to_response(err)
+                })
+            }
+            .instrument(info_span!("Execute query"))
+            .await?;
+
+            if let Some((ttl, cache, key)) = cache_key {
+                if let Some(size_bytes) = cache.insert(key, result.response.clone(), ttl) {
+                    state.metrics.set_query_response_cache_size(size_bytes);
+                }
+            }
+
+            state.metrics.record_successful_query();
+            audit_entry.record_jobs(result.jobs);
+            Ok::<_, connector::ErrorResponse>(JsonResponse::Serialized(result.response))
         }
-        .instrument(info_span!("Execute query"))
-        .await?;
+        .await;
 
-        state.metrics.record_successful_query();
-        Ok(result)
+        audit_entry.finish();
+        result
     }
     .instrument(info_span!("/query"))
     .await
@@ -64,6 +136,28 @@ pub async fn query(
     // timer.complete_with(result)
 }
 
+/// How long the response to a request can be cached for, if it can be cached: the response
+/// cache is enabled, the collection has a time to live, and the request does not bypass it.
+fn response_cache_ttl(
+    configuration: &configuration::Configuration,
+    state: &state::State,
+    query_request: &models::QueryRequest,
+) -> Option<Duration> {
+    state.response_cache.as_ref()?;
+    let collection = query_request.collection.as_str();
+    let ttl_seconds = configuration
+        .query_settings
+        .response_cache
+        .ttl_seconds_for(collection)?;
+    if response_cache::must_bypass(configuration, query_request) {
+        state
+            .metrics
+            .record_query_response_cache_bypassed(collection);
+        return None;
+    }
+    Some(Duration::from_secs(ttl_seconds))
+}
+
 fn plan_query(
     configuration: &configuration::Configuration,
     state: &state::State,
@@ -71,22 +165,280 @@ fn plan_query(
 ) -> Result<sql::execution_plan::ExecutionPlan<sql::execution_plan::Query>, translation::error::Error>
 {
     let timer = state.metrics.time_query_plan();
-    let result = translation::query::translate(&configuration.metadata, query_request);
+    let result = match &state.plan_cache {
+        None => translation::query::translate(&configuration.metadata, query_request),
+        Some(plan_cache) => plan_cache.plan(&configuration.metadata, &state.metrics, query_request),
+    };
     timer.complete_with(result)
 }
 
+/// If a budget of bytes processed is configured, dry-run a query, and reject it if it would
+/// process more bytes than the budget.
+async fn check_bytes_processed_budget(
+    configuration: &configuration::Configuration,
+    state: &state::State,
+    settings: &query::Settings,
+    plan: &sql::execution_plan::ExecutionPlan<sql::execution_plan::Query>,
+) -> Result<(), query_engine_execution::error::Error> {
+    let Some(budget) = configuration.query_settings.dry_run.maximum_bytes_processed else {
+        return Ok(());
+    };
+
+    let query_request = query::query_request(&plan.query, settings.bind_literals)?;
+    let cache_key = DryRunCache::key(&query_request, &settings.job);
+    let cached = state
+        .dry_run_cache
+        .as_ref()
+        .and_then(|cache| cache.get(&cache_key));
+
+    let bytes_processed = match cached {
+        Some(bytes_processed) => {
+            state.metrics.record_query_dry_run_cache_hit();
+            bytes_processed
+        }
+        None => {
+            let bytes_processed = query::dry_run(
+                &state.bigquery_client,
+                &state.metrics,
+                &state.project_id,
+                settings,
+                query_request,
+            )
+            .await?;
+            state.metrics.record_query_dry_run(bytes_processed);
+            if let Some(cache) = &state.dry_run_cache {
+                cache.insert(cache_key, bytes_processed);
+            }
+            bytes_processed
+        }
+    };
+
+    if bytes_processed > budget {
+        state
+            .metrics
+            .record_query_dry_run_rejected(plan.query.root_field.as_str());
+        return Err(query_engine_execution::error::Error::Query(
+            query_engine_execution::error::QueryError::BytesProcessedBudgetExceeded {
+                bytes_processed,
+                budget,
+            },
+        ));
+    }
+    Ok(())
+}
+
 async fn execute_query(
     state: &state::State,
+    settings: &query::Settings,
     plan: sql::execution_plan::ExecutionPlan<sql::execution_plan::Query>,
-) -> Result<JsonResponse<models::QueryResponse>, query_engine_execution::error::Error> {
+) -> Result<query::QueryResult, query_engine_execution::error::Error> {
     let timer = state.metrics.time_query_execution();
-    let result = query_engine_execution::query::execute(
+    let result = query::execute(
         &state.bigquery_client,
+        state.storage_read_client.as_ref(),
         &state.metrics,
         &state.project_id,
+        settings,
         plan,
     )
-    .await
-    .map(JsonResponse::Serialized);
+    .await;
     timer.complete_with(result)
 }
+
+/// The settings of a query on a collection, from the configuration.
+fn query_settings(
+    configuration: &configuration::Configuration,
+    state: &state::State,
+    collection: &str,
+    request_id: &str,
+) -> query::Settings {
+    let query_settings = &configuration.query_settings;
+    query::Settings {
+        limits: query::ResultLimits {
+            page_size: query_settings.page_size,
+            max_rows: query_settings.max_rows,
+            max_result_size: query_settings.max_result_size,
+        },
+        storage_read: storage_read::Settings {
+            mode: match query_settings.storage_read.mode {
+                StorageReadMode::Disabled => storage_read::Mode::Disabled,
+                StorageReadMode::Automatic => storage_read::Mode::Automatic {
+                    row_threshold: query_settings.storage_read.row_threshold,
+                },
+                StorageReadMode::Always => storage_read::Mode::Always,
+            },
+            max_streams: query_settings.storage_read.max_streams,
+        },
+        job: job_settings(configuration, collection, request_id),
+        timeout_seconds: query_settings.timeout_seconds,
+        retry: retry_policy(configuration),
+        parameter_redaction: parameter_redaction(configuration),
+        foreach: match query_settings.foreach.strategy {
+            ForeachStrategy::Batched => query::ForeachStrategy::Batched,
+            ForeachStrategy::Chunked => query::ForeachStrategy::Chunked {
+                chunk_size: query_settings.foreach.chunk_size,
+                max_concurrency: query_settings.foreach.max_concurrency,
+            },
+        },
+        job_slots: state.job_slots.clone(),
+        bind_literals: bind_literals(state),
+    }
+}
+
+/// Whether the literals of the SQL we run are bound as parameters: they are when the audit log
+/// redacts them, so that the statements it records are the ones we run.
+pub(crate) fn bind_literals(state: &state::State) -> bool {
+    state
+        .audit_log
+        .as_ref()
+        .is_some_and(audit_log::AuditLog::redacts_literals)
+}
+
+/// The settings of the jobs run for a collection or a procedure, from the configuration.
+pub(crate) fn job_settings(
+    configuration: &configuration::Configuration,
+    collection: &str,
+    request_id: &str,
+) -> query::JobSettings {
+    let job_settings = configuration.query_settings.job_settings_for(collection);
+    query::JobSettings {
+        maximum_bytes_billed: job_settings.maximum_bytes_billed,
+        use_query_cache: job_settings.use_query_cache,
+        location: job_settings.location,
+        job_timeout_ms: job_settings.job_timeout_ms,
+        priority: job_settings.priority.map(|priority| match priority {
+            JobPriority::Interactive => query::JobPriority::Interactive,
+            JobPriority::Batch => query::JobPriority::Batch,
+        }),
+        labels: job_settings
+            .labels
+            .iter()
+            .map(|(key, value)| {
+                (
+                    render_label_key(key),
+                    render_label(value, collection, request_id),
+                )
+            })
+            .collect(),
+    }
+}
+
+/// How the values of query parameters are redacted in traces, from the configuration.
+pub(crate) fn parameter_redaction(
+    configuration: &configuration::Configuration,
+) -> trace::ParameterRedaction {
+    match configuration.query_settings.tracing.parameter_redaction {
+        ParameterRedaction::None => trace::ParameterRedaction::None,
+        ParameterRedaction::Values => trace::ParameterRedaction::Values,
+        ParameterRedaction::All => trace::ParameterRedaction::All,
+    }
+}
+
+/// Record a request at debug level, with its literal values redacted unless the values of
+/// query parameters are traced too.
+pub(crate) fn trace_request(
+    configuration: &configuration::Configuration,
+    request_id: &str,
+    request: &impl serde::Serialize,
+) {
+    if !tracing::enabled!(tracing::Level::DEBUG) {
+        return;
+    }
+    let mut request_json = match serde_json::to_value(request) {
+        Ok(request_json) => request_json,
+        Err(err) => {
+            tracing::warn!(request_id, "unable to serialize the request: {err}");
+            return;
+        }
+    };
+    if parameter_redaction(configuration) != trace::ParameterRedaction::None {
+        audit_log::redact_request(&mut request_json);
+    }
+    tracing::debug!(request_id, request_json = %request_json);
+}
+
+/// How requests to BigQuery which fail transiently are retried, from the configuration.
+pub(crate) fn retry_policy(configuration: &configuration::Configuration) -> retry::Policy {
+    let retry_settings = &configuration.query_settings.retry;
+    retry::Policy {
+        max_attempts: retry_settings.max_attempts,
+        initial_backoff: Duration::from_millis(retry_settings.initial_backoff_ms),
+        max_backoff: Duration::from_millis(retry_settings.max_backoff_ms),
+        deadline: retry_settings.deadline_seconds.map(Duration::from_secs),
+    }
+}
+
+/// Fill in the templated values of a job label, and make it a valid label value.
+fn render_label(template: &str, collection: &str, request_id: &str) -> String {
+    sanitize_label(
+        &template
+            .replace("{collection}", collection)
+            .replace("{request_id}", request_id),
+    )
+}
+
+/// Make a valid label key out of a configured one. Keys must also start with a lowercase
+/// letter, so other keys are prefixed with `label_`.
+fn render_label_key(key: &str) -> String {
+    if key.starts_with(|char: char| char.is_ascii_alphabetic()) {
+        sanitize_label(key)
+    } else {
+        sanitize_label(&format!("label_{key}"))
+    }
+}
+
+/// Keep the characters allowed in the keys and values of job labels: at most 63 lowercase
+/// letters, digits, underscores and dashes. Other characters are replaced with underscores.
+fn sanitize_label(label: &str) -> String {
+    label
+        .chars()
+        .map(|char| match char.to_ascii_lowercase() {
+            char @ ('a'..='z' | '0'..='9' | '_' | '-') => char,
+            _ => '_',
+        })
+        .take(63)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_templated_values_of_labels() {
+        assert_eq!(
+            render_label("{collection}-{request_id}", "Album", "f81d4fae"),
+            "album-f81d4fae"
+        );
+        assert_eq!(render_label("team", "Album", "f81d4fae"), "team");
+    }
+
+    #[test]
+    fn replaces_the_characters_not_allowed_in_labels() {
+        assert_eq!(
+            render_label("{collection}", "chinook.Album Tracks", "f81d4fae"),
+            "chinook_album_tracks"
+        );
+        assert_eq!(render_label_key("Cost Centre"), "cost_centre");
+        assert_eq!(render_label_key("team.name"), "team_name");
+    }
+
+    #[test]
+    fn prefixes_label_keys_not_starting_with_a_letter() {
+        assert_eq!(render_label_key("1st"), "label_1st");
+        assert_eq!(render_label_key("_team"), "label__team");
+        assert_eq!(render_label_key(""), "label_");
+    }
+
+    #[test]
+    fn truncates_labels_to_63_characters() {
+        let long = "a".repeat(100);
+        assert_eq!(render_label(&long, "Album", "f81d4fae"), "a".repeat(63));
+        assert_eq!(
+            render_label("{collection}", &long, "f81d4fae"),
+            "a".repeat(63)
+        );
+        assert_eq!(render_label_key(&long), "a".repeat(63));
+        assert_eq!(render_label_key(&format!("1{long}")).len(), 63);
+    }
+}
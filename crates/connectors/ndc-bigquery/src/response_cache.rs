@@ -0,0 +1,456 @@
+//! Cache the responses to queries in memory.
+//!
+//! BigQuery caches the results of queries too, but reading them still takes a round trip and
+//! a job. Responses are cached by the SQL of their query, its parameters, and the
+//! configuration, for the time to live of their collection, within a maximum total size.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use gcp_bigquery_client::model::query_request::QueryRequest;
+use ndc_sdk::models;
+use sha2::{Digest, Sha256};
+
+use ndc_bigquery_configuration as configuration;
+use query_engine_translation::translation::helpers::{AS_OF_ARGUMENT, HEADERS_ARGUMENT};
+
+/// A cache of the responses to queries. When it is full, expired responses are evicted, and
+/// then the responses closest to expiring.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    max_size_bytes: u64,
+    /// A hash of the configuration, so that responses are not shared by configurations.
+    configuration_hash: Vec<u8>,
+    entries: Arc<Mutex<Entries>>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    size_bytes: u64,
+    responses: HashMap<Vec<u8>, (Instant, Bytes)>,
+}
+
+impl ResponseCache {
+    pub fn new(configuration: &configuration::Configuration) -> ResponseCache {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(&configuration.metadata).unwrap_or_default());
+        hasher.update(serde_json::to_vec(&configuration.query_settings).unwrap_or_default());
+        ResponseCache {
+            max_size_bytes: configuration.query_settings.response_cache.max_size_bytes,
+            configuration_hash: hasher.finalize().to_vec(),
+            entries: Arc::default(),
+        }
+    }
+
+    /// The key of the response to a query: a hash of its SQL, its parameters, and the
+    /// configuration. The SQL is prefixed with its length, so that the SQL of one query and
+    /// the parameters of another cannot run together into the same bytes.
+    pub fn key(&self, query_request: &QueryRequest) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.configuration_hash);
+        hasher.update((query_request.query.len() as u64).to_le_bytes());
+        hasher.update(query_request.query.as_bytes());
+        hasher.update(serde_json::to_vec(&query_request.query_parameters).unwrap_or_default());
+        hasher.finalize().to_vec()
+    }
+
+    /// The cached response to a query, if it has not expired. Also returns the total size of
+    /// the cached responses.
+    pub fn get(&self, key: &[u8]) -> Option<(Bytes, u64)> {
+        let mut entries = self.entries.lock().ok()?;
+        let (expires, response) = entries.responses.get(key)?.clone();
+        if expires > Instant::now() {
+            return Some((response, entries.size_bytes));
+        }
+        entries.remove(key);
+        None
+    }
+
+    /// Cache the response to a query, unless it is larger than the cache. Returns the total
+    /// size of the cached responses.
+    pub fn insert(&self, key: Vec<u8>, response: Bytes, time_to_live: Duration) -> Option<u64> {
+        let size = response.len() as u64;
+        if size > self.max_size_bytes {
+            return None;
+        }
+        let mut entries = self.entries.lock().ok()?;
+        entries.remove(&key);
+        if entries.size_bytes + size > self.max_size_bytes {
+            let now = Instant::now();
+            let expired: Vec<Vec<u8>> = entries
+                .responses
+                .iter()
+                .filter(|(_, (expires, _))| *expires <= now)
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in expired {
+                entries.remove(&key);
+            }
+        }
+        while entries.size_bytes + size > self.max_size_bytes {
+            let closest_to_expiring = entries
+                .responses
+                .iter()
+                .min_by_key(|(_, (expires, _))| *expires)
+                .map(|(key, _)| key.clone())?;
+            entries.remove(&closest_to_expiring);
+        }
+        entries.size_bytes += size;
+        entries
+            .responses
+            .insert(key, (Instant::now() + time_to_live, response));
+        Some(entries.size_bytes)
+    }
+}
+
+impl Entries {
+    fn remove(&mut self, key: &[u8]) {
+        if let Some((_, response)) = self.responses.remove(key) {
+            self.size_bytes -= response.len() as u64;
+        }
+    }
+}
+
+/// Whether the response to a request must not be cached: because it reads a table as of a
+/// point in time, or a volatile native query, or because it forwards a `Cache-Control` header
+/// asking not to be cached.
+pub fn must_bypass(
+    configuration: &configuration::Configuration,
+    request: &models::QueryRequest,
+) -> bool {
+    let reads_as_of = request.arguments.iter().any(|(name, argument)| {
+        name.as_str() == AS_OF_ARGUMENT
+            && !matches!(
+                argument,
+                models::Argument::Literal {
+                    value: serde_json::Value::Null
+                }
+            )
+    }) || request
+        .collection_relationships
+        .values()
+        .any(|relationship| {
+            relationship.arguments.iter().any(|(name, argument)| {
+                name.as_str() == AS_OF_ARGUMENT
+                    && !matches!(
+                        argument,
+                        models::RelationshipArgument::Literal {
+                            value: serde_json::Value::Null
+                        }
+                    )
+            })
+        });
+
+    let native_queries = &configuration.metadata.native_operations.queries.0;
+    let reads_volatile_native_query = std::iter::once(&request.collection)
+        .chain(
+            request
+                .collection_relationships
+                .values()
+                .map(|relationship| &relationship.target_collection),
+        )
+        .any(|collection| {
+            native_queries
+                .get(collection)
+                .is_some_and(|native_query| native_query.volatile)
+        });
+
+    reads_as_of || reads_volatile_native_query || asks_not_to_be_cached(request)
+}
+
+/// Whether a request forwards a `Cache-Control: no-cache` or `no-store` header.
+fn asks_not_to_be_cached(request: &models::QueryRequest) -> bool {
+    let Some(models::Argument::Literal {
+        value: serde_json::Value::Object(headers),
+    }) = request
+        .arguments
+        .iter()
+        .find(|(name, _)| name.as_str() == HEADERS_ARGUMENT)
+        .map(|(_, argument)| argument)
+    else {
+        return false;
+    };
+    headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("cache-control")
+            && value.as_str().is_some_and(|value| {
+                value.split(',').any(|directive| {
+                    matches!(
+                        directive.trim().to_ascii_lowercase().as_str(),
+                        "no-cache" | "no-store"
+                    )
+                })
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gcp_bigquery_client::model::query_parameter::QueryParameter;
+    use query_engine_metadata::metadata;
+    use serde_json::json;
+
+    fn configuration(max_size_bytes: u64) -> configuration::Configuration {
+        let mut metadata = metadata::Metadata::default();
+        metadata.native_operations.queries.0.insert(
+            "random_numbers".into(),
+            metadata::NativeQueryInfo {
+                sql: metadata::NativeQuerySqlEither::NativeQuerySql(
+                    metadata::NativeQuerySql::Inline {
+                        sql: metadata::NativeQueryParts(vec![metadata::NativeQueryPart::Text(
+                            "SELECT RAND() AS r".to_string(),
+                        )]),
+                    },
+                ),
+                columns: std::collections::BTreeMap::new(),
+                arguments: std::collections::BTreeMap::new(),
+                description: None,
+                volatile: true,
+            },
+        );
+        configuration::Configuration {
+            metadata,
+            pool_settings: configuration::values::PoolSettings::default(),
+            query_settings: configuration::values::QuerySettings {
+                response_cache: configuration::values::ResponseCacheSettings {
+                    ttl_seconds: Some(60),
+                    collection_ttl_seconds: std::collections::BTreeMap::new(),
+                    max_size_bytes,
+                },
+                ..configuration::values::QuerySettings::default()
+            },
+            audit_log: None,
+            service_key: String::new(),
+            project_id: "project".to_string(),
+            dataset_id: "dataset".to_string(),
+        }
+    }
+
+    fn request(request: serde_json::Value) -> models::QueryRequest {
+        serde_json::from_value(request).unwrap()
+    }
+
+    fn album_request(arguments: serde_json::Value) -> models::QueryRequest {
+        request(json!({
+            "collection": "Album",
+            "arguments": arguments,
+            "query": { "fields": { "Title": { "type": "column", "column": "Title" } } },
+            "collection_relationships": {},
+        }))
+    }
+
+    #[test]
+    fn keys_differ_by_sql_and_parameters() {
+        let cache = ResponseCache::new(&configuration(1024));
+        let mut with_parameter = QueryRequest::new("SELECT @param0");
+        with_parameter.query_parameters = Some(vec![QueryParameter {
+            name: Some("param0".to_string()),
+            parameter_type: None,
+            parameter_value: None,
+        }]);
+        let keys = [
+            cache.key(&QueryRequest::new("SELECT 1")),
+            cache.key(&QueryRequest::new("SELECT 2")),
+            cache.key(&QueryRequest::new("SELECT @param0")),
+            cache.key(&with_parameter),
+        ];
+        assert_eq!(keys[0], cache.key(&QueryRequest::new("SELECT 1")));
+        for (i, key) in keys.iter().enumerate() {
+            assert!(keys[i + 1..].iter().all(|other| other != key));
+        }
+    }
+
+    #[test]
+    fn keys_differ_by_configuration() {
+        let query_request = QueryRequest::new("SELECT 1");
+        assert_ne!(
+            ResponseCache::new(&configuration(1024)).key(&query_request),
+            ResponseCache::new(&configuration(2048)).key(&query_request)
+        );
+    }
+
+    #[test]
+    fn returns_cached_responses_until_they_expire() {
+        let cache = ResponseCache::new(&configuration(1024));
+        cache.insert(
+            b"fresh".to_vec(),
+            Bytes::from_static(b"[1]"),
+            Duration::from_secs(60),
+        );
+        cache.insert(
+            b"stale".to_vec(),
+            Bytes::from_static(b"[22]"),
+            Duration::ZERO,
+        );
+        assert_eq!(cache.get(b"fresh"), Some((Bytes::from_static(b"[1]"), 7)));
+        assert_eq!(cache.get(b"stale"), None);
+        assert_eq!(cache.get(b"fresh"), Some((Bytes::from_static(b"[1]"), 3)));
+    }
+
+    #[test]
+    fn evicts_the_responses_closest_to_expiring_when_full() {
+        let cache = ResponseCache::new(&configuration(10));
+        assert_eq!(
+            cache.insert(
+                b"a".to_vec(),
+                Bytes::from_static(b"aaaa"),
+                Duration::from_secs(60)
+            ),
+            Some(4)
+        );
+        assert_eq!(
+            cache.insert(
+                b"b".to_vec(),
+                Bytes::from_static(b"bbbb"),
+                Duration::from_secs(120)
+            ),
+            Some(8)
+        );
+        assert_eq!(
+            cache.insert(
+                b"c".to_vec(),
+                Bytes::from_static(b"cccc"),
+                Duration::from_secs(90)
+            ),
+            Some(8)
+        );
+        assert_eq!(cache.get(b"a"), None);
+        assert!(cache.get(b"b").is_some());
+        assert!(cache.get(b"c").is_some());
+    }
+
+    #[test]
+    fn evicts_expired_responses_first() {
+        let cache = ResponseCache::new(&configuration(10));
+        cache.insert(
+            b"a".to_vec(),
+            Bytes::from_static(b"aaaa"),
+            Duration::from_secs(60),
+        );
+        cache.insert(b"b".to_vec(), Bytes::from_static(b"bbbb"), Duration::ZERO);
+        assert_eq!(
+            cache.insert(
+                b"c".to_vec(),
+                Bytes::from_static(b"cccc"),
+                Duration::from_secs(90)
+            ),
+            Some(8)
+        );
+        assert!(cache.get(b"a").is_some());
+        assert!(cache.get(b"c").is_some());
+    }
+
+    #[test]
+    fn does_not_cache_responses_larger_than_the_cache() {
+        let cache = ResponseCache::new(&configuration(10));
+        cache.insert(
+            b"a".to_vec(),
+            Bytes::from_static(b"aaaa"),
+            Duration::from_secs(60),
+        );
+        assert_eq!(
+            cache.insert(
+                b"b".to_vec(),
+                Bytes::from_static(b"bbbbbbbbbbb"),
+                Duration::from_secs(60)
+            ),
+            None
+        );
+        assert_eq!(cache.get(b"b"), None);
+        assert!(cache.get(b"a").is_some());
+    }
+
+    #[test]
+    fn caches_responses_of_plain_requests() {
+        let configuration = configuration(1024);
+        assert!(!must_bypass(&configuration, &album_request(json!({}))));
+        assert!(!must_bypass(
+            &configuration,
+            &album_request(json!({ "as_of": { "type": "literal", "value": null } }))
+        ));
+    }
+
+    #[test]
+    fn bypasses_requests_reading_tables_as_of_a_point_in_time() {
+        let configuration = configuration(1024);
+        assert!(must_bypass(
+            &configuration,
+            &album_request(json!({
+                "as_of": { "type": "literal", "value": "2024-01-01T00:00:00Z" }
+            }))
+        ));
+        assert!(must_bypass(
+            &configuration,
+            &request(json!({
+                "collection": "Artist",
+                "arguments": {},
+                "query": {},
+                "collection_relationships": {
+                    "ArtistAlbums": {
+                        "column_mapping": { "ArtistId": "ArtistId" },
+                        "relationship_type": "array",
+                        "target_collection": "Album",
+                        "arguments": {
+                            "as_of": { "type": "literal", "value": "2024-01-01T00:00:00Z" }
+                        },
+                    },
+                },
+            }))
+        ));
+    }
+
+    #[test]
+    fn bypasses_requests_reading_volatile_native_queries() {
+        let configuration = configuration(1024);
+        assert!(must_bypass(
+            &configuration,
+            &request(json!({
+                "collection": "random_numbers",
+                "arguments": {},
+                "query": {},
+                "collection_relationships": {},
+            }))
+        ));
+        assert!(must_bypass(
+            &configuration,
+            &request(json!({
+                "collection": "Album",
+                "arguments": {},
+                "query": {},
+                "collection_relationships": {
+                    "Random": {
+                        "column_mapping": {},
+                        "relationship_type": "object",
+                        "target_collection": "random_numbers",
+                        "arguments": {},
+                    },
+                },
+            }))
+        ));
+    }
+
+    #[test]
+    fn bypasses_requests_asking_not_to_be_cached() {
+        let configuration = configuration(1024);
+        let with_cache_control = |value: &str| {
+            album_request(json!({
+                HEADERS_ARGUMENT: {
+                    "type": "literal",
+                    "value": { "Cache-Control": value },
+                },
+            }))
+        };
+        assert!(must_bypass(&configuration, &with_cache_control("no-cache")));
+        assert!(must_bypass(
+            &configuration,
+            &with_cache_control("max-age=0, No-Store")
+        ));
+        assert!(!must_bypass(
+            &configuration,
+            &with_cache_control("max-age=60")
+        ));
+    }
+}
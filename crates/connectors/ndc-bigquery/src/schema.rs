@@ -9,6 +9,10 @@ use ndc_sdk::connector;
 use ndc_sdk::models;
 use query_engine_metadata::metadata;
 use query_engine_metadata::metadata::OperatorKind;
+use query_engine_translation::translation::helpers::{
+    AS_OF_ARGUMENT, HEADERS_ARGUMENT, JSON_SCALAR_TYPE, STRING_SCALAR_TYPE, SUFFIX_FROM_ARGUMENT,
+    SUFFIX_TO_ARGUMENT, TIMESTAMP_SCALAR_TYPE,
+};
 
 use ndc_bigquery_configuration::configuration;
 
@@ -20,7 +24,7 @@ pub async fn get_schema(
     configuration: &configuration::Configuration,
 ) -> Result<models::SchemaResponse, connector::ErrorResponse> {
     let metadata = &configuration.metadata;
-    let scalar_types: BTreeMap<models::ScalarTypeName, models::ScalarType> = metadata
+    let mut scalar_types: BTreeMap<models::ScalarTypeName, models::ScalarType> = metadata
         .scalar_types
         .0
         .iter()
@@ -71,6 +75,38 @@ pub async fn get_schema(
         })
         .collect();
 
+    // Table collections accept an `as_of` timestamp for time-travel reads and wildcard tables
+    // accept string suffixes, so these types must be present even if no column uses them.
+    for (type_name, representation) in [
+        (TIMESTAMP_SCALAR_TYPE, models::TypeRepresentation::Timestamp),
+        (STRING_SCALAR_TYPE, models::TypeRepresentation::String),
+    ] {
+        scalar_types
+            .entry(type_name.into())
+            .or_insert_with(|| models::ScalarType {
+                representation: Some(representation),
+                aggregate_functions: BTreeMap::new(),
+                comparison_operators: BTreeMap::new(),
+            });
+    }
+
+    // Procedures return the rows of their final SELECT statement as json, and headers are
+    // forwarded as a json object.
+    let forwards_headers = metadata
+        .request_parameters
+        .0
+        .values()
+        .any(|parameter| matches!(parameter, metadata::RequestParameter::Header(_)));
+    if !metadata.routines.procedures.is_empty() || forwards_headers {
+        scalar_types
+            .entry(JSON_SCALAR_TYPE.into())
+            .or_insert_with(|| models::ScalarType {
+                representation: Some(models::TypeRepresentation::JSON),
+                aggregate_functions: BTreeMap::new(),
+                comparison_operators: BTreeMap::new(),
+            });
+    }
+
     let collections_by_identifier: BTreeMap<(&str, &str), &str> = metadata
         .tables
         .0
@@ -83,14 +119,14 @@ pub async fn get_schema(
         })
         .collect();
 
-    let collections = metadata
+    let mut collections: Vec<models::CollectionInfo> = metadata
         .tables
         .0
         .iter()
         .map(|(table_name, table)| models::CollectionInfo {
             name: table_name.clone(),
-            description: table.description.clone(),
-            arguments: BTreeMap::new(),
+            description: collection_description(table),
+            arguments: table_arguments(table),
             collection_type: table_name.as_str().into(),
             uniqueness_constraints: table
                 .uniqueness_constraints
@@ -146,7 +182,7 @@ pub async fn get_schema(
         })
         .collect();
 
-    let object_types = metadata //BTreeMap::from_iter(metadata.tables.0.iter().map(|(table_name, table)| {
+    let mut object_types = metadata //BTreeMap::from_iter(metadata.tables.0.iter().map(|(table_name, table)| {
         .tables
         .0
         .iter()
@@ -172,15 +208,264 @@ pub async fn get_schema(
         })
         .collect::<BTreeMap<_, _>>();
 
+    // Table functions are exposed as collections which take the function's arguments.
+    for (function_name, function_info) in &metadata.routines.table_functions {
+        collections.push(models::CollectionInfo {
+            name: function_name.clone(),
+            description: function_info.description.clone(),
+            arguments: routine_arguments(&function_info.arguments),
+            collection_type: function_name.as_str().into(),
+            uniqueness_constraints: BTreeMap::new(),
+            foreign_keys: BTreeMap::new(),
+        });
+        object_types.insert(
+            function_name.as_str().into(),
+            models::ObjectType {
+                description: function_info.description.clone(),
+                fields: function_info
+                    .columns
+                    .iter()
+                    .map(|(column_name, column_info)| {
+                        (
+                            column_name.clone(),
+                            models::ObjectField {
+                                description: column_info.description.clone(),
+                                r#type: readonly_column_to_type(column_info),
+                                arguments: BTreeMap::new(),
+                            },
+                        )
+                    })
+                    .collect(),
+            },
+        );
+    }
+
+    for (query_name, query_info) in &metadata.native_operations.queries.0 {
+        let (collection, object_type) = native_query_collection(query_name, query_info);
+        collections.push(collection);
+        object_types.insert(query_name.as_str().into(), object_type);
+    }
+
+    let mut functions: Vec<models::FunctionInfo> = metadata
+        .routines
+        .scalar_functions
+        .iter()
+        .map(|(function_name, function_info)| models::FunctionInfo {
+            name: function_name.clone(),
+            description: function_info.description.clone(),
+            arguments: routine_arguments(&function_info.arguments),
+            // BigQuery does not tell us whether a function may return NULL.
+            result_type: models::Type::Nullable {
+                underlying_type: Box::new(type_to_type(&function_info.return_type)),
+            },
+        })
+        .collect();
+
+    // Every collection and function accepts the arguments request parameters are taken from,
+    // so that the engine can pass them whatever is queried.
+    let request_parameter_arguments = request_parameter_arguments(&metadata.request_parameters);
+    let arguments = collections
+        .iter_mut()
+        .map(|collection| &mut collection.arguments)
+        .chain(functions.iter_mut().map(|function| &mut function.arguments));
+    for arguments in arguments {
+        for (argument_name, argument_info) in &request_parameter_arguments {
+            arguments
+                .entry(argument_name.clone())
+                .or_insert_with(|| argument_info.clone());
+        }
+    }
+
+    let procedures = metadata
+        .routines
+        .procedures
+        .iter()
+        .map(|(procedure_name, procedure_info)| models::ProcedureInfo {
+            name: procedure_name.clone(),
+            description: procedure_info.description.clone(),
+            arguments: routine_arguments(&procedure_info.arguments),
+            result_type: models::Type::Nullable {
+                underlying_type: Box::new(models::Type::Named {
+                    name: JSON_SCALAR_TYPE.into(),
+                }),
+            },
+        })
+        .collect();
+
     Ok(models::SchemaResponse {
         collections,
-        procedures: vec![],
-        functions: vec![],
+        procedures,
+        functions,
         object_types,
         scalar_types,
     })
 }
 
+/// Describe a table collection, including how it is partitioned and clustered,
+/// so that consumers know which filters make their queries cheap (or valid at all).
+fn collection_description(table: &metadata::TableInfo) -> Option<String> {
+    let mut notes = vec![];
+    if let Some(partition_column) = &table.partition_column {
+        if table.require_partition_filter {
+            notes.push(format!(
+                "Partitioned by '{partition_column}'; queries must filter on this column."
+            ));
+        } else {
+            notes.push(format!("Partitioned by '{partition_column}'."));
+        }
+    }
+    if !table.clustering_columns.is_empty() {
+        notes.push(format!(
+            "Clustered by '{}'.",
+            table.clustering_columns.join("', '")
+        ));
+    }
+
+    match (&table.description, notes.is_empty()) {
+        (description, true) => description.clone(),
+        (None, false) => Some(notes.join(" ")),
+        (Some(description), false) => Some(format!("{description}\n\n{}", notes.join(" "))),
+    }
+}
+
+/// The arguments a table collection accepts: wildcard tables can be restricted to a range of
+/// shards, and other tables can be read as of a point in time.
+fn table_arguments(
+    table: &metadata::TableInfo,
+) -> BTreeMap<models::ArgumentName, models::ArgumentInfo> {
+    if table.is_wildcard {
+        BTreeMap::from([
+            (
+                SUFFIX_FROM_ARGUMENT.into(),
+                nullable_argument(
+                    "Only read shards whose table suffix is at least this value",
+                    STRING_SCALAR_TYPE,
+                ),
+            ),
+            (
+                SUFFIX_TO_ARGUMENT.into(),
+                nullable_argument(
+                    "Only read shards whose table suffix is at most this value",
+                    STRING_SCALAR_TYPE,
+                ),
+            ),
+        ])
+    } else {
+        BTreeMap::from([(
+            AS_OF_ARGUMENT.into(),
+            nullable_argument(
+                "Read the table as it was at this point in time (FOR SYSTEM_TIME AS OF)",
+                TIMESTAMP_SCALAR_TYPE,
+            ),
+        )])
+    }
+}
+
+/// A native query is exposed as a collection which takes the native query's arguments, and
+/// whose type has the native query's columns as fields.
+fn native_query_collection(
+    query_name: &models::CollectionName,
+    query_info: &metadata::NativeQueryInfo,
+) -> (models::CollectionInfo, models::ObjectType) {
+    let collection = models::CollectionInfo {
+        name: query_name.clone(),
+        description: query_info.description.clone(),
+        arguments: query_info
+            .arguments
+            .iter()
+            .map(|(argument_name, argument_info)| {
+                (
+                    argument_name.clone(),
+                    models::ArgumentInfo {
+                        description: argument_info.description.clone(),
+                        argument_type: readonly_column_to_type(argument_info),
+                    },
+                )
+            })
+            .collect(),
+        collection_type: query_name.as_str().into(),
+        uniqueness_constraints: BTreeMap::new(),
+        foreign_keys: BTreeMap::new(),
+    };
+    let object_type = models::ObjectType {
+        description: query_info.description.clone(),
+        fields: query_info
+            .columns
+            .iter()
+            .map(|(column_name, column_info)| {
+                (
+                    column_name.clone(),
+                    models::ObjectField {
+                        description: column_info.description.clone(),
+                        r#type: readonly_column_to_type(column_info),
+                        arguments: BTreeMap::new(),
+                    },
+                )
+            })
+            .collect(),
+    };
+    (collection, object_type)
+}
+
+/// The arguments request parameters are taken from: the arguments they are bound to, and the
+/// forwarded headers. Any of them may be null.
+fn request_parameter_arguments(
+    request_parameters: &metadata::RequestParameters,
+) -> BTreeMap<models::ArgumentName, models::ArgumentInfo> {
+    request_parameters
+        .0
+        .iter()
+        .map(|(parameter_name, parameter)| match parameter {
+            metadata::RequestParameter::Argument(argument_name) => (
+                argument_name.clone(),
+                nullable_argument(
+                    &format!("The value of the request parameter @{parameter_name}"),
+                    STRING_SCALAR_TYPE,
+                ),
+            ),
+            metadata::RequestParameter::Header(_) => (
+                HEADERS_ARGUMENT.into(),
+                nullable_argument(
+                    "The forwarded headers request parameters are taken from",
+                    JSON_SCALAR_TYPE,
+                ),
+            ),
+        })
+        .collect()
+}
+
+/// The arguments of a routine. Any of them may be null.
+fn routine_arguments(
+    arguments: &[metadata::RoutineArgument],
+) -> BTreeMap<models::ArgumentName, models::ArgumentInfo> {
+    arguments
+        .iter()
+        .map(|argument| {
+            (
+                argument.name.clone(),
+                models::ArgumentInfo {
+                    description: argument.description.clone(),
+                    argument_type: models::Type::Nullable {
+                        underlying_type: Box::new(type_to_type(&argument.r#type)),
+                    },
+                },
+            )
+        })
+        .collect()
+}
+
+/// Describe an optional argument of a named scalar type.
+fn nullable_argument(description: &str, scalar_type: &str) -> models::ArgumentInfo {
+    models::ArgumentInfo {
+        description: Some(description.to_string()),
+        argument_type: models::Type::Nullable {
+            underlying_type: Box::new(models::Type::Named {
+                name: scalar_type.into(),
+            }),
+        },
+    }
+}
+
 /// Map our local type representation to ndc-spec type representation.
 #[allow(clippy::match_same_arms)] // merging arms would require changing the order, making this harder to understand
 fn map_type_representation(
@@ -252,3 +537,92 @@ pub fn type_to_type(typ: &metadata::Type) -> models::Type {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn exposes_native_queries_as_collections() {
+        let query_info: metadata::NativeQueryInfo = serde_json::from_value(json!({
+            "sql": { "NativeQuerySql": { "Inline": { "sql": [
+                { "Text": "SELECT Title FROM albums WHERE ArtistId = " },
+                { "Parameter": "artist_id" },
+            ] } } },
+            "columns": {
+                "Title": {
+                    "name": "Title",
+                    "type": { "scalarType": "string" },
+                    "nullable": "nonNullable",
+                    "description": "The title of the album",
+                },
+            },
+            "arguments": {
+                "artist_id": {
+                    "name": "artist_id",
+                    "type": { "scalarType": "int64" },
+                    "nullable": "nullable",
+                    "description": null,
+                },
+            },
+            "description": "The albums of an artist",
+        }))
+        .unwrap();
+
+        let (collection, object_type) =
+            native_query_collection(&"albums_by_artist".into(), &query_info);
+
+        assert_eq!(collection.name.as_str(), "albums_by_artist");
+        assert_eq!(collection.collection_type.as_str(), "albums_by_artist");
+        assert_eq!(
+            collection.description.as_deref(),
+            Some("The albums of an artist")
+        );
+        assert_eq!(
+            collection.arguments["artist_id"].argument_type,
+            models::Type::Nullable {
+                underlying_type: Box::new(models::Type::Named {
+                    name: "int64".into()
+                })
+            }
+        );
+        assert_eq!(
+            object_type.fields["Title"].r#type,
+            models::Type::Named {
+                name: "string".into()
+            }
+        );
+        assert_eq!(
+            object_type.fields["Title"].description.as_deref(),
+            Some("The title of the album")
+        );
+    }
+
+    #[test]
+    fn every_collection_accepts_the_arguments_of_request_parameters() {
+        let request_parameters: metadata::RequestParameters = serde_json::from_value(json!({
+            "hasura_user_id": { "header": "X-Hasura-User-Id" },
+            "region": { "argument": "region" },
+        }))
+        .unwrap();
+
+        let arguments = request_parameter_arguments(&request_parameters);
+
+        assert_eq!(
+            arguments
+                .keys()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>(),
+            vec![HEADERS_ARGUMENT, "region"]
+        );
+        assert_eq!(
+            arguments["region"].argument_type,
+            models::Type::Nullable {
+                underlying_type: Box::new(models::Type::Named {
+                    name: STRING_SCALAR_TYPE.into()
+                })
+            }
+        );
+    }
+}
@@ -2,25 +2,38 @@
 //!
 //! This is initialized on startup.
 
+use std::time::Duration;
+
 use thiserror::Error;
 use tracing::{info_span, Instrument};
 
-// use ndc_bigquery_configuration::PoolSettings;
-// use ndc_bigquery_configuration::ConfigurationError;
-// use super::configuration::{Configuration, ConfigurationError};
-// use query_engine_execution::database_info::{self, DatabaseInfo, DatabaseVersion};
+use ndc_bigquery_configuration::values::{PoolSettings, StorageReadMode};
+use query_engine_execution::concurrency::JobSlots;
 use query_engine_execution::metrics;
+use query_engine_execution::storage_read;
+
+use crate::audit_log::AuditLog;
+use crate::dry_run_cache::DryRunCache;
+use crate::plan_cache::PlanCache;
+use crate::response_cache::ResponseCache;
 
 /// State for our connector.
 #[derive(Clone)]
 pub struct State {
     pub metrics: metrics::Metrics,
     pub bigquery_client: gcp_bigquery_client::Client,
+    /// The client of the Storage Read API, unless reading results through it is disabled.
+    pub storage_read_client: Option<storage_read::Client>,
+    pub job_slots: JobSlots,
+    pub dry_run_cache: Option<DryRunCache>,
+    pub plan_cache: Option<PlanCache>,
+    pub response_cache: Option<ResponseCache>,
+    pub audit_log: Option<AuditLog>,
     pub project_id: String,
     pub dataset_id: String,
 }
 
-/// Create a connection pool and wrap it inside a connector State.
+/// Create the BigQuery clients and wrap them inside a connector State.
 pub async fn create_state(
     configuration: &ndc_bigquery_configuration::Configuration,
     metrics_registry: &mut prometheus::Registry,
@@ -38,23 +51,71 @@ pub async fn create_state(
 
     // Init BigQuery client
     let bigquery_client =
-        gcp_bigquery_client::Client::from_service_account_key(service_account_key, false)
+        gcp_bigquery_client::Client::from_service_account_key(service_account_key.clone(), false)
             .await
             .unwrap();
 
+    // Init Storage Read API client, used to read large results
+    let storage_read_client = match configuration.query_settings.storage_read.mode {
+        StorageReadMode::Disabled => None,
+        StorageReadMode::Automatic | StorageReadMode::Always => Some(
+            storage_read::Client::new(service_account_key)
+                .await
+                .map_err(InitializationError::StorageReadClient)?,
+        ),
+    };
+
+    let job_slots = job_slots(&configuration.pool_settings);
+
+    let audit_log = match &configuration.audit_log {
+        None => None,
+        Some(settings) => Some(
+            AuditLog::new(settings)
+                .await
+                .map_err(InitializationError::AuditLog)?,
+        ),
+    };
+
     Ok(State {
         metrics,
         bigquery_client,
+        storage_read_client,
+        job_slots,
+        dry_run_cache: configuration
+            .query_settings
+            .dry_run
+            .cache_ttl_seconds
+            .map(|ttl| DryRunCache::new(Duration::from_secs(ttl))),
+        plan_cache: match configuration.query_settings.plan_cache.capacity {
+            0 => None,
+            capacity => Some(PlanCache::new(capacity)),
+        },
+        response_cache: configuration
+            .query_settings
+            .response_cache
+            .is_enabled()
+            .then(|| ResponseCache::new(configuration)),
+        audit_log,
         project_id: configuration.project_id.clone(),
         dataset_id: configuration.dataset_id.clone(),
     })
 }
 
+/// The slots limiting how many BigQuery jobs run at once, from the pool settings.
+fn job_slots(pool_settings: &PoolSettings) -> JobSlots {
+    JobSlots::new(
+        usize::try_from(pool_settings.max_connections).unwrap_or(usize::MAX),
+        Duration::from_secs(pool_settings.pool_timeout),
+    )
+}
+
 /// State initialization error.
 #[derive(Debug, Error)]
 pub enum InitializationError {
-    #[error("unable to initialize connection pool: {0}")]
-    UnableToCreatePool(sqlx::Error),
     #[error("error initializing metrics: {0}")]
     MetricsError(prometheus::Error),
+    #[error("error initializing the Storage Read API client: {0}")]
+    StorageReadClient(storage_read::Error),
+    #[error("error opening the audit log: {0}")]
+    AuditLog(std::io::Error),
 }
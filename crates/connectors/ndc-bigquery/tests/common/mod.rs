@@ -22,15 +22,15 @@ pub async fn create_router_from_deployment(deployment_path: &str) -> axum::Route
 
     let environment = HashMap::from([
         (
-            ndc_bigquery_configuration::version1::DEFAULT_SERVICE_KEY_VARIABLE.into(),
+            ndc_bigquery_configuration::connection_settings::DEFAULT_SERVICE_KEY_VARIABLE.into(),
             HASURA_BIGQUERY_SERVICE_KEY.to_string(),
         ),
         (
-            ndc_bigquery_configuration::version1::DEFAULT_PROJECT_ID_VARIABLE.into(),
+            ndc_bigquery_configuration::connection_settings::DEFAULT_PROJECT_ID_VARIABLE.into(),
             HASURA_BIGQUERY_PROJECT_ID.to_string(),
         ),
         (
-            ndc_bigquery_configuration::version1::DEFAULT_DATASET_ID_VARIABLE.into(),
+            ndc_bigquery_configuration::connection_settings::DEFAULT_DATASET_ID_VARIABLE.into(),
             HASURA_BIGQUERY_DATASET_ID.to_string(),
         ),
     ]);
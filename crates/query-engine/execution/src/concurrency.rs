@@ -0,0 +1,91 @@
+//! Limit how many BigQuery jobs the connector runs at once.
+//!
+//! Each job takes a slot before it is sent to BigQuery, and gives it back once it completes.
+//! Jobs wait for a slot when every slot is taken, and fail if none becomes available in time.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::{Error, QueryError};
+use crate::metrics;
+
+/// The slots shared by the jobs of every request.
+#[derive(Debug, Clone)]
+pub struct JobSlots {
+    semaphore: Arc<Semaphore>,
+    /// How long a job waits for a slot before failing.
+    acquire_timeout: Duration,
+}
+
+/// A slot taken by a running job. The slot is given back when this is dropped.
+pub struct JobSlot {
+    _permit: OwnedSemaphorePermit,
+    metrics: metrics::Metrics,
+}
+
+impl JobSlots {
+    pub fn new(max_jobs: usize, acquire_timeout: Duration) -> JobSlots {
+        JobSlots {
+            semaphore: Arc::new(Semaphore::new(max_jobs.min(Semaphore::MAX_PERMITS))),
+            acquire_timeout,
+        }
+    }
+
+    /// Wait for a slot to run a job, recording how long we waited, and the job as in flight.
+    pub async fn acquire(&self, metrics: &metrics::Metrics) -> Result<JobSlot, Error> {
+        let started = Instant::now();
+        let permit = tokio::time::timeout(
+            self.acquire_timeout,
+            Arc::clone(&self.semaphore).acquire_owned(),
+        )
+        .await
+        .ok()
+        // the semaphore is never closed.
+        .and_then(Result::ok)
+        .ok_or(Error::Query(QueryError::JobSlotUnavailable {
+            timeout_seconds: self.acquire_timeout.as_secs(),
+        }))?;
+        metrics.record_job_slot_acquired(started.elapsed());
+        Ok(JobSlot {
+            _permit: permit,
+            metrics: metrics.clone(),
+        })
+    }
+}
+
+/// There is no limit on the number of jobs by default.
+impl Default for JobSlots {
+    fn default() -> JobSlots {
+        JobSlots::new(Semaphore::MAX_PERMITS, Duration::MAX)
+    }
+}
+
+impl Drop for JobSlot {
+    fn drop(&mut self) {
+        self.metrics.record_job_slot_released();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_for_a_slot_until_the_timeout() {
+        let metrics = metrics::Metrics::initialize(&mut prometheus::Registry::new()).unwrap();
+        let slots = JobSlots::new(1, Duration::from_secs(5));
+
+        let slot = slots.acquire(&metrics).await.unwrap();
+        assert!(matches!(
+            slots.acquire(&metrics).await,
+            Err(Error::Query(QueryError::JobSlotUnavailable {
+                timeout_seconds: 5
+            }))
+        ));
+
+        drop(slot);
+        assert!(slots.acquire(&metrics).await.is_ok());
+    }
+}
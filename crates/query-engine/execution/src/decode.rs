@@ -0,0 +1,325 @@
+//! Decode the rows of a BigQuery result according to the schema returned with the job.
+//!
+//! BigQuery returns every cell as a string (or `null`), repeated cells as arrays of cells,
+//! and record cells as rows. We convert them to the JSON BigQuery renders for the same values
+//! when the rows are selected as JSON, so that both give the same results. Columns whose type
+//! is represented as a string (such as 64-bit integers) are cast to strings in both queries.
+
+use gcp_bigquery_client::model::field_type::FieldType;
+use gcp_bigquery_client::model::table_field_schema::TableFieldSchema;
+use gcp_bigquery_client::model::table_row::TableRow;
+use ndc_models as models;
+use serde_json::{Map, Number, Value};
+
+use crate::error::Error;
+
+/// Decode a row into an object whose keys are the requested fields.
+pub(crate) fn decode_row(
+    fields: &[models::FieldName],
+    schema: &[TableFieldSchema],
+    row: &TableRow,
+) -> Result<Map<String, Value>, Error> {
+    let cells = row.columns.as_deref().unwrap_or_default();
+    if cells.len() != fields.len() || schema.len() != fields.len() {
+        return Err(Error::UnexpectedResult(format!(
+            "expected {} columns, got {} columns and {} schema fields",
+            fields.len(),
+            cells.len(),
+            schema.len()
+        )));
+    }
+    fields
+        .iter()
+        .zip(schema)
+        .zip(cells)
+        .map(|((field, field_schema), cell)| {
+            let value = cell.value.as_ref().unwrap_or(&Value::Null);
+            Ok((field.to_string(), decode_value(field_schema, value)?))
+        })
+        .collect()
+}
+
+/// Decode the value of a cell.
+fn decode_value(field: &TableFieldSchema, value: &Value) -> Result<Value, Error> {
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::Array(items) if field.mode.as_deref() == Some("REPEATED") => items
+            .iter()
+            .map(|item| decode_single_value(field, item.get("v").unwrap_or(&Value::Null)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        _ => decode_single_value(field, value),
+    }
+}
+
+/// Decode a value which is not repeated, according to its type.
+fn decode_single_value(field: &TableFieldSchema, value: &Value) -> Result<Value, Error> {
+    if value.is_null() {
+        return Ok(Value::Null);
+    }
+    match type_name(&field.r#type).as_str() {
+        "RECORD" | "STRUCT" => decode_record(field, value),
+        // integers and decimals are rendered as JSON numbers.
+        "INTEGER" | "INT64" => {
+            let string = as_str(value)?;
+            string
+                .parse::<i64>()
+                .map(|integer| Value::Number(integer.into()))
+                .map_err(|_| unexpected_value(field, string))
+        }
+        "NUMERIC" | "BIGNUMERIC" => {
+            let string = as_str(value)?;
+            string
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(|| unexpected_value(field, string))
+        }
+        "FLOAT" | "FLOAT64" => {
+            let string = as_str(value)?;
+            // NaN and infinities cannot be represented as JSON numbers.
+            Ok(string
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map_or_else(|| Value::String(string.into()), Value::Number))
+        }
+        "BOOLEAN" | "BOOL" => match as_str(value)? {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            other => Err(unexpected_value(field, other)),
+        },
+        "TIMESTAMP" => {
+            let string = as_str(value)?;
+            timestamp_micros(string)
+                .map(|micros| Value::String(format_timestamp(micros)))
+                .ok_or_else(|| unexpected_value(field, string))
+        }
+        "JSON" => {
+            let string = as_str(value)?;
+            serde_json::from_str(string).map_err(|_| unexpected_value(field, string))
+        }
+        // BYTES are already base64-encoded, and the other types are represented as their
+        // canonical string: DATE, TIME, DATETIME, STRING, GEOGRAPHY, INTERVAL, ...
+        _ => Ok(value.clone()),
+    }
+}
+
+/// Decode a record, represented as a row, into an object.
+fn decode_record(field: &TableFieldSchema, value: &Value) -> Result<Value, Error> {
+    let sub_fields = field.fields.as_deref().unwrap_or_default();
+    let cells = value
+        .get("f")
+        .and_then(Value::as_array)
+        .ok_or_else(|| unexpected_value(field, &value.to_string()))?;
+    sub_fields
+        .iter()
+        .zip(cells)
+        .map(|(sub_field, cell)| {
+            let value = cell.get("v").unwrap_or(&Value::Null);
+            Ok((sub_field.name.clone(), decode_value(sub_field, value)?))
+        })
+        .collect::<Result<Map<_, _>, _>>()
+        .map(Value::Object)
+}
+
+/// The name of a field type, as BigQuery writes it.
+fn type_name(field_type: &FieldType) -> String {
+    serde_json::to_value(field_type)
+        .ok()
+        .and_then(|name| name.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn as_str(value: &Value) -> Result<&str, Error> {
+    value
+        .as_str()
+        .ok_or_else(|| Error::UnexpectedResult(format!("expected a string cell, got {value}")))
+}
+
+fn unexpected_value(field: &TableFieldSchema, value: &str) -> Error {
+    Error::UnexpectedResult(format!(
+        "unexpected value {value:?} for column {:?} of type {}",
+        field.name,
+        type_name(&field.r#type)
+    ))
+}
+
+/// Timestamps are represented as microseconds since the epoch, or as floating-point
+/// seconds since the epoch (e.g. `1.7031744E9`).
+#[allow(clippy::cast_possible_truncation)]
+fn timestamp_micros(value: &str) -> Option<i64> {
+    if value.contains(['.', 'E', 'e']) {
+        let seconds = value.parse::<f64>().ok()?;
+        seconds
+            .is_finite()
+            .then(|| (seconds * 1_000_000.0).round() as i64)
+    } else {
+        value.parse().ok()
+    }
+}
+
+/// Format microseconds since the epoch as an RFC 3339 timestamp in UTC.
+pub(crate) fn format_timestamp(micros: i64) -> String {
+    let date = format_date(micros.div_euclid(86_400_000_000));
+    let time = format_time(micros.rem_euclid(86_400_000_000));
+    format!("{date}T{time}Z")
+}
+
+/// Format a number of days since the epoch as a date (`YYYY-MM-DD`).
+pub(crate) fn format_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Format a number of microseconds since midnight as a time (`HH:MM:SS[.ffffff]`).
+pub(crate) fn format_time(micros: i64) -> String {
+    let seconds = micros.div_euclid(1_000_000);
+    let fraction = micros.rem_euclid(1_000_000);
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    );
+    if fraction == 0 {
+        time
+    } else {
+        format!("{time}.{fraction:06}")
+    }
+}
+
+/// The proleptic Gregorian date of a number of days since the epoch.
+/// See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn field(name: &str, field_type: FieldType) -> TableFieldSchema {
+        TableFieldSchema::new(name, field_type)
+    }
+
+    #[test]
+    fn scalars_are_decoded_according_to_their_type() {
+        let schema = vec![
+            field("id", FieldType::Int64),
+            field("price", FieldType::Numeric),
+            field("ratio", FieldType::Float64),
+            field("active", FieldType::Bool),
+            field("created", FieldType::Timestamp),
+            field("data", FieldType::Bytes),
+            field("missing", FieldType::String),
+        ];
+        let fields: Vec<models::FieldName> = schema
+            .iter()
+            .map(|field| field.name.clone().into())
+            .collect();
+        let row: TableRow = serde_json::from_value(json!({
+            "f": [
+                { "v": "9007199254740993" },
+                { "v": "12.50" },
+                { "v": "0.25" },
+                { "v": "true" },
+                { "v": "1.7031744E9" },
+                { "v": "aGVsbG8=" },
+                { "v": null }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            Value::Object(decode_row(&fields, &schema, &row).unwrap()),
+            json!({
+                "id": 9_007_199_254_740_993_i64,
+                "price": 12.5,
+                "ratio": 0.25,
+                "active": true,
+                "created": "2023-12-21T16:00:00Z",
+                "data": "aGVsbG8=",
+                "missing": null
+            })
+        );
+    }
+
+    #[test]
+    fn repeated_and_record_cells_are_decoded() {
+        let mut tags = field("tags", FieldType::Int64);
+        tags.mode = Some("REPEATED".to_string());
+        let address = TableFieldSchema::record(
+            "address",
+            vec![
+                field("city", FieldType::String),
+                field("zip", FieldType::Int64),
+            ],
+        );
+        let value = json!([{ "v": "1" }, { "v": "2" }]);
+        assert_eq!(decode_value(&tags, &value).unwrap(), json!([1, 2]));
+
+        let value = json!({ "f": [{ "v": "Lisbon" }, { "v": null }] });
+        assert_eq!(
+            decode_value(&address, &value).unwrap(),
+            json!({ "city": "Lisbon", "zip": null })
+        );
+    }
+
+    #[test]
+    fn timestamps_are_formatted_as_rfc3339() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(951_782_400_000_001),
+            "2000-02-29T00:00:00.000001Z"
+        );
+        assert_eq!(format_timestamp(-1), "1969-12-31T23:59:59.999999Z");
+        assert_eq!(
+            timestamp_micros("1703174400000000"),
+            Some(1_703_174_400_000_000)
+        );
+        assert_eq!(timestamp_micros("1.7031744E9"), Some(1_703_174_400_000_000));
+    }
+
+    #[test]
+    fn typed_rows_are_decoded_like_json_rows() {
+        // `id` is an INT64 column, and `total` a NUMERIC column cast to a string.
+        let schema = vec![
+            field("id", FieldType::Int64),
+            field("price", FieldType::Numeric),
+            field("total", FieldType::String),
+        ];
+        let fields: Vec<models::FieldName> = schema
+            .iter()
+            .map(|field| field.name.clone().into())
+            .collect();
+        let row: TableRow = serde_json::from_value(json!({
+            "f": [{ "v": "42" }, { "v": "0.5" }, { "v": "1234.56" }]
+        }))
+        .unwrap();
+        // the same row, as BigQuery renders it with JSON_OBJECT.
+        let json_row: Value =
+            serde_json::from_str(r#"{"id":42,"price":0.5,"total":"1234.56"}"#).unwrap();
+
+        assert_eq!(
+            Value::Object(decode_row(&fields, &schema, &row).unwrap()),
+            json_row
+        );
+    }
+}
@@ -1,10 +1,16 @@
+use gcp_bigquery_client::error::BQError;
+
 /// Errors
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("{0}")]
     Query(QueryError),
     #[error("{0}")]
-    DB(sqlx::Error),
+    BigQuery(BigQueryError),
+    #[error("{0}")]
+    StorageRead(crate::storage_read::Error),
+    #[error("Unexpected result from BigQuery: {0}")]
+    UnexpectedResult(String),
 }
 
 /// Query planning error.
@@ -15,32 +21,206 @@ pub enum QueryError {
     #[error("{0} are not supported.")]
     NotSupported(String),
     #[error("{0}")]
-    DBError(sqlx::Error),
-    #[error("{0}")]
-    DBConstraintError(sqlx::Error),
+    UnsupportedSql(query_engine_sql::sql::dialect::Error),
     #[error("Mutation constraint failed.")]
     MutationConstraintFailed,
+    #[error("The query returned more than the maximum of {max_rows} rows.")]
+    TooManyRows { max_rows: u64 },
+    #[error("The query result is larger than the maximum of {max_result_size} bytes.")]
+    ResultTooLarge { max_result_size: u64 },
+    #[error("The query exceeded the maximum number of bytes billed: {0}")]
+    BytesBilledLimitExceeded(String),
+    #[error(
+        "The query would process {bytes_processed} bytes, more than the budget of {budget} bytes."
+    )]
+    BytesProcessedBudgetExceeded { bytes_processed: u64, budget: u64 },
+    #[error("The query did not complete within {timeout_seconds} seconds, and was cancelled.")]
+    TimedOut { timeout_seconds: u64 },
+    #[error(
+        "Too many queries are running: no BigQuery job slot became available within {timeout_seconds} seconds."
+    )]
+    JobSlotUnavailable { timeout_seconds: u64 },
+}
+
+/// An error from BigQuery, classified by the reason BigQuery gives for it.
+#[derive(Debug)]
+pub struct BigQueryError {
+    pub reason: Reason,
+    pub error: BQError,
+}
+
+/// Why a BigQuery request failed.
+/// See <https://cloud.google.com/bigquery/docs/error-messages>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// The query is invalid (`invalidQuery`, `invalid`).
+    InvalidQuery,
+    /// We are not allowed to run the query, or to read a table (`accessDenied`).
+    AccessDenied,
+    /// A quota or rate limit was reached (`quotaExceeded`, `rateLimitExceeded`). The request
+    /// may succeed if it is retried later.
+    QuotaExceeded,
+    /// A dataset, table or job does not exist (`notFound`).
+    NotFound,
+    /// BigQuery failed (`backendError`, `internalError`). The request may succeed if it is
+    /// retried.
+    BackendError,
+    /// Any other reason, including failing to reach BigQuery at all.
+    Other,
+}
+
+impl Reason {
+    /// Classify a reason, as BigQuery names it.
+    fn from_name(name: &str) -> Option<Reason> {
+        match name {
+            "invalidQuery" | "invalid" => Some(Reason::InvalidQuery),
+            "accessDenied" => Some(Reason::AccessDenied),
+            "quotaExceeded" | "rateLimitExceeded" => Some(Reason::QuotaExceeded),
+            "notFound" => Some(Reason::NotFound),
+            "backendError" | "internalError" => Some(Reason::BackendError),
+            _ => None,
+        }
+    }
+
+    /// Classify an error by its HTTP status code, when none of its reasons are known.
+    fn from_status_code(code: i64) -> Reason {
+        match code {
+            400 => Reason::InvalidQuery,
+            403 => Reason::AccessDenied,
+            404 => Reason::NotFound,
+            429 => Reason::QuotaExceeded,
+            500..=599 => Reason::BackendError,
+            _ => Reason::Other,
+        }
+    }
+
+    /// Whether a request which failed for this reason may succeed if it is retried.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Reason::QuotaExceeded | Reason::BackendError)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reason::InvalidQuery => "invalidQuery",
+            Reason::AccessDenied => "accessDenied",
+            Reason::QuotaExceeded => "quotaExceeded",
+            Reason::NotFound => "notFound",
+            Reason::BackendError => "backendError",
+            Reason::Other => "other",
+        }
+    }
 }
 
-impl From<sqlx::Error> for Error {
-    fn from(err: sqlx::Error) -> Error {
-        match err
-            .as_database_error()
-            .and_then(|e| e.try_downcast_ref())
-            .map(|e: &sqlx::postgres::PgDatabaseError| e.code())
-        {
-            None => Error::DB(err),
-            Some(code) => {
-                // We want to map data and constraint exceptions to query errors
-                // https://www.postgresql.org/docs/current/errcodes-appendix.html
-                if code.starts_with("22") {
-                    Error::Query(QueryError::DBError(err))
-                } else if code.starts_with("23") {
-                    Error::Query(QueryError::DBConstraintError(err))
-                } else {
-                    Error::DB(err)
+impl std::fmt::Display for BigQueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.error {
+            BQError::ResponseError { error } => write!(f, "{}", error.error.message),
+            error => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for BigQueryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<BQError> for Error {
+    /// Classify an error from BigQuery. Exceeding the maximum bytes billed is caused by the job
+    /// settings rather than by BigQuery, so it is a query error.
+    fn from(error: BQError) -> Error {
+        let reason = match &error {
+            BQError::ResponseError { error: response } => {
+                let reasons: Vec<&str> = response
+                    .error
+                    .errors
+                    .iter()
+                    .filter_map(|details| details.get("reason").map(String::as_str))
+                    .collect();
+                if reasons.contains(&"bytesBilledLimitExceeded") {
+                    return Error::Query(QueryError::BytesBilledLimitExceeded(
+                        response.error.message.clone(),
+                    ));
                 }
+                reasons
+                    .into_iter()
+                    .find_map(Reason::from_name)
+                    .unwrap_or_else(|| Reason::from_status_code(response.error.code))
             }
+            _ => Reason::Other,
+        };
+        Error::BigQuery(BigQueryError { reason, error })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gcp_bigquery_client::error::{NestedResponseError, ResponseError};
+    use std::collections::HashMap;
+
+    fn response_error(code: i64, reason: &str) -> BQError {
+        BQError::ResponseError {
+            error: ResponseError {
+                error: NestedResponseError {
+                    code,
+                    errors: vec![HashMap::from([("reason".to_string(), reason.to_string())])],
+                    message: "message".to_string(),
+                    status: String::new(),
+                },
+            },
         }
     }
+
+    fn reason(error: BQError) -> Option<Reason> {
+        match Error::from(error) {
+            Error::BigQuery(error) => Some(error.reason),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn classifies_errors_by_reason() {
+        assert_eq!(
+            reason(response_error(400, "invalidQuery")),
+            Some(Reason::InvalidQuery)
+        );
+        assert_eq!(
+            reason(response_error(403, "accessDenied")),
+            Some(Reason::AccessDenied)
+        );
+        assert_eq!(
+            reason(response_error(403, "rateLimitExceeded")),
+            Some(Reason::QuotaExceeded)
+        );
+        assert_eq!(
+            reason(response_error(404, "notFound")),
+            Some(Reason::NotFound)
+        );
+        assert_eq!(
+            reason(response_error(503, "backendError")),
+            Some(Reason::BackendError)
+        );
+    }
+
+    #[test]
+    fn classifies_unknown_reasons_by_status_code() {
+        assert_eq!(
+            reason(response_error(429, "somethingElse")),
+            Some(Reason::QuotaExceeded)
+        );
+        assert_eq!(
+            reason(response_error(418, "somethingElse")),
+            Some(Reason::Other)
+        );
+    }
+
+    #[test]
+    fn bytes_billed_limit_exceeded_is_a_query_error() {
+        assert!(matches!(
+            Error::from(response_error(400, "bytesBilledLimitExceeded")),
+            Error::Query(QueryError::BytesBilledLimitExceeded(_))
+        ));
+    }
 }
@@ -1,6 +1,12 @@
-//! Query execution against a PostgreSQL database.
+//! Query execution against BigQuery.
 //! See `/architecture.md#execution` in the repository for more details.
 
+pub mod concurrency;
+mod decode;
 pub mod error;
 pub mod metrics;
+pub mod mutation;
 pub mod query;
+pub mod retry;
+pub mod storage_read;
+pub mod trace;
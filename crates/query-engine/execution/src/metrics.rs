@@ -2,92 +2,288 @@
 
 use std::time::Duration;
 
-use prometheus::{Gauge, Histogram, HistogramTimer, IntCounter, IntGauge, Registry};
+use prometheus::core::Collector;
+use prometheus::{
+    GaugeVec, Histogram, HistogramTimer, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    Registry,
+};
+
+/// The label of metrics which are recorded per collection.
+const COLLECTION_LABEL: &str = "collection";
 
 /// The collection of all metrics exposed through the `/metrics` endpoint.
 #[derive(Debug, Clone)]
 pub struct Metrics {
     query_total: IntCounter,
+    mutation_total: IntCounter,
     explain_total: IntCounter,
     query_plan_time: Histogram,
     query_execution_time: Histogram,
-    pool_max_connections: IntGauge,
-    pool_min_connections: IntGauge,
-    pool_acquire_timeout: Gauge,
-    pool_max_lifetime: Gauge,
-    pool_idle_timeout: Gauge,
+    mutation_plan_time: Histogram,
+    mutation_execution_time: Histogram,
+    query_dry_run_bytes_processed: Histogram,
+    query_dry_run_cache_hit_total: IntCounter,
+    query_plan_cache_hit_total: IntCounter,
+    query_plan_cache_miss_total: IntCounter,
+    query_response_cache_hit_total: IntCounterVec,
+    query_response_cache_miss_total: IntCounterVec,
+    query_response_cache_bypassed_total: IntCounterVec,
+    query_response_cache_size_bytes: IntGauge,
+    query_dry_run_rejected_total: IntCounterVec,
+    query_timed_out_total: IntCounter,
+    query_cancelled_total: IntCounter,
+    bigquery_request_retried_total: IntCounter,
+    jobs_in_flight: IntGauge,
+    job_slot_wait_time: Histogram,
+    job_total: IntCounterVec,
+    job_bytes_processed_total: IntCounterVec,
+    job_bytes_billed_total: IntCounterVec,
+    job_slot_milliseconds_total: IntCounterVec,
+    job_cache_hit_total: IntCounterVec,
+    job_cache_hit_ratio: GaugeVec,
+    job_queue_time: HistogramVec,
+    job_wait_time: HistogramVec,
+    query_rows_returned_total: IntCounterVec,
     pub error_metrics: ErrorMetrics,
 }
 
+/// The statistics of a completed BigQuery job.
+#[derive(Debug, Clone, Default)]
+pub struct JobStatistics {
+    /// The number of bytes the job processed.
+    pub bytes_processed: Option<u64>,
+    /// The number of bytes the job billed.
+    pub bytes_billed: Option<u64>,
+    /// The slot milliseconds the job consumed.
+    pub slot_milliseconds: Option<u64>,
+    /// Whether the results were read from the query cache.
+    pub cache_hit: bool,
+    /// The time between the creation of the job and the start of its execution.
+    pub queue_time: Option<Duration>,
+    /// The time between the creation of the job and its completion.
+    pub wait_time: Option<Duration>,
+}
+
 impl Metrics {
     /// Set up counters and gauges used to produce Prometheus metrics
     pub fn initialize(metrics_registry: &mut Registry) -> Result<Self, prometheus::Error> {
         let query_total = add_int_counter_metric(
             metrics_registry,
-            "postgres_ndc_query_total",
+            "bigquery_ndc_query_total",
             "Total successful queries.",
         )?;
 
+        let mutation_total = add_int_counter_metric(
+            metrics_registry,
+            "bigquery_ndc_mutation_total",
+            "Total successful mutations.",
+        )?;
+
         let explain_total = add_int_counter_metric(
             metrics_registry,
-            "postgres_ndc_explain_total",
+            "bigquery_ndc_explain_total",
             "Total successful explains.",
         )?;
 
         let query_plan_time = add_histogram_metric(
             metrics_registry,
-            "postgres_ndc_query_plan_time",
+            "bigquery_ndc_query_plan_time",
             "Time taken to plan a query for execution, in seconds.",
         )?;
 
         let query_execution_time = add_histogram_metric(
             metrics_registry,
-            "postgres_ndc_query_execution_time",
+            "bigquery_ndc_query_execution_time",
             "Time taken to execute an already-planned query, in seconds.",
         )?;
 
-        let pool_max_connections = add_int_gauge_metric(
+        let mutation_plan_time = add_histogram_metric(
             metrics_registry,
-            "postgres_ndc_pool_max_connections",
-            "The maximum number of connections that this pool should maintain.",
+            "bigquery_ndc_mutation_plan_time",
+            "Time taken to plan a mutation for execution, in seconds.",
         )?;
 
-        let pool_min_connections = add_int_gauge_metric(
+        let mutation_execution_time = add_histogram_metric(
             metrics_registry,
-            "postgres_ndc_pool_min_connections",
-            "The minimum number of connections that this pool should maintain.",
+            "bigquery_ndc_mutation_execution_time",
+            "Time taken to execute an already-planned mutation, in seconds.",
         )?;
 
-        let pool_acquire_timeout = add_gauge_metric(
+        let query_dry_run_bytes_processed = add_histogram_metric_with_buckets(
             metrics_registry,
-            "postgres_ndc_pool_acquire_timeout",
-            "Get the maximum amount of time to spend waiting for a connection, in seconds.",
+            "bigquery_ndc_query_dry_run_bytes_processed",
+            "Bytes a query would process, estimated by a dry run.",
+            bytes_buckets()?,
         )?;
 
-        let pool_idle_timeout = add_gauge_metric(
+        let query_dry_run_cache_hit_total = add_int_counter_metric(
             metrics_registry,
-            "postgres_ndc_pool_idle_timeout",
-            "Get the maximum idle duration for individual connections, in seconds.",
+            "bigquery_ndc_query_dry_run_cache_hit_total",
+            "Total queries whose dry run result was found in the cache.",
         )?;
 
-        let pool_max_lifetime = add_gauge_metric(
+        let query_plan_cache_hit_total = add_int_counter_metric(
             metrics_registry,
-            "postgres_ndc_pool_max_lifetime",
-            "Get the maximum lifetime of individual connections, in seconds.",
+            "bigquery_ndc_query_plan_cache_hit_total",
+            "Total queries whose plan was found in the cache.",
+        )?;
+
+        let query_plan_cache_miss_total = add_int_counter_metric(
+            metrics_registry,
+            "bigquery_ndc_query_plan_cache_miss_total",
+            "Total queries whose plan was not found in the cache, and had to be translated.",
+        )?;
+
+        let query_response_cache_hit_total = add_int_counter_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_query_response_cache_hit_total",
+            "Total queries whose response was found in the response cache.",
+        )?;
+
+        let query_response_cache_miss_total = add_int_counter_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_query_response_cache_miss_total",
+            "Total queries whose response was not found in the response cache, and had to be run.",
+        )?;
+
+        let query_response_cache_bypassed_total = add_int_counter_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_query_response_cache_bypassed_total",
+            "Total queries whose response may not be cached, because they read tables as of a point in time or volatile native queries, or asked not to be cached.",
+        )?;
+
+        let query_response_cache_size_bytes = add_int_gauge_metric(
+            metrics_registry,
+            "bigquery_ndc_query_response_cache_size_bytes",
+            "The total size of the responses in the response cache, in bytes.",
+        )?;
+
+        let query_dry_run_rejected_total = add_int_counter_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_query_dry_run_rejected_total",
+            "Total queries rejected because they would process more bytes than the budget.",
+        )?;
+
+        let query_timed_out_total = add_int_counter_metric(
+            metrics_registry,
+            "bigquery_ndc_query_timed_out_total",
+            "Total queries which did not complete within the query timeout.",
+        )?;
+
+        let query_cancelled_total = add_int_counter_metric(
+            metrics_registry,
+            "bigquery_ndc_query_cancelled_total",
+            "Total query jobs cancelled because their request timed out or was dropped.",
+        )?;
+
+        let bigquery_request_retried_total = add_int_counter_metric(
+            metrics_registry,
+            "bigquery_ndc_request_retried_total",
+            "Total requests to BigQuery retried after a transient failure.",
+        )?;
+
+        let jobs_in_flight = add_int_gauge_metric(
+            metrics_registry,
+            "bigquery_ndc_jobs_in_flight",
+            "The number of BigQuery jobs running at the moment.",
+        )?;
+
+        let job_slot_wait_time = add_histogram_metric_with_buckets(
+            metrics_registry,
+            "bigquery_ndc_job_slot_wait_time",
+            "Time jobs waited for a slot before being sent to BigQuery, in seconds.",
+            job_time_buckets()?,
+        )?;
+
+        let job_total = add_int_counter_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_job_total",
+            "Total completed jobs.",
+        )?;
+
+        let job_bytes_processed_total = add_int_counter_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_job_bytes_processed_total",
+            "Total bytes processed by jobs.",
+        )?;
+
+        let job_bytes_billed_total = add_int_counter_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_job_bytes_billed_total",
+            "Total bytes billed for jobs.",
+        )?;
+
+        let job_slot_milliseconds_total = add_int_counter_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_job_slot_milliseconds_total",
+            "Total slot milliseconds consumed by jobs.",
+        )?;
+
+        let job_cache_hit_total = add_int_counter_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_job_cache_hit_total",
+            "Total jobs whose results were read from the query cache.",
+        )?;
+
+        let job_cache_hit_ratio = add_gauge_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_job_cache_hit_ratio",
+            "The ratio of jobs whose results were read from the query cache.",
+        )?;
+
+        let job_queue_time = add_histogram_vec_metric_with_buckets(
+            metrics_registry,
+            "bigquery_ndc_job_queue_time",
+            "Time jobs spent queued before they started, in seconds.",
+            job_time_buckets()?,
+        )?;
+
+        let job_wait_time = add_histogram_vec_metric_with_buckets(
+            metrics_registry,
+            "bigquery_ndc_job_wait_time",
+            "Time taken by jobs from their creation to their completion, in seconds.",
+            job_time_buckets()?,
+        )?;
+
+        let query_rows_returned_total = add_int_counter_vec_metric(
+            metrics_registry,
+            "bigquery_ndc_query_rows_returned_total",
+            "Total rows returned by queries.",
         )?;
 
         let error_metrics = ErrorMetrics::initialize(metrics_registry)?;
 
         Ok(Self {
             query_total,
+            mutation_total,
             explain_total,
             query_plan_time,
             query_execution_time,
-            pool_max_connections,
-            pool_min_connections,
-            pool_acquire_timeout,
-            pool_max_lifetime,
-            pool_idle_timeout,
+            mutation_plan_time,
+            mutation_execution_time,
+            query_dry_run_bytes_processed,
+            query_dry_run_cache_hit_total,
+            query_plan_cache_hit_total,
+            query_plan_cache_miss_total,
+            query_response_cache_hit_total,
+            query_response_cache_miss_total,
+            query_response_cache_bypassed_total,
+            query_response_cache_size_bytes,
+            query_dry_run_rejected_total,
+            query_timed_out_total,
+            query_cancelled_total,
+            bigquery_request_retried_total,
+            jobs_in_flight,
+            job_slot_wait_time,
+            job_total,
+            job_bytes_processed_total,
+            job_bytes_billed_total,
+            job_slot_milliseconds_total,
+            job_cache_hit_total,
+            job_cache_hit_ratio,
+            job_queue_time,
+            job_wait_time,
+            query_rows_returned_total,
             error_metrics,
         })
     }
@@ -96,10 +292,148 @@ impl Metrics {
         self.query_total.inc();
     }
 
+    pub fn record_successful_mutation(&self) {
+        self.mutation_total.inc();
+    }
+
     pub fn record_successful_explain(&self) {
         self.explain_total.inc();
     }
 
+    #[allow(clippy::cast_precision_loss)]
+    pub fn record_query_dry_run(&self, bytes_processed: u64) {
+        self.query_dry_run_bytes_processed
+            .observe(bytes_processed as f64);
+    }
+
+    pub fn record_query_dry_run_cache_hit(&self) {
+        self.query_dry_run_cache_hit_total.inc();
+    }
+
+    pub fn record_query_plan_cache_hit(&self) {
+        self.query_plan_cache_hit_total.inc();
+    }
+
+    pub fn record_query_plan_cache_miss(&self) {
+        self.query_plan_cache_miss_total.inc();
+    }
+
+    pub fn record_query_response_cache_hit(&self, collection: &str) {
+        self.query_response_cache_hit_total
+            .with_label_values(&[collection])
+            .inc();
+    }
+
+    pub fn record_query_response_cache_miss(&self, collection: &str) {
+        self.query_response_cache_miss_total
+            .with_label_values(&[collection])
+            .inc();
+    }
+
+    pub fn record_query_response_cache_bypassed(&self, collection: &str) {
+        self.query_response_cache_bypassed_total
+            .with_label_values(&[collection])
+            .inc();
+    }
+
+    pub fn set_query_response_cache_size(&self, size_bytes: u64) {
+        self.query_response_cache_size_bytes
+            .set(i64::try_from(size_bytes).unwrap_or(i64::MAX));
+    }
+
+    pub fn record_query_dry_run_rejected(&self, collection: &str) {
+        self.query_dry_run_rejected_total
+            .with_label_values(&[collection])
+            .inc();
+    }
+
+    pub fn record_query_timed_out(&self) {
+        self.query_timed_out_total.inc();
+    }
+
+    pub fn record_query_cancelled(&self) {
+        self.query_cancelled_total.inc();
+    }
+
+    pub fn record_bigquery_request_retried(&self) {
+        self.bigquery_request_retried_total.inc();
+    }
+
+    /// Record that a job took a slot to run, after waiting for it.
+    pub fn record_job_slot_acquired(&self, wait_time: Duration) {
+        self.job_slot_wait_time.observe(wait_time.as_secs_f64());
+        self.jobs_in_flight.inc();
+    }
+
+    pub fn record_job_slot_released(&self) {
+        self.jobs_in_flight.dec();
+    }
+
+    /// Record the statistics of a completed job, run for a collection or a procedure.
+    pub fn record_job(&self, collection: &str, statistics: &JobStatistics) {
+        let labels = &[collection];
+        self.job_total.with_label_values(labels).inc();
+        if statistics.cache_hit {
+            self.job_cache_hit_total.with_label_values(labels).inc();
+        }
+        for (counter, value) in [
+            (&self.job_bytes_processed_total, statistics.bytes_processed),
+            (&self.job_bytes_billed_total, statistics.bytes_billed),
+            (
+                &self.job_slot_milliseconds_total,
+                statistics.slot_milliseconds,
+            ),
+        ] {
+            if let Some(value) = value {
+                counter.with_label_values(labels).inc_by(value);
+            }
+        }
+        for (histogram, time) in [
+            (&self.job_queue_time, statistics.queue_time),
+            (&self.job_wait_time, statistics.wait_time),
+        ] {
+            if let Some(time) = time {
+                histogram
+                    .with_label_values(labels)
+                    .observe(time.as_secs_f64());
+            }
+        }
+    }
+
+    pub fn record_query_rows_returned(&self, collection: &str, rows: usize) {
+        self.query_rows_returned_total
+            .with_label_values(&[collection])
+            .inc_by(rows as u64);
+    }
+
+    /// Update the cache hit ratio of each collection from the number of jobs and cache hits
+    /// recorded so far.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn update_cache_hit_ratio(&self) {
+        for family in self.job_total.collect() {
+            for metric in family.get_metric() {
+                let Some(collection) = metric
+                    .get_label()
+                    .iter()
+                    .find(|label| label.get_name() == COLLECTION_LABEL)
+                    .map(prometheus::proto::LabelPair::get_value)
+                else {
+                    continue;
+                };
+                let jobs = metric.get_counter().get_value();
+                if jobs > 0.0 {
+                    let cache_hits = self
+                        .job_cache_hit_total
+                        .with_label_values(&[collection])
+                        .get();
+                    self.job_cache_hit_ratio
+                        .with_label_values(&[collection])
+                        .set(cache_hits as f64 / jobs);
+                }
+            }
+        }
+    }
+
     pub fn time_query_plan(&self) -> Timer {
         Timer(self.query_plan_time.start_timer())
     }
@@ -108,33 +442,24 @@ impl Metrics {
         Timer(self.query_execution_time.start_timer())
     }
 
-    // Set the metrics populated from the pool options.
-    //
-    // This only needs to be called once, as the options don't change.
-    pub fn set_pool_options_metrics(&self, pool_options: &sqlx::pool::PoolOptions<sqlx::Postgres>) {
-        let max_connections: i64 = pool_options.get_max_connections().into();
-        self.pool_max_connections.set(max_connections);
-
-        let min_connections: i64 = pool_options.get_min_connections().into();
-        self.pool_min_connections.set(min_connections);
+    pub fn time_mutation_plan(&self) -> Timer {
+        Timer(self.mutation_plan_time.start_timer())
+    }
 
-        let acquire_timeout: f64 = pool_options.get_acquire_timeout().as_secs_f64();
-        self.pool_acquire_timeout.set(acquire_timeout);
+    pub fn time_mutation_execution(&self) -> Timer {
+        Timer(self.mutation_execution_time.start_timer())
+    }
+}
 
-        // if nothing is set, return 0
-        let idle_timeout: f64 = pool_options
-            .get_idle_timeout()
-            .unwrap_or(Duration::ZERO)
-            .as_secs_f64();
-        self.pool_idle_timeout.set(idle_timeout);
+/// Buckets for metrics measuring bytes, from 1 MiB to 16 TiB.
+fn bytes_buckets() -> Result<Vec<f64>, prometheus::Error> {
+    prometheus::exponential_buckets(1_048_576.0, 4.0, 13)
+}
 
-        // if nothing is set, return 0
-        let max_lifetime: f64 = pool_options
-            .get_max_lifetime()
-            .unwrap_or(Duration::ZERO)
-            .as_secs_f64();
-        self.pool_max_lifetime.set(max_lifetime);
-    }
+/// Buckets for metrics measuring the time taken by jobs, from 100 milliseconds to about 7
+/// minutes.
+fn job_time_buckets() -> Result<Vec<f64>, prometheus::Error> {
+    prometheus::exponential_buckets(0.1, 2.0, 13)
 }
 
 /// Create a new int counter metric and register it with the provided Prometheus Registry
@@ -148,6 +473,20 @@ fn add_int_counter_metric(
     register_collector(metrics_registry, int_counter)
 }
 
+/// Create a new int counter metric labelled by collection, and register it with the provided
+/// Prometheus Registry
+fn add_int_counter_vec_metric(
+    metrics_registry: &mut Registry,
+    metric_name: &str,
+    metric_description: &str,
+) -> Result<IntCounterVec, prometheus::Error> {
+    let int_counter_vec = IntCounterVec::new(
+        prometheus::Opts::new(metric_name, metric_description),
+        &[COLLECTION_LABEL],
+    )?;
+    register_collector(metrics_registry, int_counter_vec)
+}
+
 /// Create a new int gauge metric and register it with the provided Prometheus Registry
 fn add_int_gauge_metric(
     metrics_registry: &mut Registry,
@@ -158,14 +497,18 @@ fn add_int_gauge_metric(
     register_collector(metrics_registry, int_gauge)
 }
 
-/// Create a new gauge metric and register it with the provided Prometheus Registry
-fn add_gauge_metric(
+/// Create a new gauge metric labelled by collection, and register it with the provided
+/// Prometheus Registry
+fn add_gauge_vec_metric(
     metrics_registry: &mut Registry,
     metric_name: &str,
     metric_description: &str,
-) -> Result<Gauge, prometheus::Error> {
-    let gauge = Gauge::with_opts(prometheus::Opts::new(metric_name, metric_description))?;
-    register_collector(metrics_registry, gauge)
+) -> Result<GaugeVec, prometheus::Error> {
+    let gauge_vec = GaugeVec::new(
+        prometheus::Opts::new(metric_name, metric_description),
+        &[COLLECTION_LABEL],
+    )?;
+    register_collector(metrics_registry, gauge_vec)
 }
 
 /// Create a new histogram metric using the default buckets, and register it with the provided
@@ -182,6 +525,35 @@ fn add_histogram_metric(
     register_collector(metrics_registry, histogram)
 }
 
+/// Create a new histogram metric using the given buckets, and register it with the provided
+/// Prometheus Registry.
+fn add_histogram_metric_with_buckets(
+    metrics_registry: &mut prometheus::Registry,
+    metric_name: &str,
+    metric_description: &str,
+    buckets: Vec<f64>,
+) -> Result<Histogram, prometheus::Error> {
+    let histogram = Histogram::with_opts(
+        prometheus::HistogramOpts::new(metric_name, metric_description).buckets(buckets),
+    )?;
+    register_collector(metrics_registry, histogram)
+}
+
+/// Create a new histogram metric labelled by collection using the given buckets, and register
+/// it with the provided Prometheus Registry.
+fn add_histogram_vec_metric_with_buckets(
+    metrics_registry: &mut prometheus::Registry,
+    metric_name: &str,
+    metric_description: &str,
+    buckets: Vec<f64>,
+) -> Result<HistogramVec, prometheus::Error> {
+    let histogram_vec = HistogramVec::new(
+        prometheus::HistogramOpts::new(metric_name, metric_description).buckets(buckets),
+        &[COLLECTION_LABEL],
+    )?;
+    register_collector(metrics_registry, histogram_vec)
+}
+
 /// Register a new collector with the registry, and returns it for later use.
 fn register_collector<Collector: prometheus::core::Collector + std::clone::Clone + 'static>(
     metrics_registry: &mut Registry,
@@ -238,8 +610,8 @@ pub struct ErrorMetrics {
     connector_error_total: IntCounter,
     /// the database emmited an error.
     database_error_total: IntCounter,
-    /// we failed to acquire a database connection from the pool
-    connection_acquisition_error_total: IntCounter,
+    /// no slot became available in time to run a BigQuery job.
+    job_slot_acquisition_error_total: IntCounter,
 }
 
 impl ErrorMetrics {
@@ -249,38 +621,38 @@ impl ErrorMetrics {
     ) -> Result<Self, prometheus::Error> {
         let invalid_request_total = add_int_counter_metric(
             metrics_registry,
-            "ndc_postgres_error_invalid_request_total_count",
+            "bigquery_ndc_error_invalid_request_total_count",
             "Total number of invalid requests encountered.",
         )?;
 
         let unsupported_capability_total = add_int_counter_metric(
             metrics_registry,
-            "ndc_postgres_error_unsupported_capability_total_count",
+            "bigquery_ndc_error_unsupported_capability_total_count",
             "Total number of invalid requests with unsupported capabilities encountered.",
         )?;
 
         let unsupported_feature_total = add_int_counter_metric(
             metrics_registry,
-            "ndc_postgres_error_unsupported_capabilities_total_count",
+            "bigquery_ndc_error_unsupported_capabilities_total_count",
             "Total number of invalid requests with unsupported capabilities encountered.",
         )?;
 
         let connector_error_total = add_int_counter_metric(
             metrics_registry,
-            "ndc_postgres_error_connector_error_total_count",
+            "bigquery_ndc_error_connector_error_total_count",
             "Total number of requests failed due to an internal conenctor error.",
         )?;
 
         let database_error_total = add_int_counter_metric(
             metrics_registry,
-            "ndc_postgres_error_database_error_total_count",
+            "bigquery_ndc_error_database_error_total_count",
             "Total number of requests failed due to a database error.",
         )?;
 
-        let connection_acquisition_error_total = add_int_counter_metric(
+        let job_slot_acquisition_error_total = add_int_counter_metric(
             metrics_registry,
-            "ndc_postgres_error_connection_acquisition_error_total_count",
-            "Total number of failures to acquire a database connection.",
+            "bigquery_ndc_error_job_slot_acquisition_error_total_count",
+            "Total number of failures to acquire a slot to run a BigQuery job.",
         )?;
 
         Ok(ErrorMetrics {
@@ -289,7 +661,7 @@ impl ErrorMetrics {
             unsupported_feature_total,
             connector_error_total,
             database_error_total,
-            connection_acquisition_error_total,
+            job_slot_acquisition_error_total,
         })
     }
 
@@ -308,7 +680,139 @@ impl ErrorMetrics {
     pub fn record_database_error(&self) {
         self.database_error_total.inc();
     }
-    pub fn record_connection_acquisition_error(&self) {
-        self.connection_acquisition_error_total.inc();
+    pub fn record_job_slot_acquisition_error(&self) {
+        self.job_slot_acquisition_error_total.inc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics() -> Metrics {
+        Metrics::initialize(&mut Registry::new()).unwrap()
+    }
+
+    #[test]
+    fn records_the_statistics_of_jobs_per_collection() {
+        let metrics = metrics();
+        let statistics = JobStatistics {
+            bytes_processed: Some(1000),
+            bytes_billed: Some(10_485_760),
+            slot_milliseconds: Some(250),
+            cache_hit: false,
+            queue_time: Some(Duration::from_millis(200)),
+            wait_time: Some(Duration::from_secs(3)),
+        };
+        metrics.record_job("albums", &statistics);
+        metrics.record_job("albums", &statistics);
+        metrics.record_job(
+            "artists",
+            &JobStatistics {
+                cache_hit: true,
+                ..JobStatistics::default()
+            },
+        );
+
+        let albums = &["albums"];
+        assert_eq!(metrics.job_total.with_label_values(albums).get(), 2);
+        assert_eq!(
+            metrics.job_cache_hit_total.with_label_values(albums).get(),
+            0
+        );
+        assert_eq!(
+            metrics
+                .job_bytes_processed_total
+                .with_label_values(albums)
+                .get(),
+            2000
+        );
+        assert_eq!(
+            metrics
+                .job_bytes_billed_total
+                .with_label_values(albums)
+                .get(),
+            20_971_520
+        );
+        assert_eq!(
+            metrics
+                .job_slot_milliseconds_total
+                .with_label_values(albums)
+                .get(),
+            500
+        );
+        let queue_time = metrics.job_queue_time.with_label_values(albums);
+        assert_eq!(queue_time.get_sample_count(), 2);
+        assert!((queue_time.get_sample_sum() - 0.4).abs() < f64::EPSILON);
+        let wait_time = metrics.job_wait_time.with_label_values(albums);
+        assert_eq!(wait_time.get_sample_count(), 2);
+        assert!((wait_time.get_sample_sum() - 6.0).abs() < f64::EPSILON);
+
+        let artists = &["artists"];
+        assert_eq!(metrics.job_total.with_label_values(artists).get(), 1);
+        assert_eq!(
+            metrics.job_cache_hit_total.with_label_values(artists).get(),
+            1
+        );
+        assert_eq!(
+            metrics
+                .job_bytes_processed_total
+                .with_label_values(artists)
+                .get(),
+            0
+        );
+        assert_eq!(
+            metrics
+                .job_queue_time
+                .with_label_values(artists)
+                .get_sample_count(),
+            0
+        );
+    }
+
+    #[test]
+    fn updates_the_cache_hit_ratio_of_each_collection() {
+        let metrics = metrics();
+        let cache_hit = JobStatistics {
+            cache_hit: true,
+            ..JobStatistics::default()
+        };
+        let cache_miss = JobStatistics::default();
+        for statistics in [&cache_hit, &cache_miss, &cache_miss, &cache_miss] {
+            metrics.record_job("albums", statistics);
+        }
+        metrics.record_job("artists", &cache_hit);
+        metrics.record_job("tracks", &cache_miss);
+
+        metrics.update_cache_hit_ratio();
+
+        let ratio = |collection| {
+            metrics
+                .job_cache_hit_ratio
+                .with_label_values(&[collection])
+                .get()
+        };
+        assert!((ratio("albums") - 0.25).abs() < f64::EPSILON);
+        assert!((ratio("artists") - 1.0).abs() < f64::EPSILON);
+        assert!(ratio("tracks").abs() < f64::EPSILON);
+
+        metrics.record_job("tracks", &cache_hit);
+        metrics.update_cache_hit_ratio();
+
+        assert!((ratio("tracks") - 0.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn does_not_report_a_cache_hit_ratio_for_collections_without_jobs() {
+        let metrics = metrics();
+        metrics.record_query_rows_returned("albums", 10);
+
+        metrics.update_cache_hit_ratio();
+
+        assert!(metrics
+            .job_cache_hit_ratio
+            .collect()
+            .iter()
+            .all(|family| family.get_metric().is_empty()));
     }
 }
@@ -0,0 +1,100 @@
+//! Execute a mutation execution plan against the database.
+
+use gcp_bigquery_client::model::query_request::QueryRequest;
+use gcp_bigquery_client::model::table_field_schema::TableFieldSchema;
+use gcp_bigquery_client::model::table_row::TableRow;
+use ndc_models as models;
+use serde_json::Value;
+
+use crate::decode;
+use crate::error::{Error, QueryError};
+use crate::metrics;
+use crate::query::{
+    dialect, fetch_all_rows, query_parameters, run_query, CompletedJob, JobSettings, Settings,
+};
+use query_engine_sql::sql;
+
+/// The response to a mutation, and the BigQuery jobs which ran its procedure calls.
+#[derive(Debug)]
+pub struct MutationResult {
+    pub response: models::MutationResponse,
+    pub jobs: Vec<CompletedJob>,
+}
+
+/// Execute the procedure calls of a mutation in order, returning the result of each.
+/// Each call waits for its job to complete, and reads every page of its result.
+///
+/// The job calling each procedure is configured with the job settings of the procedure.
+///
+/// Procedure calls are not idempotent. They are only retried with the same job id, so that
+/// BigQuery runs each of them at most once.
+pub async fn execute(
+    bigquery_client: &gcp_bigquery_client::Client,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    settings: &Settings,
+    job_settings: impl Fn(&str) -> JobSettings,
+    plan: sql::execution_plan::ExecutionPlan<Vec<sql::execution_plan::Mutation>>,
+) -> Result<MutationResult, Error> {
+    let mut operation_results = vec![];
+    let mut jobs = vec![];
+
+    for mutation in plan.query {
+        let query_request = call_request(&mutation, settings.bind_literals)?;
+        let settings = Settings {
+            job: job_settings(mutation.procedure.as_str()),
+            ..settings.clone()
+        };
+        let response = run_query(
+            bigquery_client,
+            metrics,
+            project_id,
+            &settings,
+            mutation.procedure.as_str(),
+            query_request,
+            &mut jobs,
+        )
+        .await?;
+        let (schema, rows) =
+            fetch_all_rows(bigquery_client, metrics, project_id, &settings, response).await?;
+
+        operation_results.push(models::MutationOperationResults::Procedure {
+            result: rows_to_json(&schema, &rows)?,
+        });
+    }
+
+    Ok(MutationResult {
+        response: models::MutationResponse { operation_results },
+        jobs,
+    })
+}
+
+/// The request calling the procedure of a mutation. With `bind_literals`, the number and
+/// boolean literals of its SQL are bound as parameters too.
+pub fn call_request(
+    mutation: &sql::execution_plan::Mutation,
+    bind_literals: bool,
+) -> Result<QueryRequest, Error> {
+    let call_sql = mutation
+        .call_sql(dialect(bind_literals))
+        .map_err(|err| Error::Query(QueryError::UnsupportedSql(err)))?;
+    let mut query_request = QueryRequest::new(call_sql.sql);
+    query_request.query_parameters = Some(query_parameters(&call_sql.params, &[], &[])?);
+    Ok(query_request)
+}
+
+/// Convert the result of a procedure's final SELECT statement to an array of row objects.
+/// Procedures which do not end with a SELECT statement have no result and return null.
+fn rows_to_json(schema: &[TableFieldSchema], rows: &[TableRow]) -> Result<Value, Error> {
+    if schema.is_empty() {
+        return Ok(Value::Null);
+    }
+    let fields: Vec<models::FieldName> = schema
+        .iter()
+        .map(|field| field.name.clone().into())
+        .collect();
+    rows.iter()
+        .map(|row| decode::decode_row(&fields, schema, row).map(Value::Object))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Value::Array)
+}
@@ -1,88 +1,1580 @@
 //! Execute an execution plan against the database.
 
-use crate::error::Error;
+use crate::concurrency::JobSlots;
+use crate::decode;
+use crate::error::{BigQueryError, Error, QueryError, Reason};
 use crate::metrics;
+use crate::retry;
+use crate::storage_read;
+use crate::trace;
 use bytes::{BufMut, Bytes, BytesMut};
+use gcp_bigquery_client::error::BQError;
+use gcp_bigquery_client::model::get_query_results_parameters::GetQueryResultsParameters;
+use gcp_bigquery_client::model::job::Job;
+use gcp_bigquery_client::model::job_configuration::JobConfiguration;
+use gcp_bigquery_client::model::job_configuration_query::JobConfigurationQuery;
+use gcp_bigquery_client::model::job_reference::JobReference;
+use gcp_bigquery_client::model::job_statistics::JobStatistics;
 use gcp_bigquery_client::model::query_request::QueryRequest;
-use gcp_bigquery_client::model::{query_parameter, query_parameter_type, query_parameter_value};
-use query_engine_sql::sql::string::Param;
+use gcp_bigquery_client::model::query_response::QueryResponse;
+use gcp_bigquery_client::model::table_field_schema::TableFieldSchema;
+use gcp_bigquery_client::model::table_reference::TableReference;
+use gcp_bigquery_client::model::table_row::TableRow;
+use gcp_bigquery_client::model::{
+    query_parameter, query_parameter_type, query_parameter_type_struct_types, query_parameter_value,
+};
+use ndc_models as models;
+use query_engine_sql::sql::dialect::{BigQuery, Dialect, ParameterisedLiterals};
+use query_engine_sql::sql::helpers::{VARIABLES_FIELD, VARIABLE_ORDER_FIELD};
+use query_engine_sql::sql::string::{LiteralEncoding, Param, SQL};
 use serde_json::{self, to_string, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tracing::Instrument;
 
 use query_engine_sql::sql;
 
-/// Execute a query against postgres.
+/// Settings which control how a query is run, and how its results are read.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub limits: ResultLimits,
+    pub storage_read: storage_read::Settings,
+    pub job: JobSettings,
+    /// Cancel the query if it does not complete within this many seconds.
+    pub timeout_seconds: Option<u64>,
+    /// How requests to BigQuery which fail transiently are retried.
+    pub retry: retry::Policy,
+    /// How the values of query parameters are redacted in traces.
+    pub parameter_redaction: trace::ParameterRedaction,
+    /// How a query is run on its sets of variables.
+    pub foreach: ForeachStrategy,
+    /// The slots limiting how many jobs run at once, shared by every query.
+    pub job_slots: JobSlots,
+    /// Bind the number and boolean literals of the SQL as parameters too, so that the SQL holds
+    /// none of the values of the request.
+    pub bind_literals: bool,
+}
+
+/// How a query is run on its sets of variables, e.g. for the keys of a remote relationship.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForeachStrategy {
+    /// Run a single query on every set of variables, unnested from an array parameter.
+    #[default]
+    Batched,
+    /// Run a query on each chunk of at most `chunk_size` sets of variables, running at most
+    /// `max_concurrency` queries at a time.
+    Chunked {
+        chunk_size: usize,
+        max_concurrency: usize,
+    },
+}
+
+/// Limits on how the results of a query are read.
+#[derive(Debug, Clone, Default)]
+pub struct ResultLimits {
+    /// The maximum number of rows to fetch in each page of results.
+    pub page_size: Option<u32>,
+    /// The maximum number of rows a query may return.
+    pub max_rows: Option<u64>,
+    /// The maximum size of the response to a query, in bytes.
+    pub max_result_size: Option<u64>,
+}
+
+/// Settings of the BigQuery job running a query.
+#[derive(Debug, Clone, Default)]
+pub struct JobSettings {
+    /// Fail the query if it would bill more bytes than this.
+    pub maximum_bytes_billed: Option<u64>,
+    /// Whether to look for the result in the query cache.
+    pub use_query_cache: Option<bool>,
+    /// The location where the job runs.
+    pub location: Option<String>,
+    /// Cancel the job if it runs for longer than this, in milliseconds.
+    pub job_timeout_ms: Option<u64>,
+    pub priority: Option<JobPriority>,
+    /// Labels attached to the job.
+    pub labels: HashMap<String, String>,
+}
+
+/// The priority of a BigQuery job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobPriority {
+    /// Run the query as soon as possible.
+    Interactive,
+    /// Queue the query until resources are available.
+    Batch,
+}
+
+impl JobPriority {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobPriority::Interactive => "INTERACTIVE",
+            JobPriority::Batch => "BATCH",
+        }
+    }
+}
+
+/// The response to a query, and the BigQuery jobs which ran it.
+#[derive(Debug)]
+pub struct QueryResult {
+    pub response: Bytes,
+    pub jobs: Vec<CompletedJob>,
+}
+
+/// A completed BigQuery job.
+#[derive(Debug)]
+pub struct CompletedJob {
+    pub job_id: String,
+    /// The statistics of the job, which are fetched in the background once it completes.
+    /// Nothing if they could not be fetched.
+    pub statistics: tokio::task::JoinHandle<Option<metrics::JobStatistics>>,
+}
+
+/// Execute a query against BigQuery.
+///
+/// Large results are read through the Storage Read API when a client for it is given, depending
+/// on the storage read settings.
+///
+/// If the query does not complete within the timeout, or if the returned future is dropped
+/// (e.g. because the client disconnected), the BigQuery job running it is cancelled.
 pub async fn execute(
     bigquery_client: &gcp_bigquery_client::Client,
-    _metrics: &metrics::Metrics,
+    storage_read_client: Option<&storage_read::Client>,
+    metrics: &metrics::Metrics,
     project_id: &str,
+    settings: &Settings,
     plan: sql::execution_plan::ExecutionPlan<sql::execution_plan::Query>,
-) -> Result<Bytes, Error> {
+) -> Result<QueryResult, Error> {
+    let execution = execute_plan(
+        bigquery_client,
+        storage_read_client,
+        metrics,
+        project_id,
+        settings,
+        plan,
+    );
+    with_timeout(metrics, settings.timeout_seconds, execution).await
+}
+
+/// Wait for the execution of a query, failing if it does not complete within the timeout. The
+/// execution is then dropped, which cancels the job it waits for.
+async fn with_timeout<T>(
+    metrics: &metrics::Metrics,
+    timeout_seconds: Option<u64>,
+    execution: impl std::future::Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    match timeout_seconds {
+        None => execution.await,
+        Some(timeout_seconds) => {
+            tokio::time::timeout(Duration::from_secs(timeout_seconds), execution)
+                .await
+                .unwrap_or_else(|_| {
+                    metrics.record_query_timed_out();
+                    Err(Error::Query(QueryError::TimedOut { timeout_seconds }))
+                })
+        }
+    }
+}
+
+async fn execute_plan(
+    bigquery_client: &gcp_bigquery_client::Client,
+    storage_read_client: Option<&storage_read::Client>,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    settings: &Settings,
+    plan: sql::execution_plan::ExecutionPlan<sql::execution_plan::Query>,
+) -> Result<QueryResult, Error> {
     let mut buffer = BytesMut::new();
+    let mut jobs = vec![];
+    let collection = plan.query.root_field.as_str();
 
     // run the query on each set of variables. The result is a vector of rows each
     // element in the vector is the result of running the query on one set of variables.
-    match plan.query.variables {
-        None => {
+    match (&plan.query.variables, &plan.query.typed_rows) {
+        (None, Some(typed_rows)) => {
+            execute_typed_rows(
+                bigquery_client,
+                storage_read_client,
+                metrics,
+                project_id,
+                settings,
+                collection,
+                typed_rows,
+                query_request(&plan.query, settings.bind_literals)?,
+                &mut buffer,
+                &mut jobs,
+            )
+            .await?;
+        }
+        (None, None) => {
             // TODO: need to parse this from service account key or allow user to provide it
             // TODO(PY)
             // let project_id = "hasura-development";
 
             // let mut inner_rows = vec![];
 
-            let mut query_request = QueryRequest::new(plan.query.query_sql().sql);
-
-            // smash query.params in here pls
-            query_request.query_parameters = Some(
-                plan.query
-                    .query_sql()
-                    .params
-                    // .params
-                    .iter()
-                    .enumerate()
-                    .map(|(i, param)| match param {
-                        Param::String(str) => {
-                            let value = query_parameter_value::QueryParameterValue {
-                                array_values: None,
-                                struct_values: None,
-                                value: Some(str.to_string()),
-                            };
-                            let value_type = query_parameter_type::QueryParameterType {
-                                array_type: None,
-                                struct_types: None,
-                                r#type: "STRING".to_string(),
-                            };
-                            query_parameter::QueryParameter {
-                                name: Some(format!("param{}", i + 1)),
-                                parameter_type: Some(value_type),
-                                parameter_value: Some(value),
-                            }
-                        }
-                        Param::Variable(_var) => todo!("Variables not implemented yet"), // error that `Err(Error::Query(QueryError::VariableNotFound(var.to_string())))`
-                        Param::Value(_value) => todo!("Values not implemented yet"),     // todo(PY)
-                    })
-                    .collect(),
-            );
+            let query_request = query_request(&plan.query, settings.bind_literals)?;
 
             // Query
-            let mut rs = bigquery_client
-                .job()
-                .query(project_id, query_request)
-                .await
-                .unwrap();
+            let response = run_query(
+                bigquery_client,
+                metrics,
+                project_id,
+                settings,
+                collection,
+                query_request,
+                &mut jobs,
+            )
+            .await?;
+            let (_schema, rows) =
+                fetch_all_rows(bigquery_client, metrics, project_id, settings, response).await?;
 
-            while rs.next_row() {
-                let this_row = rs.get_string(0).unwrap().unwrap(); // we should only have one row called 'universe'
-                let row_value: Value = serde_json::from_str(&this_row).unwrap();
+            for row in &rows {
+                let row_value = decode_row_set(row)?;
+                if let Some(rows) = row_value.get("rows").and_then(Value::as_array) {
+                    metrics.record_query_rows_returned(collection, rows.len());
+                }
                 let row_value_array = Value::Array(vec![row_value]);
-                let final_row = to_string(&row_value_array).unwrap();
+                let final_row = to_string(&row_value_array)
+                    .map_err(|err| Error::UnexpectedResult(err.to_string()))?;
                 let b: Bytes = Bytes::from(final_row);
                 buffer.put(b);
+                check_result_size(&settings.limits, &buffer)?;
             }
         }
-        Some(_variable_sets) => {
-            todo!("foreach/variables not implemented in query engine / execution")
+        (Some(variable_sets), _) => {
+            execute_foreach(
+                bigquery_client,
+                metrics,
+                project_id,
+                settings,
+                collection,
+                &plan.query,
+                variable_sets,
+                &mut buffer,
+                &mut jobs,
+            )
+            .await?;
         }
     };
 
-    Ok(buffer.freeze())
+    Ok(QueryResult {
+        response: buffer.freeze(),
+        jobs,
+    })
+}
+
+/// The request running a query: the query selecting typed columns if there is one, or else
+/// the query rendering the rows as JSON. A query with variables runs on all its sets of
+/// variables. With `bind_literals`, the number and boolean literals of its SQL are bound as
+/// parameters too.
+pub fn query_request(
+    query: &sql::execution_plan::Query,
+    bind_literals: bool,
+) -> Result<QueryRequest, Error> {
+    let query_sql = query_sql(query, dialect(bind_literals))?;
+    let variable_sets = query.variables.as_deref().unwrap_or_default();
+    bind_query_request(&query_sql, query, variable_sets)
+}
+
+/// The dialect rendering the SQL we run, binding its literals as parameters or not.
+pub(crate) fn dialect(bind_literals: bool) -> &'static dyn Dialect {
+    if bind_literals {
+        &ParameterisedLiterals(BigQuery)
+    } else {
+        &BigQuery
+    }
+}
+
+fn query_sql(query: &sql::execution_plan::Query, dialect: &dyn Dialect) -> Result<SQL, Error> {
+    match &query.typed_rows {
+        Some(typed_rows) => typed_rows.query_sql(dialect),
+        None => query.query_sql(dialect),
+    }
+    .map_err(|err| Error::Query(QueryError::UnsupportedSql(err)))
+}
+
+/// The request running the SQL of a query on some of its sets of variables.
+fn bind_query_request(
+    query_sql: &SQL,
+    query: &sql::execution_plan::Query,
+    variable_sets: &[BTreeMap<models::VariableName, Value>],
+) -> Result<QueryRequest, Error> {
+    let mut parameters = query_parameters(&query_sql.params, &query.literals, variable_sets)?;
+    parameters.extend(
+        query
+            .request_parameters
+            .iter()
+            .map(|(name, value)| string_parameter(name.clone(), value.clone())),
+    );
+
+    let mut query_request = QueryRequest::new(query_sql.sql.clone());
+    query_request.query_parameters = Some(parameters);
+    Ok(query_request)
+}
+
+/// Dry-run a query, and return the number of bytes it would process. A dry run validates the
+/// query and estimates its cost, without running it.
+pub async fn dry_run(
+    bigquery_client: &gcp_bigquery_client::Client,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    settings: &Settings,
+    mut query_request: QueryRequest,
+) -> Result<u64, Error> {
+    query_request.dry_run = Some(true);
+    query_request.location.clone_from(&settings.job.location);
+
+    let result_set = retry::retry(&settings.retry, metrics, || {
+        bigquery_client
+            .job()
+            .query(project_id, query_request.clone())
+    })
+    .await?;
+    result_set
+        .query_response()
+        .total_bytes_processed
+        .as_deref()
+        .and_then(|bytes| bytes.parse().ok())
+        .ok_or_else(|| {
+            Error::UnexpectedResult("the dry run did not return the bytes processed".to_string())
+        })
+}
+
+/// Run a query selecting typed columns, and write the resulting `RowSet` to the buffer,
+/// decoding each row according to the schema of the result.
+///
+/// Large results may be read through the Storage Read API instead of `getQueryResults`,
+/// depending on the storage read settings, when a client for it is given.
+#[allow(clippy::too_many_arguments)]
+async fn execute_typed_rows(
+    bigquery_client: &gcp_bigquery_client::Client,
+    storage_read_client: Option<&storage_read::Client>,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    settings: &Settings,
+    collection: &str,
+    typed_rows: &sql::execution_plan::TypedRows,
+    query_request: QueryRequest,
+    buffer: &mut BytesMut,
+    jobs: &mut Vec<CompletedJob>,
+) -> Result<(), Error> {
+    let limits = &settings.limits;
+    let storage_read_settings = &settings.storage_read;
+
+    let response = run_query(
+        bigquery_client,
+        metrics,
+        project_id,
+        settings,
+        collection,
+        query_request,
+        jobs,
+    )
+    .await?;
+    let total_rows = total_rows(&response);
+    check_row_count(limits, total_rows)?;
+
+    let storage_read_client = storage_read_client
+        .filter(|_| response.page_token.is_some() && storage_read_settings.should_read(total_rows));
+    let rows = if let Some(storage_read_client) = storage_read_client {
+        let destination_table =
+            destination_table(bigquery_client, metrics, project_id, settings, &response).await?;
+        let ordered = !typed_rows.query.order_by.elements.is_empty();
+        storage_read_client
+            .read_table(
+                project_id,
+                &destination_table,
+                &typed_rows.fields,
+                storage_read_settings.max_streams(ordered),
+            )
+            .await?
+    } else {
+        let (schema, rows) =
+            fetch_all_rows(bigquery_client, metrics, project_id, settings, response).await?;
+        rows.iter()
+            .map(|row| decode::decode_row(&typed_rows.fields, &schema, row))
+            .collect::<Result<_, _>>()?
+    };
+    metrics.record_query_rows_returned(collection, rows.len());
+
+    // the response is a list of row sets, one for each set of variables.
+    buffer.put_slice(b"[{\"rows\":[");
+    for (index, row) in rows.iter().enumerate() {
+        if index > 0 {
+            buffer.put_u8(b',');
+        }
+        serde_json::to_writer((&mut *buffer).writer(), &row)
+            .map_err(|err| Error::UnexpectedResult(err.to_string()))?;
+        check_result_size(limits, buffer)?;
+    }
+    buffer.put_slice(b"]}]");
+
+    Ok(())
+}
+
+/// Run a query on each of its sets of variables, and write the list of their `RowSet`s to the
+/// buffer, in the order of the sets of variables.
+///
+/// The query returns a row for each set of variables it runs on, holding its `RowSet` as JSON.
+/// Depending on the strategy, it runs once on every set of variables, or on chunks of them in
+/// parallel, whose results are put back in order.
+#[allow(clippy::too_many_arguments)]
+async fn execute_foreach(
+    bigquery_client: &gcp_bigquery_client::Client,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    settings: &Settings,
+    collection: &str,
+    query: &sql::execution_plan::Query,
+    variable_sets: &[BTreeMap<models::VariableName, Value>],
+    buffer: &mut BytesMut,
+    jobs: &mut Vec<CompletedJob>,
+) -> Result<(), Error> {
+    // each row holds the `RowSet` of a set of variables, so the limit on the number of rows
+    // applies to the rows of the `RowSet`s instead.
+    let fetch_settings = &Settings {
+        limits: ResultLimits {
+            max_rows: None,
+            ..settings.limits.clone()
+        },
+        ..settings.clone()
+    };
+    let query_sql = &query_sql(query, dialect(settings.bind_literals))?;
+
+    let chunks = run_chunks(
+        settings.foreach,
+        variable_sets,
+        |variable_sets| async move {
+            let mut chunk_jobs = vec![];
+            let response = run_query(
+                bigquery_client,
+                metrics,
+                project_id,
+                settings,
+                collection,
+                bind_query_request(query_sql, query, variable_sets)?,
+                &mut chunk_jobs,
+            )
+            .await?;
+            let (_schema, rows) = fetch_all_rows(
+                bigquery_client,
+                metrics,
+                project_id,
+                fetch_settings,
+                response,
+            )
+            .await?;
+            Ok((rows, chunk_jobs))
+        },
+    )
+    .await?;
+
+    let rows_returned = write_row_sets(
+        &settings.limits,
+        chunks.iter().flat_map(|(rows, _)| rows),
+        buffer,
+    )?;
+    metrics.record_query_rows_returned(collection, rows_returned);
+    check_row_count(&settings.limits, rows_returned as u64)?;
+    jobs.extend(chunks.into_iter().flat_map(|(_, chunk_jobs)| chunk_jobs));
+    Ok(())
+}
+
+/// Run a query on the chunks of its sets of variables the strategy splits them in, running
+/// the chunks in parallel up to the maximum concurrency of the strategy. The results of the
+/// chunks are returned in order, whatever order they complete in.
+async fn run_chunks<'a, T, F, Fut>(
+    foreach: ForeachStrategy,
+    variable_sets: &'a [BTreeMap<models::VariableName, Value>],
+    run_chunk: F,
+) -> Result<Vec<T>, Error>
+where
+    F: Fn(&'a [BTreeMap<models::VariableName, Value>]) -> Fut,
+    Fut: std::future::Future<Output = Result<T, Error>>,
+{
+    let (chunk_size, max_concurrency) = match foreach {
+        ForeachStrategy::Batched => (variable_sets.len(), 1),
+        ForeachStrategy::Chunked {
+            chunk_size,
+            max_concurrency,
+        } => (chunk_size, max_concurrency),
+    };
+    let semaphore = &tokio::sync::Semaphore::new(max_concurrency.max(1));
+    let run_chunk = &run_chunk;
+
+    let chunks = variable_sets
+        .chunks(chunk_size.max(1))
+        .map(|variable_sets| async move {
+            let _permit = semaphore
+                .acquire()
+                .await
+                .map_err(|err| Error::UnexpectedResult(err.to_string()))?;
+            run_chunk(variable_sets).await
+        });
+    futures::future::try_join_all(chunks).await
+}
+
+/// Write the list of the `RowSet`s held by the rows of a query run on sets of variables to the
+/// buffer. Returns the number of rows of the `RowSet`s.
+fn write_row_sets<'a>(
+    limits: &ResultLimits,
+    rows: impl Iterator<Item = &'a TableRow>,
+    buffer: &mut BytesMut,
+) -> Result<usize, Error> {
+    let mut rows_returned = 0;
+    buffer.put_u8(b'[');
+    for (index, row) in rows.enumerate() {
+        let row_set = decode_row_set(row)?;
+        if let Some(rows) = row_set.get("rows").and_then(Value::as_array) {
+            rows_returned += rows.len();
+        }
+        if index > 0 {
+            buffer.put_u8(b',');
+        }
+        serde_json::to_writer((&mut *buffer).writer(), &row_set)
+            .map_err(|err| Error::UnexpectedResult(err.to_string()))?;
+        check_result_size(limits, buffer)?;
+    }
+    buffer.put_u8(b']');
+    Ok(rows_returned)
+}
+
+/// The `RowSet` held by a row of a query rendering its rows as JSON, in its single column.
+fn decode_row_set(row: &TableRow) -> Result<Value, Error> {
+    let row_set = row
+        .columns
+        .as_deref()
+        .and_then(|cells| cells.first())
+        .and_then(|cell| cell.value.as_ref())
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::UnexpectedResult("expected a single JSON column".to_string()))?;
+    serde_json::from_str(row_set).map_err(|err| Error::UnexpectedResult(err.to_string()))
+}
+
+/// Run a query, waiting for its job to complete. Returns the first page of the results, and
+/// adds the job to the completed jobs.
+/// The statistics of the job are recorded in metrics, labelled with the queried collection.
+///
+/// The job is cancelled if the returned future is dropped while we wait for it.
+pub(crate) async fn run_query(
+    bigquery_client: &gcp_bigquery_client::Client,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    settings: &Settings,
+    collection: &str,
+    query_request: QueryRequest,
+    jobs: &mut Vec<CompletedJob>,
+) -> Result<QueryResponse, Error> {
+    let span = trace::job_span(&query_request.query);
+    run_query_job(
+        bigquery_client,
+        metrics,
+        project_id,
+        settings,
+        collection,
+        query_request,
+        jobs,
+    )
+    .instrument(span)
+    .await
+}
+
+/// Run a query in the span of its job, recording what we learn about the job in the span.
+///
+/// The query is inserted as a job whose id we generate, so that the job can be cancelled from
+/// the moment it is sent, and so that retrying the insertion cannot run the query twice.
+async fn run_query_job(
+    bigquery_client: &gcp_bigquery_client::Client,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    settings: &Settings,
+    collection: &str,
+    mut query_request: QueryRequest,
+    jobs: &mut Vec<CompletedJob>,
+) -> Result<QueryResponse, Error> {
+    let job_settings = &settings.job;
+    query_request.maximum_bytes_billed = job_settings
+        .maximum_bytes_billed
+        .map(|bytes| bytes.to_string());
+    query_request.use_query_cache = job_settings.use_query_cache;
+    query_request.location.clone_from(&job_settings.location);
+    if !job_settings.labels.is_empty() {
+        query_request.labels = Some(job_settings.labels.clone());
+    }
+    trace::trace_sql(&query_request, settings.parameter_redaction);
+    let job = query_job(project_id, job_settings, query_request);
+
+    // the slot is given back once the job has completed, or failed.
+    let _job_slot = settings.job_slots.acquire(metrics).await?;
+    let running_job = RunningJob::new(
+        bigquery_client,
+        metrics,
+        project_id,
+        job.job_reference.clone(),
+    );
+    let result = wait_for_job(running_job, async {
+        let inserted = retry::retry(&settings.retry, metrics, || {
+            bigquery_client.job().insert(project_id, job.clone())
+        })
+        .await;
+        let job_reference = match inserted {
+            Ok(inserted) => inserted.job_reference,
+            // a retried insertion fails if an earlier attempt created the job after all.
+            Err(err) if is_duplicate_job(&err) => job.job_reference.clone(),
+            Err(err) => return Err(err),
+        };
+        trace::record_job_reference(job_reference.as_ref());
+
+        let mut response = QueryResponse {
+            job_complete: Some(false),
+            job_reference,
+            ..Default::default()
+        };
+        while !response.job_complete.unwrap_or(false) {
+            response = get_query_results(
+                bigquery_client,
+                metrics,
+                project_id,
+                settings,
+                &response,
+                None,
+            )
+            .await?;
+        }
+        Ok(response)
+    })
+    .await;
+    if let Ok(response) = &result {
+        trace::record_job_response(response);
+        jobs.extend(record_job_statistics(
+            bigquery_client,
+            metrics,
+            project_id,
+            collection,
+            response.job_reference.as_ref(),
+        ));
+    }
+    result
+}
+
+/// Wait for a job to finish. Unless we know that the job has finished, because it completed or
+/// BigQuery failed it, the job is cancelled: failing to insert the job or to get its results
+/// does not stop it from running.
+async fn wait_for_job(
+    running_job: RunningJob,
+    wait: impl std::future::Future<Output = Result<QueryResponse, Error>>,
+) -> Result<QueryResponse, Error> {
+    let result = wait.await;
+    let finished = match &result {
+        Ok(response) => response.job_complete == Some(true),
+        Err(err) => is_job_error(err),
+    };
+    if finished {
+        running_job.finished();
+    }
+    result
+}
+
+/// Whether BigQuery failed a job, as opposed to failing a request about the job.
+fn is_job_error(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::BigQuery(BigQueryError {
+            reason: Reason::InvalidQuery,
+            ..
+        }) | Error::Query(QueryError::BytesBilledLimitExceeded(_))
+    )
+}
+
+/// Record the statistics of a completed job in metrics, and its statement type in the current
+/// span. The statistics are fetched in the background, so that the response is not delayed.
+pub(crate) fn record_job_statistics(
+    bigquery_client: &gcp_bigquery_client::Client,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    collection: &str,
+    job_reference: Option<&JobReference>,
+) -> Option<CompletedJob> {
+    let Some(JobReference {
+        job_id: Some(job_id),
+        location,
+        ..
+    }) = job_reference.cloned()
+    else {
+        return None;
+    };
+    let bigquery_client = bigquery_client.clone();
+    let metrics = metrics.clone();
+    let project_id = project_id.to_string();
+    let collection = collection.to_string();
+    let span = tracing::Span::current();
+    let statistics = tokio::spawn(
+        {
+            let job_id = job_id.clone();
+            async move {
+                match bigquery_client
+                    .job()
+                    .get_job(&project_id, &job_id, location.as_deref())
+                    .await
+                {
+                    Ok(Job {
+                        statistics: Some(statistics),
+                        ..
+                    }) => {
+                        if let Some(statement_type) = statistics
+                            .query
+                            .as_ref()
+                            .and_then(|query| query.statement_type.as_deref())
+                        {
+                            tracing::Span::current().record("statement_type", statement_type);
+                        }
+                        let statistics = job_statistics(&statistics);
+                        metrics.record_job(&collection, &statistics);
+                        Some(statistics)
+                    }
+                    Ok(_) => None,
+                    Err(err) => {
+                        tracing::warn!(job_id, "unable to fetch the statistics of the job: {err}");
+                        None
+                    }
+                }
+            }
+        }
+        .instrument(span),
+    );
+    Some(CompletedJob { job_id, statistics })
+}
+
+/// The statistics of a job relevant to metrics. Numbers and times (in milliseconds since the
+/// epoch) are returned as strings.
+fn job_statistics(statistics: &JobStatistics) -> metrics::JobStatistics {
+    fn number(value: Option<&String>) -> Option<u64> {
+        value.and_then(|value| value.parse().ok())
+    }
+    fn elapsed(from: Option<u64>, to: Option<u64>) -> Option<Duration> {
+        Some(Duration::from_millis(to?.checked_sub(from?)?))
+    }
+
+    let query = statistics.query.as_ref();
+    let creation_time = number(statistics.creation_time.as_ref());
+    metrics::JobStatistics {
+        bytes_processed: number(statistics.total_bytes_processed.as_ref()),
+        bytes_billed: number(query.and_then(|query| query.total_bytes_billed.as_ref())),
+        slot_milliseconds: number(statistics.total_slot_ms.as_ref()),
+        cache_hit: query.and_then(|query| query.cache_hit).unwrap_or(false),
+        queue_time: elapsed(creation_time, number(statistics.start_time.as_ref())),
+        wait_time: elapsed(creation_time, number(statistics.end_time.as_ref())),
+    }
+}
+
+/// A query job we are waiting for. If we stop waiting before the job finishes, because the
+/// query timed out or the request was dropped, the job is cancelled when this is dropped, so
+/// that it does not keep running (and billing) for nothing.
+struct RunningJob {
+    metrics: metrics::Metrics,
+    job_reference: Option<JobReference>,
+    /// Cancel the job, given its id and location.
+    cancel: Option<Box<dyn FnOnce(String, Option<String>) + Send>>,
+}
+
+impl RunningJob {
+    /// A job about to be sent to BigQuery, which is cancelled with the client.
+    fn new(
+        bigquery_client: &gcp_bigquery_client::Client,
+        metrics: &metrics::Metrics,
+        project_id: &str,
+        job_reference: Option<JobReference>,
+    ) -> RunningJob {
+        let bigquery_client = bigquery_client.clone();
+        let project_id = project_id.to_string();
+        RunningJob::with_cancel(metrics, job_reference, move |job_id, location| {
+            // `drop` cannot wait for the cancellation, so we cancel the job in the background.
+            let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+                return;
+            };
+            runtime.spawn(async move {
+                match bigquery_client
+                    .job()
+                    .cancel_job(&project_id, &job_id, location.as_deref())
+                    .await
+                {
+                    Ok(_) => tracing::info!(job_id, "cancelled the query job"),
+                    Err(err) => tracing::warn!(job_id, "unable to cancel the query job: {err}"),
+                }
+            });
+        })
+    }
+
+    fn with_cancel(
+        metrics: &metrics::Metrics,
+        job_reference: Option<JobReference>,
+        cancel: impl FnOnce(String, Option<String>) + Send + 'static,
+    ) -> RunningJob {
+        RunningJob {
+            metrics: metrics.clone(),
+            job_reference,
+            cancel: Some(Box::new(cancel)),
+        }
+    }
+
+    /// The job has finished: do not cancel it.
+    fn finished(mut self) {
+        self.job_reference = None;
+    }
+}
+
+impl Drop for RunningJob {
+    fn drop(&mut self) {
+        let Some(JobReference {
+            job_id: Some(job_id),
+            location,
+            ..
+        }) = self.job_reference.take()
+        else {
+            return;
+        };
+        self.metrics.record_query_cancelled();
+        if let Some(cancel) = self.cancel.take() {
+            cancel(job_id, location);
+        }
+    }
+}
+
+/// Whether inserting a job failed because a job with the same id already exists.
+fn is_duplicate_job(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::BigQuery(BigQueryError {
+            error: BQError::ResponseError { error },
+            ..
+        }) if error.error.code == 409
+    )
+}
+
+/// A query job equivalent to a query request, with the job settings applied. The job has a
+/// fresh id, so that inserting it again cannot run the query twice.
+fn query_job(project_id: &str, job_settings: &JobSettings, query_request: QueryRequest) -> Job {
+    Job {
+        configuration: Some(JobConfiguration {
+            job_timeout_ms: job_settings
+                .job_timeout_ms
+                .map(|timeout| timeout.to_string()),
+            labels: query_request.labels,
+            query: Some(JobConfigurationQuery {
+                query: query_request.query,
+                query_parameters: query_request.query_parameters,
+                parameter_mode: Some("NAMED".to_string()),
+                use_legacy_sql: Some(false),
+                maximum_bytes_billed: query_request.maximum_bytes_billed,
+                use_query_cache: query_request.use_query_cache,
+                priority: job_settings
+                    .priority
+                    .map(|priority| priority.as_str().to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        job_reference: Some(JobReference {
+            project_id: Some(project_id.to_string()),
+            job_id: Some(uuid::Uuid::new_v4().to_string()),
+            location: query_request.location,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Read every page of the results of a completed query, starting from its first page.
+/// Returns the schema and rows of the result.
+pub(crate) async fn fetch_all_rows(
+    bigquery_client: &gcp_bigquery_client::Client,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    settings: &Settings,
+    response: QueryResponse,
+) -> Result<(Vec<TableFieldSchema>, Vec<TableRow>), Error> {
+    fetch_pages(
+        &settings.limits,
+        response,
+        |response, page_token| async move {
+            get_query_results(
+                bigquery_client,
+                metrics,
+                project_id,
+                settings,
+                &response,
+                Some(page_token),
+            )
+            .await
+        },
+    )
+    .await
+}
+
+/// Read the rows of every page of the results of a query, starting from the response holding
+/// the first page. `next_page` fetches the page following a response, given its page token.
+async fn fetch_pages<F, Fut>(
+    limits: &ResultLimits,
+    mut response: QueryResponse,
+    mut next_page: F,
+) -> Result<(Vec<TableFieldSchema>, Vec<TableRow>), Error>
+where
+    F: FnMut(QueryResponse, String) -> Fut,
+    Fut: std::future::Future<Output = Result<QueryResponse, Error>>,
+{
+    let schema = response
+        .schema
+        .take()
+        .and_then(|schema| schema.fields)
+        .unwrap_or_default();
+    let mut rows = vec![];
+
+    loop {
+        rows.extend(response.rows.take().unwrap_or_default());
+        check_row_count(limits, total_rows(&response).max(rows.len() as u64))?;
+
+        let Some(page_token) = response.page_token.take() else {
+            break;
+        };
+        response = next_page(response, page_token).await?;
+    }
+
+    Ok((schema, rows))
+}
+
+/// Fetch a page of the results of the job which produced a response. Without a page token,
+/// this waits for the job to complete, and returns the first page.
+async fn get_query_results(
+    bigquery_client: &gcp_bigquery_client::Client,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    settings: &Settings,
+    response: &QueryResponse,
+    page_token: Option<String>,
+) -> Result<QueryResponse, Error> {
+    let job_reference = response.job_reference.as_ref();
+    let job_id = job_reference
+        .and_then(|job_reference| job_reference.job_id.as_deref())
+        .ok_or_else(|| {
+            Error::UnexpectedResult("the query did not return a job reference".to_string())
+        })?;
+    let parameters = GetQueryResultsParameters {
+        location: job_reference.and_then(|job_reference| job_reference.location.clone()),
+        max_results: page_size(&settings.limits),
+        page_token,
+        ..Default::default()
+    };
+    let response = retry::retry(&settings.retry, metrics, || {
+        bigquery_client
+            .job()
+            .get_query_results(project_id, job_id, parameters.clone())
+    })
+    .await?;
+    Ok(response.into())
+}
+
+/// Find the (temporary) table holding the results of the job which produced a response.
+async fn destination_table(
+    bigquery_client: &gcp_bigquery_client::Client,
+    metrics: &metrics::Metrics,
+    project_id: &str,
+    settings: &Settings,
+    response: &QueryResponse,
+) -> Result<TableReference, Error> {
+    let job_reference = response.job_reference.as_ref();
+    let job_id = job_reference
+        .and_then(|job_reference| job_reference.job_id.as_deref())
+        .ok_or_else(|| {
+            Error::UnexpectedResult("the query did not return a job reference".to_string())
+        })?;
+    let location = job_reference.and_then(|job_reference| job_reference.location.as_deref());
+    let job = retry::retry(&settings.retry, metrics, || {
+        bigquery_client.job().get_job(project_id, job_id, location)
+    })
+    .await?;
+    job.configuration
+        .and_then(|configuration| configuration.query)
+        .and_then(|query| query.destination_table)
+        .ok_or_else(|| {
+            Error::UnexpectedResult("the query job has no destination table".to_string())
+        })
+}
+
+fn page_size(limits: &ResultLimits) -> Option<i32> {
+    limits
+        .page_size
+        .map(|page_size| i32::try_from(page_size).unwrap_or(i32::MAX))
+}
+
+/// The total number of rows returned by a query, which BigQuery tells us with each page.
+fn total_rows(response: &QueryResponse) -> u64 {
+    response
+        .total_rows
+        .as_deref()
+        .and_then(|total_rows| total_rows.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Fail if the query returns more rows than allowed. BigQuery tells us the total number of
+/// rows with the first page, so we can usually fail before fetching the rest.
+fn check_row_count(limits: &ResultLimits, total_rows: u64) -> Result<(), Error> {
+    match limits.max_rows {
+        Some(max_rows) if total_rows > max_rows => {
+            Err(Error::Query(QueryError::TooManyRows { max_rows }))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fail if the response written so far is larger than allowed.
+fn check_result_size(limits: &ResultLimits, buffer: &BytesMut) -> Result<(), Error> {
+    match limits.max_result_size {
+        Some(max_result_size) if buffer.len() as u64 > max_result_size => {
+            Err(Error::Query(QueryError::ResultTooLarge { max_result_size }))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Convert the parameters of a SQL statement to BigQuery named query parameters
+/// (`@param1`, `@param2`, ...). Lifted literals are looked up by their index in `literals`.
+/// The only variable parameter is the placeholder of the sets of variables. JSON values are
+/// not bound as parameters, as their BigQuery type is unknown.
+pub(crate) fn query_parameters(
+    params: &[Param],
+    literals: &[Value],
+    variable_sets: &[BTreeMap<models::VariableName, Value>],
+) -> Result<Vec<query_parameter::QueryParameter>, Error> {
+    params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| match param {
+            Param::String(str) => Ok(string_parameter(
+                format!("param{}", i + 1),
+                Some(str.clone()),
+            )),
+            Param::Variable(_placeholder) => Ok(variable_sets_parameter(
+                format!("param{}", i + 1),
+                variable_sets,
+            )),
+            Param::Value(_value) => Err(Error::Query(QueryError::NotSupported(
+                "JSON values as query parameters".to_string(),
+            ))),
+            Param::Literal { index, encoding } => Ok(literal_parameter(
+                format!("param{}", i + 1),
+                literals.get(*index),
+                *encoding,
+            )),
+        })
+        .collect()
+}
+
+/// A literal lifted out of the request, bound according to how the query uses it.
+fn literal_parameter(
+    name: String,
+    literal: Option<&Value>,
+    encoding: LiteralEncoding,
+) -> query_parameter::QueryParameter {
+    let (r#type, value) = match encoding {
+        LiteralEncoding::String => (
+            "STRING",
+            literal.and_then(Value::as_str).map(str::to_string),
+        ),
+        LiteralEncoding::Float64 => ("FLOAT64", literal.map(Value::to_string)),
+        LiteralEncoding::Bool => ("BOOL", literal.map(Value::to_string)),
+        LiteralEncoding::Json => ("STRING", literal.map(Value::to_string)),
+    };
+    typed_parameter(name, r#type, value)
+}
+
+/// The sets of variables of a query, bound as an array of structs holding the position of each
+/// set and its variables as a JSON object. See `sql::helpers::from_variables`.
+fn variable_sets_parameter(
+    name: String,
+    variable_sets: &[BTreeMap<models::VariableName, Value>],
+) -> query_parameter::QueryParameter {
+    let field_type = |name: &str, r#type: &str| {
+        query_parameter_type_struct_types::QueryParameterTypeStructTypes {
+            description: None,
+            name: Some(name.to_string()),
+            r#type: Some(query_parameter_type::QueryParameterType {
+                array_type: None,
+                struct_types: None,
+                r#type: r#type.to_string(),
+            }),
+        }
+    };
+    let field_value = |value: String| query_parameter_value::QueryParameterValue {
+        array_values: None,
+        struct_values: None,
+        value: Some(value),
+    };
+    query_parameter::QueryParameter {
+        name: Some(name),
+        parameter_type: Some(query_parameter_type::QueryParameterType {
+            array_type: Some(Box::new(query_parameter_type::QueryParameterType {
+                array_type: None,
+                struct_types: Some(vec![
+                    field_type(VARIABLE_ORDER_FIELD, "INT64"),
+                    field_type(VARIABLES_FIELD, "JSON"),
+                ]),
+                r#type: "STRUCT".to_string(),
+            })),
+            struct_types: None,
+            r#type: "ARRAY".to_string(),
+        }),
+        parameter_value: Some(query_parameter_value::QueryParameterValue {
+            array_values: Some(
+                variable_sets
+                    .iter()
+                    .enumerate()
+                    .map(|(order, variables)| {
+                        let variables = serde_json::to_string(variables).unwrap_or_default();
+                        query_parameter_value::QueryParameterValue {
+                            array_values: None,
+                            struct_values: Some(HashMap::from([
+                                (
+                                    VARIABLE_ORDER_FIELD.to_string(),
+                                    field_value(order.to_string()),
+                                ),
+                                (VARIABLES_FIELD.to_string(), field_value(variables)),
+                            ])),
+                            value: None,
+                        }
+                    })
+                    .collect(),
+            ),
+            struct_values: None,
+            value: None,
+        }),
+    }
+}
+
+/// A named STRING query parameter. A missing value is bound as NULL.
+fn string_parameter(name: String, value: Option<String>) -> query_parameter::QueryParameter {
+    typed_parameter(name, "STRING", value)
+}
+
+/// A named query parameter of a scalar type. A missing value is bound as NULL.
+fn typed_parameter(
+    name: String,
+    r#type: &str,
+    value: Option<String>,
+) -> query_parameter::QueryParameter {
+    query_parameter::QueryParameter {
+        name: Some(name),
+        parameter_type: Some(query_parameter_type::QueryParameterType {
+            array_type: None,
+            struct_types: None,
+            r#type: r#type.to_string(),
+        }),
+        parameter_value: Some(query_parameter_value::QueryParameterValue {
+            array_values: None,
+            struct_values: None,
+            value,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gcp_bigquery_client::error::{NestedResponseError, ResponseError};
+    use std::sync::{Arc, Mutex};
+
+    fn test_metrics() -> metrics::Metrics {
+        metrics::Metrics::initialize(&mut prometheus::Registry::new()).unwrap()
+    }
+
+    /// A job whose cancellations are recorded in `cancelled`.
+    fn running_job(metrics: &metrics::Metrics, cancelled: &Arc<Mutex<Vec<String>>>) -> RunningJob {
+        let cancelled = Arc::clone(cancelled);
+        RunningJob::with_cancel(
+            metrics,
+            Some(JobReference {
+                job_id: Some("job_1".to_string()),
+                ..Default::default()
+            }),
+            move |job_id, _location| cancelled.lock().unwrap().push(job_id),
+        )
+    }
+
+    #[test]
+    fn cancels_a_job_dropped_before_it_finishes() {
+        let metrics = test_metrics();
+        let cancelled = Arc::new(Mutex::new(vec![]));
+
+        running_job(&metrics, &cancelled).finished();
+        assert!(cancelled.lock().unwrap().is_empty());
+
+        drop(running_job(&metrics, &cancelled));
+        assert_eq!(*cancelled.lock().unwrap(), vec!["job_1".to_string()]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancels_the_job_of_a_query_which_times_out() {
+        let metrics = test_metrics();
+        let cancelled = Arc::new(Mutex::new(vec![]));
+
+        let execution = async {
+            let job = running_job(&metrics, &cancelled);
+            // the job never completes.
+            std::future::pending::<()>().await;
+            job.finished();
+            Ok::<_, Error>(())
+        };
+        let result = with_timeout(&metrics, Some(5), execution).await;
+
+        assert!(matches!(
+            result,
+            Err(Error::Query(QueryError::TimedOut { timeout_seconds: 5 }))
+        ));
+        assert_eq!(*cancelled.lock().unwrap(), vec!["job_1".to_string()]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn does_not_cancel_the_job_of_a_query_which_completes_in_time() {
+        let metrics = test_metrics();
+        let cancelled = Arc::new(Mutex::new(vec![]));
+
+        let execution = async {
+            let job = running_job(&metrics, &cancelled);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            job.finished();
+            Ok::<_, Error>(())
+        };
+        let result = with_timeout(&metrics, Some(5), execution).await;
+
+        assert!(result.is_ok());
+        assert!(cancelled.lock().unwrap().is_empty());
+    }
+
+    fn response_error(code: i64, reason: &str) -> Error {
+        Error::from(BQError::ResponseError {
+            error: ResponseError {
+                error: NestedResponseError {
+                    code,
+                    errors: vec![HashMap::from([("reason".to_string(), reason.to_string())])],
+                    message: "message".to_string(),
+                    status: String::new(),
+                },
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn cancels_a_job_whose_results_cannot_be_fetched() {
+        let metrics = test_metrics();
+        let cancelled = Arc::new(Mutex::new(vec![]));
+
+        // getting the results of the job fails once retries are exhausted.
+        let result = wait_for_job(running_job(&metrics, &cancelled), async {
+            Err(response_error(503, "backendError"))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(*cancelled.lock().unwrap(), vec!["job_1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn does_not_cancel_a_job_which_finished() {
+        let metrics = test_metrics();
+        let cancelled = Arc::new(Mutex::new(vec![]));
+
+        let completed = wait_for_job(running_job(&metrics, &cancelled), async {
+            Ok(QueryResponse {
+                job_complete: Some(true),
+                ..Default::default()
+            })
+        })
+        .await;
+        let failed = wait_for_job(running_job(&metrics, &cancelled), async {
+            Err(response_error(400, "invalidQuery"))
+        })
+        .await;
+
+        assert!(completed.is_ok());
+        assert!(failed.is_err());
+        assert!(cancelled.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_json_value_parameters() {
+        let params = [
+            Param::String("a".to_string()),
+            Param::Value(serde_json::json!({"a": 1})),
+        ];
+        assert!(matches!(
+            query_parameters(&params, &[], &[]),
+            Err(Error::Query(QueryError::NotSupported(_)))
+        ));
+    }
+
+    #[test]
+    fn binds_each_set_of_variables_as_json_in_order() {
+        // the values of variables are bound as they are, whether they have the expected shape or
+        // not, and converted by the query.
+        let variable_sets: Vec<BTreeMap<models::VariableName, Value>> = vec![
+            BTreeMap::from([("artist_ids".into(), serde_json::json!([3, 1, 2]))]),
+            BTreeMap::from([("artist_ids".into(), Value::Null)]),
+            BTreeMap::from([("artist_ids".into(), serde_json::json!({"id": 1}))]),
+        ];
+
+        let parameter = variable_sets_parameter("param1".to_string(), &variable_sets);
+
+        let bound: Vec<(Option<String>, Option<String>)> = parameter
+            .parameter_value
+            .and_then(|value| value.array_values)
+            .unwrap()
+            .into_iter()
+            .map(|value| {
+                let fields = value.struct_values.unwrap();
+                (
+                    fields[VARIABLE_ORDER_FIELD].value.clone(),
+                    fields[VARIABLES_FIELD].value.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            bound,
+            vec![
+                (
+                    Some("0".to_string()),
+                    Some(r#"{"artist_ids":[3,1,2]}"#.to_string())
+                ),
+                (
+                    Some("1".to_string()),
+                    Some(r#"{"artist_ids":null}"#.to_string())
+                ),
+                (
+                    Some("2".to_string()),
+                    Some(r#"{"artist_ids":{"id":1}}"#.to_string())
+                ),
+            ]
+        );
+    }
+
+    /// Sets of variables numbering their position.
+    fn numbered_variable_sets(count: u64) -> Vec<BTreeMap<models::VariableName, Value>> {
+        (0..count)
+            .map(|n| BTreeMap::from([("n".into(), serde_json::json!(n))]))
+            .collect()
+    }
+
+    /// A row holding a `RowSet` as JSON, as queries run on sets of variables return.
+    fn row_set_row(row_set: &Value) -> TableRow {
+        TableRow {
+            columns: Some(vec![gcp_bigquery_client::model::table_cell::TableCell {
+                value: Some(Value::String(row_set.to_string())),
+            }]),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_chunks_in_parallel_and_returns_them_in_order() {
+        let variable_sets = numbered_variable_sets(7);
+        let running = Arc::new(Mutex::new((0, 0)));
+
+        let chunks = run_chunks(
+            ForeachStrategy::Chunked {
+                chunk_size: 2,
+                max_concurrency: 2,
+            },
+            &variable_sets,
+            |variable_sets| {
+                let running = Arc::clone(&running);
+                async move {
+                    {
+                        let (now, most) = &mut *running.lock().unwrap();
+                        *now += 1;
+                        *most = (*most).max(*now);
+                    }
+                    // the later chunks complete first.
+                    let first = variable_sets[0]["n"].as_u64().unwrap();
+                    tokio::time::sleep(Duration::from_millis(100 - first * 10)).await;
+                    running.lock().unwrap().0 -= 1;
+                    Ok(variable_sets.to_vec())
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks.concat(), variable_sets);
+        assert_eq!(running.lock().unwrap().1, 2);
+    }
+
+    #[tokio::test]
+    async fn runs_a_single_batch_on_every_set_of_variables() {
+        let variable_sets = numbered_variable_sets(5);
+        let chunks = run_chunks(
+            ForeachStrategy::Batched,
+            &variable_sets,
+            |variable_sets| async { Ok(variable_sets.to_vec()) },
+        )
+        .await
+        .unwrap();
+        assert_eq!(chunks, vec![variable_sets]);
+    }
+
+    #[tokio::test]
+    async fn returns_an_empty_list_for_no_sets_of_variables() {
+        for foreach in [
+            ForeachStrategy::Batched,
+            ForeachStrategy::Chunked {
+                chunk_size: 2,
+                max_concurrency: 2,
+            },
+        ] {
+            let chunks: Vec<Vec<TableRow>> = run_chunks(foreach, &[], |_| async {
+                Err(Error::UnexpectedResult(
+                    "no query runs without sets of variables".to_string(),
+                ))
+            })
+            .await
+            .unwrap();
+            assert!(chunks.is_empty());
+
+            let mut buffer = BytesMut::new();
+            let rows_returned = write_row_sets(
+                &ResultLimits::default(),
+                chunks.iter().flatten(),
+                &mut buffer,
+            )
+            .unwrap();
+            assert_eq!(rows_returned, 0);
+            assert_eq!(&buffer[..], b"[]");
+        }
+    }
+
+    #[test]
+    fn writes_the_row_sets_of_each_set_of_variables_in_order() {
+        let row_sets = [
+            serde_json::json!({"rows": [{"Title": "Facelift"}]}),
+            serde_json::json!({"rows": []}),
+            serde_json::json!({"rows": [{"Title": "Dirt"}, {"Title": "Jar of Flies"}]}),
+        ];
+        let rows: Vec<TableRow> = row_sets.iter().map(row_set_row).collect();
+
+        let mut buffer = BytesMut::new();
+        let rows_returned =
+            write_row_sets(&ResultLimits::default(), rows.iter(), &mut buffer).unwrap();
+        assert_eq!(rows_returned, 3);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&buffer).unwrap(),
+            Value::Array(row_sets.to_vec())
+        );
+    }
+
+    /// A row with a single cell.
+    fn row(value: &str) -> TableRow {
+        TableRow {
+            columns: Some(vec![gcp_bigquery_client::model::table_cell::TableCell {
+                value: Some(Value::String(value.to_string())),
+            }]),
+        }
+    }
+
+    /// A page of results, with the token of the next page if there is one.
+    fn page(rows: &[&str], total_rows: Option<u64>, page_token: Option<&str>) -> QueryResponse {
+        QueryResponse {
+            rows: Some(rows.iter().map(|value| row(value)).collect()),
+            total_rows: total_rows.map(|total_rows| total_rows.to_string()),
+            page_token: page_token.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn cell_values(rows: &[TableRow]) -> Vec<Value> {
+        rows.iter()
+            .map(|row| row.columns.as_ref().unwrap()[0].value.clone().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reads_every_page_of_the_results() {
+        let mut pages = HashMap::from([
+            ("p2".to_string(), page(&["3", "4"], Some(5), Some("p3"))),
+            ("p3".to_string(), page(&["5"], Some(5), None)),
+        ]);
+        let mut requested = vec![];
+
+        let (_schema, rows) =
+            fetch_pages(
+                &ResultLimits::default(),
+                page(&["1", "2"], Some(5), Some("p2")),
+                |_response, page_token| {
+                    requested.push(page_token.clone());
+                    let page = pages.remove(&page_token);
+                    async move {
+                        page.ok_or_else(|| Error::UnexpectedResult("no such page".to_string()))
+                    }
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(requested, vec!["p2".to_string(), "p3".to_string()]);
+        assert_eq!(
+            cell_values(&rows),
+            ["1", "2", "3", "4", "5"].map(|value| Value::String(value.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_before_reading_more_pages_when_the_results_have_too_many_rows() {
+        let limits = ResultLimits {
+            max_rows: Some(3),
+            ..ResultLimits::default()
+        };
+        let result = fetch_pages(
+            &limits,
+            page(&["1", "2"], Some(5), Some("p2")),
+            |_response, _page_token| async {
+                Err(Error::UnexpectedResult("read another page".to_string()))
+            },
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::Query(QueryError::TooManyRows { max_rows: 3 }))
+        ));
+
+        // without a total number of rows, the rows read so far are counted.
+        let result = fetch_pages(
+            &limits,
+            page(&["1", "2"], None, Some("p2")),
+            |_response, _page_token| async { Ok(page(&["3", "4"], None, None)) },
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::Query(QueryError::TooManyRows { max_rows: 3 }))
+        ));
+    }
+
+    #[test]
+    fn fails_when_the_response_is_larger_than_the_maximum_size() {
+        let rows: Vec<TableRow> = (0..10)
+            .map(|_| row_set_row(&serde_json::json!({"rows": [{"Title": "Facelift"}]})))
+            .collect();
+        let limits = ResultLimits {
+            max_result_size: Some(100),
+            ..ResultLimits::default()
+        };
+
+        let mut buffer = BytesMut::new();
+        assert!(write_row_sets(&limits, rows[..2].iter(), &mut buffer).is_ok());
+
+        let mut buffer = BytesMut::new();
+        assert!(matches!(
+            write_row_sets(&limits, rows.iter(), &mut buffer),
+            Err(Error::Query(QueryError::ResultTooLarge {
+                max_result_size: 100
+            }))
+        ));
+    }
 }
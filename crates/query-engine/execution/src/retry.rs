@@ -0,0 +1,226 @@
+//! Retry requests to BigQuery which fail transiently.
+//!
+//! BigQuery regularly fails requests because a rate limit was reached, or because of an
+//! internal error, and the same request usually succeeds a little later. We retry such
+//! requests with exponential backoff and jitter, until they succeed, fail for another reason,
+//! or we run out of attempts or time.
+//!
+//! Only requests which are safe to repeat may be retried: reads, and query jobs whose job id
+//! ensures BigQuery runs them at most once.
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::error::Error;
+use crate::metrics;
+
+/// How requests which fail transiently are retried.
+#[derive(Debug, Clone)]
+pub struct Policy {
+    /// The maximum number of attempts of a request, including the first one.
+    pub max_attempts: u32,
+    /// The backoff before the first retry. It doubles with each retry.
+    pub initial_backoff: Duration,
+    /// The maximum backoff between two attempts.
+    pub max_backoff: Duration,
+    /// Do not retry once this much time has passed since the first attempt.
+    pub deadline: Option<Duration>,
+}
+
+impl Policy {
+    /// Never retry.
+    pub fn none() -> Policy {
+        Policy {
+            max_attempts: 1,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            deadline: None,
+        }
+    }
+
+    /// The backoff before a retry, counting retries from 1. The exponential backoff is
+    /// randomized between half and all of it, so that requests failing together are not
+    /// retried together.
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2_u32.saturating_pow(retry - 1))
+            .min(self.max_backoff);
+        backoff.mul_f64(0.5 + rand::random::<f64>() / 2.0)
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy::none()
+    }
+}
+
+/// Send a request, and send it again while it fails with a retryable error, according to the
+/// policy. The last error is returned if the request never succeeds.
+pub(crate) async fn retry<T, E, F, Fut>(
+    policy: &Policy,
+    metrics: &metrics::Metrics,
+    mut request: F,
+) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    Error: From<E>,
+{
+    let started = Instant::now();
+    let mut attempt = 1;
+    loop {
+        match request().await.map_err(Error::from) {
+            Err(Error::BigQuery(error))
+                if error.reason.is_retryable() && attempt < policy.max_attempts =>
+            {
+                let backoff = policy.backoff(attempt);
+                if policy
+                    .deadline
+                    .is_some_and(|deadline| started.elapsed() + backoff > deadline)
+                {
+                    return Err(Error::BigQuery(error));
+                }
+                tracing::warn!(
+                    attempt,
+                    reason = error.reason.as_str(),
+                    "retrying a BigQuery request in {backoff:?}: {error}"
+                );
+                metrics.record_bigquery_request_retried();
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Reason;
+    use gcp_bigquery_client::error::{BQError, NestedResponseError, ResponseError};
+    use std::collections::{HashMap, VecDeque};
+
+    fn policy(max_attempts: u32, deadline: Option<Duration>) -> Policy {
+        Policy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            deadline,
+        }
+    }
+
+    fn response_error(code: i64, reason: &str) -> BQError {
+        BQError::ResponseError {
+            error: ResponseError {
+                error: NestedResponseError {
+                    code,
+                    errors: vec![HashMap::from([("reason".to_string(), reason.to_string())])],
+                    message: "message".to_string(),
+                    status: String::new(),
+                },
+            },
+        }
+    }
+
+    /// Retry a request which fails with these errors in turn, and then succeeds. Returns the
+    /// result and the number of attempts.
+    async fn retry_failures(policy: &Policy, failures: Vec<BQError>) -> (Result<(), Error>, u32) {
+        let metrics = metrics::Metrics::initialize(&mut prometheus::Registry::new()).unwrap();
+        let mut failures = VecDeque::from(failures);
+        let mut attempts = 0;
+        let result = retry(policy, &metrics, || {
+            attempts += 1;
+            let failure = failures.pop_front();
+            async move { failure.map_or(Ok(()), Err) }
+        })
+        .await;
+        (result, attempts)
+    }
+
+    fn reason(result: Result<(), Error>) -> Option<Reason> {
+        match result {
+            Err(Error::BigQuery(error)) => Some(error.reason),
+            _ => None,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn does_not_retry_errors_which_are_not_transient() {
+        let started = Instant::now();
+        let (result, attempts) =
+            retry_failures(&policy(3, None), vec![response_error(400, "invalidQuery")]).await;
+        assert_eq!(reason(result), Some(Reason::InvalidQuery));
+        assert_eq!(attempts, 1);
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_transient_errors_until_the_request_succeeds() {
+        for (code, reason) in [
+            (403, "quotaExceeded"),
+            (429, "rateLimitExceeded"),
+            (500, "backendError"),
+        ] {
+            let (result, attempts) = retry_failures(
+                &policy(3, None),
+                vec![response_error(code, reason), response_error(code, reason)],
+            )
+            .await;
+            assert!(result.is_ok(), "{reason}: {result:?}");
+            assert_eq!(attempts, 3, "{reason}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_the_maximum_number_of_attempts() {
+        let (result, attempts) = retry_failures(
+            &policy(3, None),
+            (0..5)
+                .map(|_| response_error(500, "backendError"))
+                .collect(),
+        )
+        .await;
+        assert_eq!(reason(result), Some(Reason::BackendError));
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_when_the_next_attempt_would_be_after_the_deadline() {
+        // The first backoff is at most 100ms, and the first two at least 150ms together.
+        let started = Instant::now();
+        let (result, attempts) = retry_failures(
+            &policy(10, Some(Duration::from_millis(140))),
+            (0..10)
+                .map(|_| response_error(429, "rateLimitExceeded"))
+                .collect(),
+        )
+        .await;
+        assert_eq!(reason(result), Some(Reason::QuotaExceeded));
+        assert_eq!(attempts, 2);
+        assert!(started.elapsed() <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_maximum() {
+        let policy = Policy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            deadline: None,
+        };
+        for (retry, expected) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000), (9, 1000)] {
+            let backoff = policy.backoff(retry);
+            let expected = Duration::from_millis(expected);
+            assert!(
+                backoff >= expected / 2 && backoff <= expected,
+                "retry {retry}: {backoff:?} is not between {:?} and {expected:?}",
+                expected / 2
+            );
+        }
+    }
+}
@@ -0,0 +1,475 @@
+//! Decode rows read through the Storage Read API, which are encoded with the Avro binary
+//! encoding (<https://avro.apache.org/docs/1.11.1/specification/#binary-encoding>).
+//!
+//! BigQuery describes the rows with an Avro schema, in which each column type maps to an Avro
+//! type, sometimes refined by a logical type or a SQL type (e.g. NUMERIC is a `decimal`
+//! represented as `bytes`, and JSON is a `string` whose SQL type is `JSON`). We convert the
+//! values to the same JSON representation as the rows returned by `getQueryResults`.
+
+use base64::Engine;
+use ndc_models as models;
+use serde_json::{Map, Number, Value};
+
+use crate::decode::{format_date, format_time, format_timestamp};
+use crate::error::Error;
+
+/// The subset of Avro schemas BigQuery uses to describe rows.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Schema {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    /// An `int` counting days since the epoch.
+    Date,
+    /// A `long` counting microseconds since midnight.
+    TimeMicros,
+    /// A `long` counting microseconds since the epoch.
+    TimestampMicros,
+    /// A two's-complement big-endian integer, scaled by `10^-scale`.
+    Decimal {
+        scale: u32,
+    },
+    /// A `string` holding a JSON document.
+    Json,
+    Array(Box<Schema>),
+    Union(Vec<Schema>),
+    Record(Vec<(String, Schema)>),
+}
+
+impl Schema {
+    /// Parse the JSON representation of a schema.
+    pub(crate) fn parse(schema: &str) -> Result<Schema, Error> {
+        let value: Value = serde_json::from_str(schema)
+            .map_err(|err| Error::UnexpectedResult(format!("invalid Avro schema: {err}")))?;
+        Schema::from_json(&value)
+    }
+
+    fn from_json(value: &Value) -> Result<Schema, Error> {
+        match value {
+            Value::String(name) => Schema::primitive(name),
+            Value::Array(branches) => branches
+                .iter()
+                .map(Schema::from_json)
+                .collect::<Result<_, _>>()
+                .map(Schema::Union),
+            Value::Object(object) => {
+                let logical_type = object.get("logicalType").and_then(Value::as_str);
+                let sql_type = object.get("sqlType").and_then(Value::as_str);
+                match (object.get("type"), logical_type, sql_type) {
+                    (_, Some("date"), _) => Ok(Schema::Date),
+                    (_, Some("time-micros"), _) => Ok(Schema::TimeMicros),
+                    (_, Some("timestamp-micros"), _) => Ok(Schema::TimestampMicros),
+                    (_, Some("decimal"), _) => {
+                        let scale = object.get("scale").and_then(Value::as_u64).unwrap_or(0);
+                        Ok(Schema::Decimal {
+                            scale: u32::try_from(scale).map_err(|_| unsupported(value))?,
+                        })
+                    }
+                    (_, _, Some("JSON")) => Ok(Schema::Json),
+                    (Some(Value::String(name)), _, _) if name == "record" => object
+                        .get("fields")
+                        .and_then(Value::as_array)
+                        .ok_or_else(|| unsupported(value))?
+                        .iter()
+                        .map(|field| {
+                            let name = field
+                                .get("name")
+                                .and_then(Value::as_str)
+                                .ok_or_else(|| unsupported(field))?;
+                            let schema = field.get("type").ok_or_else(|| unsupported(field))?;
+                            Ok((name.to_string(), Schema::from_json(schema)?))
+                        })
+                        .collect::<Result<_, _>>()
+                        .map(Schema::Record),
+                    (Some(Value::String(name)), _, _) if name == "array" => {
+                        let items = object.get("items").ok_or_else(|| unsupported(value))?;
+                        Ok(Schema::Array(Box::new(Schema::from_json(items)?)))
+                    }
+                    // other logical types (such as `datetime`) and SQL types (such as
+                    // `GEOGRAPHY`) are represented by their underlying type.
+                    (Some(underlying), _, _) => Schema::from_json(underlying),
+                    (None, _, _) => Err(unsupported(value)),
+                }
+            }
+            _ => Err(unsupported(value)),
+        }
+    }
+
+    fn primitive(name: &str) -> Result<Schema, Error> {
+        match name {
+            "null" => Ok(Schema::Null),
+            "boolean" => Ok(Schema::Boolean),
+            "int" => Ok(Schema::Int),
+            "long" => Ok(Schema::Long),
+            "float" => Ok(Schema::Float),
+            "double" => Ok(Schema::Double),
+            "bytes" => Ok(Schema::Bytes),
+            "string" => Ok(Schema::String),
+            _ => Err(unsupported(&Value::String(name.to_string()))),
+        }
+    }
+}
+
+fn unsupported(schema: &Value) -> Error {
+    Error::UnexpectedResult(format!("unsupported Avro schema: {schema}"))
+}
+
+/// Decode a block of rows into objects whose keys are the requested fields. The schema of the
+/// rows must be a record with one field for each requested field.
+pub(crate) fn decode_rows(
+    fields: &[models::FieldName],
+    schema: &Schema,
+    bytes: &[u8],
+) -> Result<Vec<Map<String, Value>>, Error> {
+    let Schema::Record(columns) = schema else {
+        return Err(Error::UnexpectedResult(format!(
+            "expected rows to be Avro records, got {schema:?}"
+        )));
+    };
+    if columns.len() != fields.len() {
+        return Err(Error::UnexpectedResult(format!(
+            "expected {} columns, got {} columns",
+            fields.len(),
+            columns.len()
+        )));
+    }
+    let mut reader = Reader { bytes };
+    let mut rows = vec![];
+    while !reader.bytes.is_empty() {
+        let row = fields
+            .iter()
+            .zip(columns)
+            .map(|(field, (_, column))| Ok((field.to_string(), reader.read_value(column)?)))
+            .collect::<Result<Map<String, Value>, Error>>()?;
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Reads Avro-encoded values from the front of a slice of bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn read_value(&mut self, schema: &Schema) -> Result<Value, Error> {
+        match schema {
+            Schema::Null => Ok(Value::Null),
+            Schema::Boolean => match self.take(1)? {
+                [0] => Ok(Value::Bool(false)),
+                [1] => Ok(Value::Bool(true)),
+                other => Err(invalid(&format!("boolean {other:?}"))),
+            },
+            Schema::Int => Ok(Value::Number(self.read_long()?.into())),
+            // integers and decimals are rendered as JSON numbers, as BigQuery renders them in
+            // JSON. Columns represented as strings are cast to strings by the query.
+            Schema::Long => Ok(Value::Number(self.read_long()?.into())),
+            Schema::Float => {
+                let bytes = self.take(4)?.try_into().map_err(|_| invalid("float"))?;
+                Ok(float(f64::from(f32::from_le_bytes(bytes))))
+            }
+            Schema::Double => {
+                let bytes = self.take(8)?.try_into().map_err(|_| invalid("double"))?;
+                Ok(float(f64::from_le_bytes(bytes)))
+            }
+            Schema::Bytes => Ok(Value::String(
+                base64::engine::general_purpose::STANDARD.encode(self.read_bytes()?),
+            )),
+            Schema::String => Ok(Value::String(self.read_string()?.to_string())),
+            Schema::Date => Ok(Value::String(format_date(self.read_long()?))),
+            Schema::TimeMicros => Ok(Value::String(format_time(self.read_long()?))),
+            Schema::TimestampMicros => Ok(Value::String(format_timestamp(self.read_long()?))),
+            Schema::Decimal { scale } => {
+                let decimal = format_decimal(self.read_bytes()?, *scale);
+                decimal
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+                    .ok_or_else(|| invalid(&format!("decimal {decimal}")))
+            }
+            Schema::Json => {
+                let string = self.read_string()?;
+                serde_json::from_str(string).map_err(|_| invalid(&format!("JSON {string:?}")))
+            }
+            Schema::Array(items) => {
+                let mut values = vec![];
+                loop {
+                    let count = self.read_long()?;
+                    if count == 0 {
+                        break;
+                    }
+                    // a negative count is followed by the size of the block in bytes.
+                    if count < 0 {
+                        self.read_long()?;
+                    }
+                    for _ in 0..count.unsigned_abs() {
+                        values.push(self.read_value(items)?);
+                    }
+                }
+                Ok(Value::Array(values))
+            }
+            Schema::Union(branches) => {
+                let index = self.read_long()?;
+                let branch = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| branches.get(index))
+                    .ok_or_else(|| invalid(&format!("union branch {index}")))?;
+                self.read_value(branch)
+            }
+            Schema::Record(fields) => fields
+                .iter()
+                .map(|(name, field)| Ok((name.clone(), self.read_value(field)?)))
+                .collect::<Result<Map<_, _>, Error>>()
+                .map(Value::Object),
+        }
+    }
+
+    /// Read a zig-zag encoded variable-length integer.
+    #[allow(clippy::cast_possible_wrap)]
+    fn read_long(&mut self) -> Result<i64, Error> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+        }
+        Err(invalid("variable-length integer"))
+    }
+
+    fn read_bytes(&mut self) -> Result<&'a [u8], Error> {
+        let length = self.read_long()?;
+        let length = usize::try_from(length).map_err(|_| invalid(&format!("length {length}")))?;
+        self.take(length)
+    }
+
+    fn read_string(&mut self) -> Result<&'a str, Error> {
+        std::str::from_utf8(self.read_bytes()?).map_err(|_| invalid("UTF-8 string"))
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if length > self.bytes.len() {
+            return Err(invalid("row: unexpected end of data"));
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+}
+
+fn invalid(what: &str) -> Error {
+    Error::UnexpectedResult(format!("invalid Avro {what}"))
+}
+
+/// NaN and infinities cannot be represented as JSON numbers, so we represent them as strings,
+/// like BigQuery does.
+fn float(value: f64) -> Value {
+    Number::from_f64(value).map_or_else(
+        || {
+            Value::String(
+                match value {
+                    v if v.is_nan() => "NaN",
+                    v if v > 0.0 => "Infinity",
+                    _ => "-Infinity",
+                }
+                .to_string(),
+            )
+        },
+        Value::Number,
+    )
+}
+
+/// Format a two's-complement big-endian integer scaled by `10^-scale` as a decimal number,
+/// without trailing zeros (e.g. `12.5`).
+#[allow(clippy::cast_possible_truncation)]
+fn format_decimal(bytes: &[u8], scale: u32) -> String {
+    let negative = bytes.first().is_some_and(|byte| byte & 0x80 != 0);
+    // the magnitude of the number, in base 256.
+    let mut magnitude: Vec<u8> = if negative {
+        let mut carry = true;
+        let mut magnitude: Vec<u8> = bytes.iter().map(|byte| !byte).collect();
+        for byte in magnitude.iter_mut().rev() {
+            let (sum, overflow) = byte.overflowing_add(u8::from(carry));
+            *byte = sum;
+            carry = overflow;
+        }
+        magnitude
+    } else {
+        bytes.to_vec()
+    };
+
+    // convert the magnitude to base 10 by repeatedly dividing it by 10.
+    let mut digits = vec![];
+    while magnitude.iter().any(|byte| *byte != 0) {
+        let mut remainder: u16 = 0;
+        for byte in &mut magnitude {
+            let current = remainder << 8 | u16::from(*byte);
+            *byte = (current / 10) as u8;
+            remainder = current % 10;
+        }
+        digits.push(char::from(
+            b'0' + u8::try_from(remainder).unwrap_or_default(),
+        ));
+    }
+
+    let scale = scale as usize;
+    while digits.len() <= scale {
+        digits.push('0');
+    }
+    digits.reverse();
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    let integer: String = integer.iter().collect();
+    let fraction: String = fraction.iter().collect();
+    let fraction = fraction.trim_end_matches('0');
+    let sign = if negative { "-" } else { "" };
+    if fraction.is_empty() {
+        format!("{sign}{integer}")
+    } else {
+        format!("{sign}{integer}.{fraction}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Encode an integer as a zig-zag variable-length integer.
+    fn long(value: i64) -> Vec<u8> {
+        #[allow(clippy::cast_sign_loss)]
+        let mut value = ((value << 1) ^ (value >> 63)) as u64;
+        let mut bytes = vec![];
+        loop {
+            #[allow(clippy::cast_possible_truncation)]
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte);
+                return bytes;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        let length = i64::try_from(value.len()).unwrap();
+        [long(length), value.as_bytes().to_vec()].concat()
+    }
+
+    #[test]
+    fn bigquery_schemas_are_parsed() {
+        let schema = Schema::parse(
+            r#"{
+                "type": "record",
+                "name": "__root__",
+                "fields": [
+                    { "name": "id", "type": ["null", "long"] },
+                    { "name": "price", "type": ["null", { "type": "bytes", "logicalType": "decimal", "precision": 38, "scale": 9 }] },
+                    { "name": "created", "type": ["null", { "type": "long", "logicalType": "timestamp-micros" }] },
+                    { "name": "updated", "type": ["null", { "type": "string", "logicalType": "datetime" }] },
+                    { "name": "data", "type": ["null", { "type": "string", "sqlType": "JSON" }] },
+                    { "name": "tags", "type": { "type": "array", "items": "string" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let nullable = |schema| Schema::Union(vec![Schema::Null, schema]);
+        assert_eq!(
+            schema,
+            Schema::Record(vec![
+                ("id".to_string(), nullable(Schema::Long)),
+                ("price".to_string(), nullable(Schema::Decimal { scale: 9 })),
+                ("created".to_string(), nullable(Schema::TimestampMicros)),
+                ("updated".to_string(), nullable(Schema::String)),
+                ("data".to_string(), nullable(Schema::Json)),
+                ("tags".to_string(), Schema::Array(Box::new(Schema::String))),
+            ])
+        );
+    }
+
+    #[test]
+    fn rows_are_decoded_according_to_their_schema() {
+        let nullable = |schema| Schema::Union(vec![Schema::Null, schema]);
+        let schema = Schema::Record(vec![
+            ("id".to_string(), nullable(Schema::Long)),
+            ("ratio".to_string(), Schema::Double),
+            ("day".to_string(), Schema::Date),
+            ("tags".to_string(), Schema::Array(Box::new(Schema::String))),
+            ("data".to_string(), nullable(Schema::Json)),
+        ]);
+        let fields: Vec<models::FieldName> = ["id", "ratio", "day", "tags", "data"]
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        let bytes = [
+            // first row
+            long(1),
+            long(9_007_199_254_740_993),
+            0.25f64.to_le_bytes().to_vec(),
+            long(11_016),
+            long(2),
+            string("a"),
+            string("b"),
+            long(0),
+            long(1),
+            string(r#"{"a":1}"#),
+            // second row
+            long(0),
+            f64::NAN.to_le_bytes().to_vec(),
+            long(-1),
+            long(0),
+            long(0),
+        ]
+        .concat();
+
+        let rows = decode_rows(&fields, &schema, &bytes).unwrap();
+        assert_eq!(
+            Value::Array(rows.into_iter().map(Value::Object).collect()),
+            json!([
+                {
+                    "id": 9_007_199_254_740_993_i64,
+                    "ratio": 0.25,
+                    "day": "2000-02-29",
+                    "tags": ["a", "b"],
+                    "data": { "a": 1 }
+                },
+                {
+                    "id": null,
+                    "ratio": "NaN",
+                    "day": "1969-12-31",
+                    "tags": [],
+                    "data": null
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn truncated_rows_are_rejected() {
+        let schema = Schema::Record(vec![("name".to_string(), Schema::String)]);
+        let fields: Vec<models::FieldName> = vec!["name".into()];
+        let bytes = [long(10), b"abc".to_vec()].concat();
+        assert!(decode_rows(&fields, &schema, &bytes).is_err());
+    }
+
+    #[test]
+    fn decimals_are_formatted() {
+        // 12.5 with a scale of 9 is 12_500_000_000.
+        let bytes = 12_500_000_000i64.to_be_bytes();
+        assert_eq!(format_decimal(&bytes, 9), "12.5");
+        let bytes = (-12_500_000_000i64).to_be_bytes();
+        assert_eq!(format_decimal(&bytes, 9), "-12.5");
+        assert_eq!(format_decimal(&[0x00], 9), "0");
+        assert_eq!(format_decimal(&[0x05], 9), "0.000000005");
+        assert_eq!(format_decimal(&[0x01, 0x00], 0), "256");
+    }
+}
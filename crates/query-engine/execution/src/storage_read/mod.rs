@@ -0,0 +1,311 @@
+//! Read the results of a query through the BigQuery Storage Read API.
+//!
+//! The Storage Read API streams the rows of a table over gRPC, split into several streams which
+//! can be read in parallel. It is much faster than paging through large results with
+//! `getQueryResults`. We read the (temporary) destination table of a query job in the Avro
+//! format, and decode the rows according to the Avro schema of the read session.
+//! See <https://cloud.google.com/bigquery/docs/reference/storage>.
+
+mod avro;
+mod proto;
+
+use gcp_bigquery_client::model::table_reference::TableReference;
+use ndc_models as models;
+use serde_json::{Map, Value};
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, ClientTlsConfig};
+use yup_oauth2::authenticator::Authenticator;
+use yup_oauth2::hyper::client::HttpConnector;
+use yup_oauth2::hyper_rustls::HttpsConnector;
+
+const ENDPOINT: &str = "https://bigquerystorage.googleapis.com";
+const DOMAIN: &str = "bigquerystorage.googleapis.com";
+const SCOPE: &str = "https://www.googleapis.com/auth/bigquery";
+
+/// When to read the results of queries through the Storage Read API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Always page through the results with `getQueryResults`.
+    #[default]
+    Disabled,
+    /// Use the Storage Read API when a query returns more rows than the threshold.
+    Automatic { row_threshold: u64 },
+    /// Use the Storage Read API whenever the results do not fit in the first page.
+    Always,
+}
+
+/// Settings which control when and how the Storage Read API is used.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub mode: Mode,
+    /// The maximum number of streams to read in parallel.
+    pub max_streams: u32,
+}
+
+impl Settings {
+    /// Whether to read the results of a query through the Storage Read API, given the total
+    /// number of rows it returned.
+    pub(crate) fn should_read(&self, total_rows: u64) -> bool {
+        match self.mode {
+            Mode::Disabled => false,
+            Mode::Automatic { row_threshold } => total_rows > row_threshold,
+            Mode::Always => true,
+        }
+    }
+
+    /// The maximum number of streams to read the results of a query from. Rows are only read
+    /// in order from a single stream, so ordered results are read from one.
+    pub(crate) fn max_streams(&self, ordered: bool) -> u32 {
+        if ordered {
+            1
+        } else {
+            self.max_streams
+        }
+    }
+}
+
+/// Errors from the Storage Read API.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unable to create the Storage Read API authenticator: {0}")]
+    Authenticator(std::io::Error),
+    #[error("unable to authenticate to the Storage Read API: {0}")]
+    Authentication(yup_oauth2::Error),
+    #[error("unable to connect to the Storage Read API: {0}")]
+    Transport(tonic::transport::Error),
+    #[error("Storage Read API request failed: {0}")]
+    Status(tonic::Status),
+    #[error("invalid request metadata: {0}")]
+    InvalidMetadata(String),
+}
+
+/// A client for the Storage Read API.
+#[derive(Clone)]
+pub struct Client {
+    channel: Channel,
+    authenticator: Authenticator<HttpsConnector<HttpConnector>>,
+}
+
+impl Client {
+    /// Create a client authenticated with a service account key. The connection is only
+    /// established when the client is first used.
+    pub async fn new(service_account_key: yup_oauth2::ServiceAccountKey) -> Result<Client, Error> {
+        let authenticator = yup_oauth2::ServiceAccountAuthenticator::builder(service_account_key)
+            .build()
+            .await
+            .map_err(Error::Authenticator)?;
+        let channel = Channel::from_static(ENDPOINT)
+            .tls_config(ClientTlsConfig::new().domain_name(DOMAIN))
+            .map_err(Error::Transport)?
+            .connect_lazy();
+        Ok(Client {
+            channel,
+            authenticator,
+        })
+    }
+
+    /// Read every row of a table, decoding the rows into objects whose keys are the requested
+    /// fields. The table must have one column for each field, in the same order.
+    ///
+    /// Rows are read from up to `max_streams` streams in parallel. The order of the rows is
+    /// only preserved when reading a single stream.
+    pub(crate) async fn read_table(
+        &self,
+        project_id: &str,
+        table: &TableReference,
+        fields: &[models::FieldName],
+        max_streams: u32,
+    ) -> Result<Vec<Map<String, Value>>, crate::error::Error> {
+        let table = format!(
+            "projects/{}/datasets/{}/tables/{}",
+            table.project_id, table.dataset_id, table.table_id
+        );
+        let request = proto::CreateReadSessionRequest {
+            parent: format!("projects/{project_id}"),
+            read_session: Some(proto::ReadSession {
+                data_format: proto::DataFormat::Avro.into(),
+                table: table.clone(),
+                ..Default::default()
+            }),
+            max_stream_count: i32::try_from(max_streams).unwrap_or(i32::MAX),
+        };
+        let session: proto::ReadSession = self
+            .request(
+                proto::CREATE_READ_SESSION,
+                request,
+                &format!("read_session.table={table}"),
+            )
+            .await?;
+
+        // an empty table has no streams and no schema.
+        if session.streams.is_empty() {
+            return Ok(vec![]);
+        }
+        let schema = avro::Schema::parse(
+            &session
+                .avro_schema
+                .ok_or_else(|| {
+                    crate::error::Error::UnexpectedResult(
+                        "the read session has no Avro schema".to_string(),
+                    )
+                })?
+                .schema,
+        )?;
+
+        let streams = session
+            .streams
+            .iter()
+            .map(|stream| self.read_stream(&stream.name, fields, &schema));
+        let rows = futures::future::try_join_all(streams).await?;
+        Ok(rows.into_iter().flatten().collect())
+    }
+
+    /// Read every row of a stream.
+    async fn read_stream(
+        &self,
+        stream: &str,
+        fields: &[models::FieldName],
+        schema: &avro::Schema,
+    ) -> Result<Vec<Map<String, Value>>, crate::error::Error> {
+        let request = proto::ReadRowsRequest {
+            read_stream: stream.to_string(),
+            offset: 0,
+        };
+        let mut grpc = tonic::client::Grpc::new(self.channel.clone());
+        grpc.ready()
+            .await
+            .map_err(storage_read_error(Error::Transport))?;
+        let mut responses = grpc
+            .server_streaming(
+                self.authorize(request, &format!("read_stream={stream}"))
+                    .await?,
+                PathAndQuery::from_static(proto::READ_ROWS),
+                ProstCodec::<proto::ReadRowsRequest, proto::ReadRowsResponse>::default(),
+            )
+            .await
+            .map_err(storage_read_error(Error::Status))?
+            .into_inner();
+
+        let mut rows = vec![];
+        while let Some(response) = responses
+            .message()
+            .await
+            .map_err(storage_read_error(Error::Status))?
+        {
+            if let Some(avro_rows) = response.avro_rows {
+                rows.extend(avro::decode_rows(
+                    fields,
+                    schema,
+                    &avro_rows.serialized_binary_rows,
+                )?);
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Send a unary request.
+    async fn request<Request, Response>(
+        &self,
+        path: &'static str,
+        request: Request,
+        routing: &str,
+    ) -> Result<Response, crate::error::Error>
+    where
+        Request: prost::Message + Send + Sync + 'static,
+        Response: prost::Message + Default + Send + Sync + 'static,
+    {
+        let mut grpc = tonic::client::Grpc::new(self.channel.clone());
+        grpc.ready()
+            .await
+            .map_err(storage_read_error(Error::Transport))?;
+        let response = grpc
+            .unary(
+                self.authorize(request, routing).await?,
+                PathAndQuery::from_static(path),
+                ProstCodec::<Request, Response>::default(),
+            )
+            .await
+            .map_err(storage_read_error(Error::Status))?;
+        Ok(response.into_inner())
+    }
+
+    /// Wrap a message in a request carrying an access token, and the routing header Google
+    /// uses to route requests to the right region.
+    async fn authorize<T>(
+        &self,
+        message: T,
+        routing: &str,
+    ) -> Result<tonic::Request<T>, crate::error::Error> {
+        let token = self
+            .authenticator
+            .token(&[SCOPE])
+            .await
+            .map_err(storage_read_error(Error::Authentication))?;
+        let token = token.token().ok_or_else(|| {
+            crate::error::Error::StorageRead(Error::InvalidMetadata(
+                "the access token is empty".to_string(),
+            ))
+        })?;
+
+        let mut request = tonic::Request::new(message);
+        let metadata = request.metadata_mut();
+        for (key, value) in [
+            ("authorization", format!("Bearer {token}")),
+            ("x-goog-request-params", routing.to_string()),
+        ] {
+            let value = value.parse().map_err(|_| {
+                crate::error::Error::StorageRead(Error::InvalidMetadata(key.to_string()))
+            })?;
+            metadata.insert(key, value);
+        }
+        Ok(request)
+    }
+}
+
+fn storage_read_error<E>(error: impl Fn(E) -> Error) -> impl Fn(E) -> crate::error::Error {
+    move |err| crate::error::Error::StorageRead(error(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(mode: Mode) -> Settings {
+        Settings {
+            mode,
+            max_streams: 4,
+        }
+    }
+
+    #[test]
+    fn never_reads_when_disabled() {
+        let settings = settings(Mode::Disabled);
+        assert!(!settings.should_read(0));
+        assert!(!settings.should_read(u64::MAX));
+    }
+
+    #[test]
+    fn reads_results_with_more_rows_than_the_threshold() {
+        let settings = settings(Mode::Automatic {
+            row_threshold: 1000,
+        });
+        assert!(!settings.should_read(0));
+        assert!(!settings.should_read(1000));
+        assert!(settings.should_read(1001));
+    }
+
+    #[test]
+    fn always_reads_when_always_enabled() {
+        let settings = settings(Mode::Always);
+        assert!(settings.should_read(0));
+        assert!(settings.should_read(u64::MAX));
+    }
+
+    #[test]
+    fn reads_ordered_results_from_a_single_stream() {
+        let settings = settings(Mode::Always);
+        assert_eq!(settings.max_streams(false), 4);
+        assert_eq!(settings.max_streams(true), 1);
+    }
+}
@@ -0,0 +1,87 @@
+//! The messages of the `google.cloud.bigquery.storage.v1.BigQueryRead` service we use.
+//!
+//! These are a subset of the messages defined in
+//! <https://github.com/googleapis/googleapis/tree/master/google/cloud/bigquery/storage/v1>,
+//! written by hand so we do not need to generate code from the protobuf definitions. Fields we
+//! do not declare are skipped when decoding.
+
+/// The path of the `CreateReadSession` method.
+pub const CREATE_READ_SESSION: &str =
+    "/google.cloud.bigquery.storage.v1.BigQueryRead/CreateReadSession";
+
+/// The path of the `ReadRows` method.
+pub const READ_ROWS: &str = "/google.cloud.bigquery.storage.v1.BigQueryRead/ReadRows";
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CreateReadSessionRequest {
+    /// The project billed for the read session, as `projects/{project_id}`.
+    #[prost(string, tag = "1")]
+    pub parent: String,
+    #[prost(message, optional, tag = "2")]
+    pub read_session: Option<ReadSession>,
+    /// The maximum number of streams of the session. The server may return fewer streams.
+    #[prost(int32, tag = "3")]
+    pub max_stream_count: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReadSession {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(enumeration = "DataFormat", tag = "3")]
+    pub data_format: i32,
+    /// Part of the `schema` oneof, which also holds the Arrow schema.
+    #[prost(message, optional, tag = "4")]
+    pub avro_schema: Option<AvroSchema>,
+    /// The table to read, as `projects/{project_id}/datasets/{dataset_id}/tables/{table_id}`.
+    #[prost(string, tag = "6")]
+    pub table: String,
+    #[prost(message, repeated, tag = "10")]
+    pub streams: Vec<ReadStream>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum DataFormat {
+    Unspecified = 0,
+    Avro = 1,
+    Arrow = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReadStream {
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AvroSchema {
+    /// The Avro schema of the rows, as JSON.
+    #[prost(string, tag = "1")]
+    pub schema: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReadRowsRequest {
+    #[prost(string, tag = "1")]
+    pub read_stream: String,
+    /// The offset of the first row to read in the stream.
+    #[prost(int64, tag = "2")]
+    pub offset: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReadRowsResponse {
+    /// Part of the `rows` oneof, which also holds Arrow record batches.
+    #[prost(message, optional, tag = "3")]
+    pub avro_rows: Option<AvroRows>,
+    #[prost(int64, tag = "6")]
+    pub row_count: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AvroRows {
+    /// Rows encoded with the Avro binary encoding, one after the other.
+    #[prost(bytes = "vec", tag = "1")]
+    pub serialized_binary_rows: Vec<u8>,
+}
@@ -0,0 +1,174 @@
+//! Link traces to BigQuery jobs.
+//!
+//! Each BigQuery job runs in a span carrying its id, location, statement type, the number of
+//! bytes it processed, whether it hit the cache, and a hash of its SQL, so that a slow request
+//! can be followed from its trace to the job in the BigQuery console. The SQL itself, and its
+//! parameters, are attached at debug level.
+
+use gcp_bigquery_client::model::job_reference::JobReference;
+use gcp_bigquery_client::model::query_parameter::QueryParameter;
+use gcp_bigquery_client::model::query_request::QueryRequest;
+use gcp_bigquery_client::model::query_response::QueryResponse;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use tracing::field::Empty;
+
+/// The placeholder replacing redacted values.
+const REDACTED: &str = "<redacted>";
+
+/// How the values of query parameters are redacted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParameterRedaction {
+    /// Include the names, types and values of parameters.
+    None,
+    /// Include the names and types of parameters, but not their values.
+    #[default]
+    Values,
+    /// Leave parameters out entirely.
+    All,
+}
+
+/// A span for a BigQuery job running a SQL statement. The fields describing the job are
+/// recorded as they become known.
+pub(crate) fn job_span(sql: &str) -> tracing::Span {
+    tracing::info_span!(
+        "Run BigQuery job",
+        sql_hash = sql_hash(sql),
+        job_id = Empty,
+        location = Empty,
+        statement_type = Empty,
+        bytes_processed = Empty,
+        cache_hit = Empty,
+    )
+}
+
+/// Attach the SQL and parameters of a request to the current span, at debug level.
+pub(crate) fn trace_sql(query_request: &QueryRequest, redaction: ParameterRedaction) {
+    let parameters = query_request
+        .query_parameters
+        .as_deref()
+        .and_then(|parameters| describe_parameters(parameters, redaction));
+    match parameters {
+        None => tracing::debug!(sql = %query_request.query, "SQL statement"),
+        Some(parameters) => tracing::debug!(
+            sql = %query_request.query,
+            parameters = %parameters,
+            "SQL statement"
+        ),
+    }
+}
+
+/// Record the reference of a job in the current span.
+pub(crate) fn record_job_reference(job_reference: Option<&JobReference>) {
+    let span = tracing::Span::current();
+    if let Some(job_reference) = job_reference {
+        if let Some(job_id) = &job_reference.job_id {
+            span.record("job_id", job_id.as_str());
+        }
+        if let Some(location) = &job_reference.location {
+            span.record("location", location.as_str());
+        }
+    }
+}
+
+/// Record what the response of a completed job tells us about it in the current span.
+pub(crate) fn record_job_response(response: &QueryResponse) {
+    let span = tracing::Span::current();
+    record_job_reference(response.job_reference.as_ref());
+    if let Some(bytes_processed) = response
+        .total_bytes_processed
+        .as_deref()
+        .and_then(|bytes| bytes.parse::<u64>().ok())
+    {
+        span.record("bytes_processed", bytes_processed);
+    }
+    if let Some(cache_hit) = response.cache_hit {
+        span.record("cache_hit", cache_hit);
+    }
+}
+
+/// Describe query parameters as a JSON array of their names, types and (unless redacted)
+/// values. Returns nothing if parameters are redacted entirely.
+pub fn describe_parameters(
+    parameters: &[QueryParameter],
+    redaction: ParameterRedaction,
+) -> Option<Value> {
+    if redaction == ParameterRedaction::All {
+        return None;
+    }
+    Some(Value::Array(
+        parameters
+            .iter()
+            .map(|parameter| {
+                let value = match redaction {
+                    ParameterRedaction::None => parameter
+                        .parameter_value
+                        .as_ref()
+                        .and_then(|value| value.value.clone())
+                        .map_or(Value::Null, Value::String),
+                    ParameterRedaction::Values | ParameterRedaction::All => {
+                        Value::String(REDACTED.to_string())
+                    }
+                };
+                json!({
+                    "name": parameter.name,
+                    "type": parameter
+                        .parameter_type
+                        .as_ref()
+                        .map(|parameter_type| parameter_type.r#type.as_str()),
+                    "value": value,
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// A hash identifying a SQL statement, hex-encoded.
+pub fn sql_hash(sql: &str) -> String {
+    Sha256::digest(sql.as_bytes())
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gcp_bigquery_client::model::query_parameter_type::QueryParameterType;
+    use gcp_bigquery_client::model::query_parameter_value::QueryParameterValue;
+
+    fn parameter() -> QueryParameter {
+        QueryParameter {
+            name: Some("param1".to_string()),
+            parameter_type: Some(QueryParameterType {
+                array_type: None,
+                struct_types: None,
+                r#type: "STRING".to_string(),
+            }),
+            parameter_value: Some(QueryParameterValue {
+                array_values: None,
+                struct_values: None,
+                value: Some("secret".to_string()),
+            }),
+        }
+    }
+
+    #[test]
+    fn describes_parameters_with_their_values_redacted() {
+        assert_eq!(
+            describe_parameters(&[parameter()], ParameterRedaction::None),
+            Some(json!([{ "name": "param1", "type": "STRING", "value": "secret" }]))
+        );
+        assert_eq!(
+            describe_parameters(&[parameter()], ParameterRedaction::Values),
+            Some(json!([{ "name": "param1", "type": "STRING", "value": "<redacted>" }]))
+        );
+        assert_eq!(
+            describe_parameters(&[parameter()], ParameterRedaction::All),
+            None
+        );
+    }
+}
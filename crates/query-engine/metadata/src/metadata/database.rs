@@ -117,6 +117,24 @@ pub struct TableInfo {
     pub foreign_relations: ForeignRelations,
 
     pub description: Option<String>,
+
+    /// The column the table is partitioned by, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition_column: Option<String>,
+    /// Whether queries against this table must filter on the partition column.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub require_partition_filter: bool,
+    /// The columns the table is clustered by, in clustering order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clustering_columns: Vec<String>,
+    /// Whether this is a wildcard table (e.g. `events_*`) over a set of date-sharded tables.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_wildcard: bool,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // serde's skip_serializing_if requires a reference
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 /// Can this column contain null values
@@ -3,10 +3,14 @@
 pub mod database;
 pub mod mutations;
 pub mod native_queries;
+pub mod request_parameters;
+pub mod routines;
 
 // re-export without modules
 pub use database::*;
 pub use native_queries::*;
+pub use request_parameters::*;
+pub use routines::*;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -18,6 +22,10 @@ pub struct Metadata {
     // pub composite_types: CompositeTypes,
     pub native_operations: NativeOperations,
     pub scalar_types: ScalarTypes,
+    #[serde(default, skip_serializing_if = "Routines::is_empty")]
+    pub routines: Routines,
+    #[serde(default, skip_serializing_if = "RequestParameters::is_empty")]
+    pub request_parameters: RequestParameters,
 }
 
 impl Metadata {
@@ -27,6 +35,8 @@ impl Metadata {
             // composite_types: CompositeTypes::empty(),
             native_operations: NativeOperations::empty(),
             scalar_types: ScalarTypes::empty(),
+            routines: Routines::empty(),
+            request_parameters: RequestParameters::empty(),
         }
     }
 }
@@ -58,6 +58,12 @@ pub struct NativeQueryInfo {
     pub arguments: BTreeMap<models::ArgumentName, ReadOnlyColumnInfo>,
 
     pub description: Option<String>,
+
+    /// Whether the Native Query may return different results each time it runs, e.g. because
+    /// it calls `CURRENT_TIMESTAMP()` or `RAND()`. The responses to queries on volatile Native
+    /// Queries are never cached.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub volatile: bool,
 }
 
 /// Information about a native query column.
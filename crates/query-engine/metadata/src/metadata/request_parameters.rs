@@ -0,0 +1,35 @@
+//! Metadata information regarding request parameters: values taken from each query request
+//! and bound as query parameters, so that the SQL we run can depend on the end user.
+
+use ndc_models as models;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Query parameters bound to values taken from each query request, by name.
+/// Native queries can reference them as `{{name}}` or `@name`, for example to only return
+/// the rows the end user is allowed to see. Table collections cannot reference them: a table
+/// is filtered by a request parameter by querying it through a native query.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub struct RequestParameters(pub BTreeMap<String, RequestParameter>);
+
+impl RequestParameters {
+    pub fn empty() -> Self {
+        RequestParameters(BTreeMap::new())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Where the value of a request parameter comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum RequestParameter {
+    /// The value of an argument of the queried collection.
+    Argument(models::ArgumentName),
+    /// The value of a header forwarded by the engine in the `headers` argument of the queried
+    /// collection, an object of header names to values.
+    Header(String),
+}
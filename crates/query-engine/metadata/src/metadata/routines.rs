@@ -0,0 +1,85 @@
+//! Metadata information regarding routines: table functions, scalar functions and procedures.
+
+use super::database::*;
+
+use ndc_models as models;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Metadata information of the routines in the dataset.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Routines {
+    /// Table-valued functions, exposed as collections.
+    #[serde(default)]
+    pub table_functions: BTreeMap<models::CollectionName, TableFunctionInfo>,
+    /// Scalar SQL functions, exposed as functions.
+    #[serde(default)]
+    pub scalar_functions: BTreeMap<models::FunctionName, ScalarFunctionInfo>,
+    /// Stored procedures, exposed as procedures.
+    #[serde(default)]
+    pub procedures: BTreeMap<models::ProcedureName, ProcedureInfo>,
+}
+
+impl Routines {
+    pub fn empty() -> Self {
+        Routines::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table_functions.is_empty()
+            && self.scalar_functions.is_empty()
+            && self.procedures.is_empty()
+    }
+}
+
+/// Information about a table-valued function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TableFunctionInfo {
+    pub schema_name: String,
+    pub function_name: String,
+    /// The arguments of the function, in the order they are passed to it.
+    pub arguments: Vec<RoutineArgument>,
+    /// The columns of the table returned by the function.
+    pub columns: BTreeMap<models::FieldName, ReadOnlyColumnInfo>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Information about a scalar function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScalarFunctionInfo {
+    pub schema_name: String,
+    pub function_name: String,
+    /// The arguments of the function, in the order they are passed to it.
+    pub arguments: Vec<RoutineArgument>,
+    pub return_type: Type,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Information about a stored procedure. Procedures do not declare a result type;
+/// the result set of their final SELECT statement, if any, is returned as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcedureInfo {
+    pub schema_name: String,
+    pub procedure_name: String,
+    /// The arguments of the procedure, in the order they are passed to it.
+    pub arguments: Vec<RoutineArgument>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// An argument of a routine.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutineArgument {
+    pub name: models::ArgumentName,
+    pub r#type: Type,
+    #[serde(default)]
+    pub description: Option<String>,
+}
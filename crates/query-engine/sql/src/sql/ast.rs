@@ -2,6 +2,8 @@
 
 use std::collections::BTreeMap;
 
+use super::string::LiteralEncoding;
+
 /// An EXPLAIN clause
 #[derive(Debug, Clone, PartialEq)]
 pub enum Explain<'a> {
@@ -64,6 +66,14 @@ pub struct Insert {
     pub returning: Returning,
 }
 
+/// A CALL statement, invoking a stored procedure
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub schema: SchemaName,
+    pub procedure: RoutineName,
+    pub arguments: Vec<Expression>,
+}
+
 /// Source from which values would be inserted.
 #[derive(Debug, Clone, PartialEq)]
 pub enum InsertFrom {
@@ -112,32 +122,38 @@ pub enum SelectList {
 /// A FROM clause
 #[derive(Debug, Clone, PartialEq)]
 pub enum From {
-    /// Select from a table reference
+    /// Select from a table reference, optionally as of a point in time
+    /// (`FOR SYSTEM_TIME AS OF <expression>`).
     Table {
         reference: TableReference,
         alias: TableAlias,
+        system_time: Option<Expression>,
     },
     /// Select from a subquery
     Select {
         select: Box<Select>,
         alias: TableAlias,
     },
-    /// Convert a json array of objects to a relation.
-    /// Should probably be of the form `jsonb_to_recordset(cast($1 as json))`
-    JsonbToRecordset {
-        expression: Expression,
+    /// Select from a call to a table-valued function
+    TableFunction {
+        schema: SchemaName,
+        function: RoutineName,
+        arguments: Vec<Expression>,
         alias: TableAlias,
-        columns: Vec<(ColumnAlias, ScalarType)>,
     },
-    JsonbArrayElements {
+    /// Select from an array of structs, whose fields are the columns of the table:
+    /// `UNNEST(<expression>) AS <alias>`.
+    UnnestStructs {
         expression: Expression,
         alias: TableAlias,
-        column: ColumnAlias,
     },
+    /// Select from an array of values, optionally along with their position in the array:
+    /// `UNNEST(<expression>) AS <column> WITH OFFSET AS <offset>`.
     Unnest {
         expression: Expression,
         alias: TableAlias,
         column: ColumnAlias,
+        offset: Option<ColumnAlias>,
     },
 }
 
@@ -259,6 +275,12 @@ pub enum Expression {
         operator: BinaryArrayOperator,
         right: Vec<Expression>,
     },
+    /// A BETWEEN clause, inclusive on both ends
+    Between {
+        expression: Box<Expression>,
+        low: Box<Expression>,
+        high: Box<Expression>,
+    },
     /// An unary operation on a scalar expression
     UnaryOperation {
         expression: Box<Expression>,
@@ -290,10 +312,17 @@ pub enum Expression {
         expression: Box<Expression>,
         r#type: ScalarType,
     },
+    /// Access a field of a JSON object, e.g. `json_expression['field']`
+    JsonSubscript {
+        expression: Box<Expression>,
+        field: Box<Expression>,
+    },
     /// A COUNT clause
     Count(CountType),
     ArrayConstructor(Vec<Expression>),
     CorrelatedSubSelect(Box<Select>),
+    /// Build an array from the rows of a subquery, e.g. `ARRAY(SELECT ...)`
+    ArraySubquery(Box<Select>),
     NestedFieldSelect {
         expression: Box<Expression>,
         nested_field: NestedField,
@@ -338,11 +367,18 @@ pub enum BinaryArrayOperator {
 pub enum Function {
     Coalesce,
     JsonAgg,
-    JsonbPopulateRecord,
     ArrayAgg,
+    ParseJson,
+    JsonValue,
+    JsonQueryArray,
     Unnest,
     Unknown(String),
     SafeOffSet(String),
+    /// A user defined function
+    Routine {
+        schema: SchemaName,
+        name: RoutineName,
+    },
 }
 
 /// COUNT clause
@@ -366,6 +402,11 @@ pub enum Value {
     JsonValue(serde_json::Value),
     EmptyJsonArray,
     Variable(String),
+    /// A literal lifted out of the request, bound when the query runs.
+    Literal {
+        index: usize,
+        encoding: LiteralEncoding,
+    },
 }
 
 /// Scalar type
@@ -396,6 +437,10 @@ pub struct SchemaName(pub String);
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TableName(pub String);
 
+/// A database routine (user defined function) name
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RoutineName(pub String);
+
 /// A reference to a table. Used when we want to query it,
 /// for example in a FROM clause.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -1,92 +1,98 @@
 //! Convert a SQL AST to a low-level SQL string.
 
 use super::ast::*;
+use super::dialect::{ArrayFunction, Dialect, Error, JsonFunction, ScalarLiteral};
 use super::helpers;
 use super::string::*;
 
 // Convert to SQL strings
 
 impl With {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         if self.common_table_expressions.is_empty() {
         } else {
             sql.append_syntax("WITH ");
 
             let ctes = &self.common_table_expressions;
             for (index, cte) in ctes.iter().enumerate() {
-                cte.to_sql(sql);
+                cte.to_sql(sql, dialect)?;
                 if index < (ctes.len() - 1) {
                     sql.append_syntax(", ");
                 }
             }
         }
+        Ok(())
     }
 }
 
 impl CommonTableExpression {
-    pub fn to_sql(&self, sql: &mut SQL) {
-        self.alias.to_sql(sql);
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        self.alias.to_sql(sql, dialect)?;
         match &self.column_names {
             None => {}
             Some(names) => {
                 sql.append_syntax("(");
                 for name in names {
-                    name.to_sql(sql);
+                    name.to_sql(sql, dialect)?;
                 }
                 sql.append_syntax(")");
             }
         }
 
         sql.append_syntax(" AS (");
-        self.select.to_sql(sql);
+        self.select.to_sql(sql, dialect)?;
         // the newline is important because a native query might end with a comment
         sql.append_syntax("\n)");
+        Ok(())
     }
 }
 
 impl CTExpr {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
             CTExpr::Select(select) => {
-                select.to_sql(sql);
+                select.to_sql(sql, dialect)?;
             }
             CTExpr::RawSql(raw_vec) => {
                 for item in raw_vec {
-                    item.to_sql(sql);
+                    item.to_sql(sql, dialect)?;
                 }
-            } // CTExpr::Delete(delete) => delete.to_sql(sql),
-              // CTExpr::Insert(insert) => insert.to_sql(sql),
-              // CTExpr::Update(update) => update.to_sql(sql),
+            } // CTExpr::Delete(delete) => delete.to_sql(sql, dialect)?,
+              // CTExpr::Insert(insert) => insert.to_sql(sql, dialect)?,
+              // CTExpr::Update(update) => update.to_sql(sql, dialect)?,
         }
+        Ok(())
     }
 }
 
 impl RawSql {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
             RawSql::RawText(text) => sql.append_syntax(text),
-            RawSql::Expression(exp) => exp.to_sql(sql),
+            RawSql::Expression(exp) => exp.to_sql(sql, dialect)?,
         }
+        Ok(())
     }
 }
 
 impl Explain<'_> {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         sql.append_syntax("EXPLAIN ");
         match self {
-            Explain::Select(select) => select.to_sql(sql),
+            Explain::Select(select) => select.to_sql(sql, dialect)?,
         }
+        Ok(())
     }
 }
 
 impl SelectList {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
             SelectList::SelectList(select_list) => {
                 for (index, (col, expr)) in select_list.iter().enumerate() {
-                    expr.to_sql(sql);
+                    expr.to_sql(sql, dialect)?;
                     sql.append_syntax(" AS ");
-                    col.to_sql(sql);
+                    col.to_sql(sql, dialect)?;
                     if index < (select_list.len() - 1) {
                         sql.append_syntax(", ");
                     }
@@ -96,55 +102,57 @@ impl SelectList {
                 sql.append_syntax("*");
             }
             SelectList::SelectStarFrom(table_reference) => {
-                table_reference.to_sql(sql);
+                table_reference.to_sql(sql, dialect)?;
                 sql.append_syntax(".*");
             }
             SelectList::Select1 => {
                 sql.append_syntax("1");
             }
         }
+        Ok(())
     }
 }
 
 impl Select {
-    pub fn to_sql(&self, sql: &mut SQL) {
-        self.with.to_sql(sql);
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        self.with.to_sql(sql, dialect)?;
 
         sql.append_syntax("SELECT ");
 
-        self.select_list.to_sql(sql);
+        self.select_list.to_sql(sql, dialect)?;
 
         sql.append_syntax(" ");
 
         match &self.from {
-            Some(from) => from.to_sql(sql),
+            Some(from) => from.to_sql(sql, dialect)?,
             None => (),
         }
 
         for join in &self.joins {
-            join.to_sql(sql);
+            join.to_sql(sql, dialect)?;
         }
 
-        self.where_.to_sql(sql);
+        self.where_.to_sql(sql, dialect)?;
 
-        self.order_by.to_sql(sql);
+        self.order_by.to_sql(sql, dialect)?;
 
-        self.limit.to_sql(sql);
+        self.limit.to_sql(sql, dialect)?;
+        Ok(())
     }
 }
 
 impl Insert {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         sql.append_syntax("INSERT INTO ");
 
-        self.schema.to_sql(sql);
+        self.schema.to_sql(sql, dialect)?;
         sql.append_syntax(".");
-        self.table.to_sql(sql);
+        self.table.to_sql(sql, dialect)?;
 
         if let Some(columns) = &self.columns {
             sql.append_syntax("(");
             for (index, column_name) in columns.iter().enumerate() {
-                column_name.to_sql(sql);
+                column_name.to_sql(sql, dialect)?;
                 if index < (columns.len() - 1) {
                     sql.append_syntax(", ");
                 }
@@ -154,25 +162,46 @@ impl Insert {
 
         sql.append_syntax(" ");
 
-        self.from.to_sql(sql);
+        self.from.to_sql(sql, dialect)?;
 
         sql.append_syntax(" ");
 
-        self.returning.to_sql(sql);
+        self.returning.to_sql(sql, dialect)?;
+        Ok(())
+    }
+}
+
+impl Call {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        sql.append_syntax("CALL ");
+
+        self.schema.to_sql(sql, dialect)?;
+        sql.append_syntax(".");
+        self.procedure.to_sql(sql, dialect)?;
+
+        sql.append_syntax("(");
+        for (index, argument) in self.arguments.iter().enumerate() {
+            argument.to_sql(sql, dialect)?;
+            if index < (self.arguments.len() - 1) {
+                sql.append_syntax(", ");
+            }
+        }
+        sql.append_syntax(")");
+        Ok(())
     }
 }
 
 impl InsertFrom {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
-            InsertFrom::Select(select) => select.to_sql(sql),
+            InsertFrom::Select(select) => select.to_sql(sql, dialect)?,
             InsertFrom::Values(values) => {
                 sql.append_syntax("VALUES ");
 
                 for (index, object) in values.iter().enumerate() {
                     sql.append_syntax("(");
                     for (index, value) in object.iter().enumerate() {
-                        value.to_sql(sql);
+                        value.to_sql(sql, dialect)?;
                         if index < (object.len() - 1) {
                             sql.append_syntax(", ");
                         }
@@ -185,11 +214,12 @@ impl InsertFrom {
                 }
             }
         }
+        Ok(())
     }
 }
 
 impl Delete {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         let Delete {
             from,
             where_,
@@ -198,33 +228,34 @@ impl Delete {
 
         sql.append_syntax("DELETE ");
 
-        from.to_sql(sql);
+        from.to_sql(sql, dialect)?;
 
         sql.append_syntax(" ");
 
-        where_.to_sql(sql);
+        where_.to_sql(sql, dialect)?;
 
         sql.append_syntax(" ");
 
-        returning.to_sql(sql);
+        returning.to_sql(sql, dialect)?;
+        Ok(())
     }
 }
 
 impl Update {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         sql.append_syntax("UPDATE ");
 
-        self.schema.to_sql(sql);
+        self.schema.to_sql(sql, dialect)?;
         sql.append_syntax(".");
-        self.table.to_sql(sql);
+        self.table.to_sql(sql, dialect)?;
 
         sql.append_syntax(" SET ");
 
         // Set values to columns
         for (index, (column, expression)) in self.set.iter().enumerate() {
-            column.to_sql(sql);
+            column.to_sql(sql, dialect)?;
             sql.append_syntax(" = ");
-            expression.to_sql(sql);
+            expression.to_sql(sql, dialect)?;
             if index < (self.set.len() - 1) {
                 sql.append_syntax(", ");
             }
@@ -232,181 +263,218 @@ impl Update {
 
         sql.append_syntax(" ");
 
-        self.where_.to_sql(sql);
+        self.where_.to_sql(sql, dialect)?;
 
         sql.append_syntax(" ");
 
-        self.returning.to_sql(sql);
+        self.returning.to_sql(sql, dialect)?;
+        Ok(())
     }
 }
 
 impl Returning {
-    pub fn to_sql(&self, sql: &mut SQL) {
-        sql.append_syntax("RETURNING ");
-        self.0.to_sql(sql);
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        sql.append_syntax(dialect.returning_keyword()?);
+        sql.append_syntax(" ");
+        self.0.to_sql(sql, dialect)?;
+        Ok(())
     }
 }
 
 impl From {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         sql.append_syntax("FROM ");
         match &self {
-            From::Table { reference, alias } => {
-                reference.to_sql(sql);
+            From::Table {
+                reference,
+                alias,
+                system_time,
+            } => {
+                reference.to_sql(sql, dialect)?;
                 sql.append_syntax(" AS ");
-                alias.to_sql(sql);
+                alias.to_sql(sql, dialect)?;
+                if let Some(system_time) = system_time {
+                    sql.append_syntax(" FOR SYSTEM_TIME AS OF ");
+                    system_time.to_sql(sql, dialect)?;
+                }
             }
             From::Select { select, alias } => {
                 sql.append_syntax("(");
-                select.to_sql(sql);
+                select.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
                 sql.append_syntax(" AS ");
-                alias.to_sql(sql);
+                alias.to_sql(sql, dialect)?;
             }
-            From::JsonbToRecordset {
-                expression,
+            From::TableFunction {
+                schema,
+                function,
+                arguments,
                 alias,
-                columns,
             } => {
-                sql.append_syntax("jsonb_to_recordset");
-                sql.append_syntax("(");
-                expression.to_sql(sql);
-                sql.append_syntax(")");
-                sql.append_syntax(" AS ");
-                alias.to_sql(sql);
+                schema.to_sql(sql, dialect)?;
+                sql.append_syntax(".");
+                function.to_sql(sql, dialect)?;
                 sql.append_syntax("(");
-
-                for (index, (column, scalar_type)) in columns.iter().enumerate() {
-                    column.to_sql(sql);
-                    sql.append_syntax(" ");
-                    scalar_type.to_sql(sql);
-                    if index < (columns.len() - 1) {
+                for (index, argument) in arguments.iter().enumerate() {
+                    argument.to_sql(sql, dialect)?;
+                    if index < (arguments.len() - 1) {
                         sql.append_syntax(", ");
                     }
                 }
                 sql.append_syntax(")");
+                sql.append_syntax(" AS ");
+                alias.to_sql(sql, dialect)?;
             }
-            From::JsonbArrayElements {
-                expression,
-                alias,
-                column,
-            } => {
-                sql.append_syntax("jsonb_array_elements");
+            From::UnnestStructs { expression, alias } => {
+                sql.append_syntax(dialect.array_function(ArrayFunction::Unnest)?);
                 sql.append_syntax("(");
-                expression.to_sql(sql);
+                expression.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
                 sql.append_syntax(" AS ");
-                alias.to_sql(sql);
-                sql.append_syntax("(");
-                column.to_sql(sql);
-                sql.append_syntax(")");
+                alias.to_sql(sql, dialect)?;
             }
             From::Unnest {
                 expression,
                 alias,
                 column,
+                offset,
             } => {
-                sql.append_syntax("UNNEST");
+                // an unnested array is a table of values, which we name so we can refer
+                // to the values as `<alias>.<column>`, and to their position as `<alias>.<offset>`.
+                sql.append_syntax("(");
+                sql.append_syntax("SELECT ");
+                column.to_sql(sql, dialect)?;
+                if let Some(offset) = offset {
+                    sql.append_syntax(", ");
+                    offset.to_sql(sql, dialect)?;
+                }
+                sql.append_syntax(" FROM ");
+                sql.append_syntax(dialect.array_function(ArrayFunction::Unnest)?);
                 sql.append_syntax("(");
-                expression.to_sql(sql);
+                expression.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
                 sql.append_syntax(" AS ");
-                alias.to_sql(sql);
-                sql.append_syntax("(");
-                column.to_sql(sql);
+                column.to_sql(sql, dialect)?;
+                if let Some(offset) = offset {
+                    sql.append_syntax(" WITH OFFSET AS ");
+                    offset.to_sql(sql, dialect)?;
+                }
                 sql.append_syntax(")");
+                sql.append_syntax(" AS ");
+                alias.to_sql(sql, dialect)?;
             }
         }
+        Ok(())
     }
 }
 
 // todo(PY): correct the join syntax for each join type
 impl Join {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
             Join::LeftOuterJoin(LeftOuterJoin { select, alias, on }) => {
                 sql.append_syntax(" LEFT OUTER JOIN ");
                 sql.append_syntax("(");
-                select.to_sql(sql);
+                select.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
                 sql.append_syntax(" AS ");
-                alias.to_sql(sql);
+                alias.to_sql(sql, dialect)?;
                 sql.append_syntax(" ON (");
-                on.to_sql(sql);
+                on.to_sql(sql, dialect)?;
                 sql.append_syntax(") ");
             }
             Join::InnerJoin(InnerJoin { select, alias }) => {
                 sql.append_syntax(" INNER JOIN ");
                 sql.append_syntax("(");
-                select.to_sql(sql);
+                select.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
                 sql.append_syntax(" AS ");
-                alias.to_sql(sql);
-                sql.append_syntax(" ON ('true') ");
+                alias.to_sql(sql, dialect)?;
+                sql.append_syntax(" ON (");
+                sql.append_syntax(dialect.true_join_condition());
+                sql.append_syntax(") ");
             }
             Join::CrossJoin(CrossJoin { select, alias }) => {
                 sql.append_syntax(" CROSS JOIN ");
                 sql.append_syntax("(");
-                select.to_sql(sql);
+                select.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
                 sql.append_syntax(" AS ");
-                alias.to_sql(sql);
+                alias.to_sql(sql, dialect)?;
             }
             Join::FullOuterJoin(join) => {
                 sql.append_syntax(" FULL OUTER JOIN ");
                 sql.append_syntax("(");
-                join.select.to_sql(sql);
+                join.select.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
                 sql.append_syntax(" AS ");
-                join.alias.to_sql(sql);
-                sql.append_syntax(" ON ('true') ");
+                join.alias.to_sql(sql, dialect)?;
+                sql.append_syntax(" ON (");
+                sql.append_syntax(dialect.true_join_condition());
+                sql.append_syntax(") ");
             }
         }
+        Ok(())
     }
 }
 
 impl Where {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         let Where(expression) = self;
         if *expression != helpers::true_expr() {
             sql.append_syntax(" WHERE ");
-            expression.to_sql(sql);
+            expression.to_sql(sql, dialect)?;
         }
+        Ok(())
     }
 }
 
 // scalars
 impl Expression {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match &self {
-            Expression::ColumnReference(column_reference) => column_reference.to_sql(sql),
-            Expression::TableReference(table_reference) => table_reference.to_sql(sql),
-            Expression::Value(value) => value.to_sql(sql),
+            Expression::ColumnReference(column_reference) => {
+                column_reference.to_sql(sql, dialect)?
+            }
+            Expression::TableReference(table_reference) => table_reference.to_sql(sql, dialect)?,
+            Expression::Value(value) => value.to_sql(sql, dialect)?,
             Expression::Cast { expression, r#type } => {
                 sql.append_syntax("cast");
                 sql.append_syntax("(");
-                expression.to_sql(sql);
+                expression.to_sql(sql, dialect)?;
                 sql.append_syntax(" as ");
-                r#type.to_sql(sql);
+                r#type.to_sql(sql, dialect)?;
+                sql.append_syntax(")");
+            }
+            Expression::JsonSubscript { expression, field } => {
+                expression.to_sql(sql, dialect)?;
+                sql.append_syntax("[");
+                field.to_sql(sql, dialect)?;
+                sql.append_syntax("]");
+            }
+            Expression::ArraySubquery(select) => {
+                sql.append_syntax(dialect.array_function(ArrayFunction::FromSubquery)?);
+                sql.append_syntax("(");
+                select.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
             }
             Expression::And { left, right } => {
                 sql.append_syntax("(");
-                left.to_sql(sql);
+                left.to_sql(sql, dialect)?;
                 sql.append_syntax(" AND ");
-                right.to_sql(sql);
+                right.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
             }
             Expression::Or { left, right } => {
                 sql.append_syntax("(");
-                left.to_sql(sql);
+                left.to_sql(sql, dialect)?;
                 sql.append_syntax(" OR ");
-                right.to_sql(sql);
+                right.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
             }
             Expression::Not(expr) => {
                 sql.append_syntax("NOT ");
-                expr.to_sql(sql);
+                expr.to_sql(sql, dialect)?;
             }
             Expression::BinaryOperation {
                 left,
@@ -414,9 +482,9 @@ impl Expression {
                 right,
             } => {
                 sql.append_syntax("(");
-                left.to_sql(sql);
-                operator.to_sql(sql);
-                right.to_sql(sql);
+                left.to_sql(sql, dialect)?;
+                operator.to_sql(sql, dialect)?;
+                right.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
             }
             Expression::BinaryArrayOperation {
@@ -426,11 +494,11 @@ impl Expression {
             } => {
                 sql.append_syntax("(");
                 {
-                    left.to_sql(sql);
-                    operator.to_sql(sql);
+                    left.to_sql(sql, dialect)?;
+                    operator.to_sql(sql, dialect)?;
                     sql.append_syntax("(");
                     for (index, item) in right.iter().enumerate() {
-                        item.to_sql(sql);
+                        item.to_sql(sql, dialect)?;
                         if index < (right.len() - 1) {
                             sql.append_syntax(", ");
                         }
@@ -439,20 +507,33 @@ impl Expression {
                 }
                 sql.append_syntax(")");
             }
+            Expression::Between {
+                expression,
+                low,
+                high,
+            } => {
+                sql.append_syntax("(");
+                expression.to_sql(sql, dialect)?;
+                sql.append_syntax(" BETWEEN ");
+                low.to_sql(sql, dialect)?;
+                sql.append_syntax(" AND ");
+                high.to_sql(sql, dialect)?;
+                sql.append_syntax(")");
+            }
             Expression::UnaryOperation {
                 expression,
                 operator,
             } => {
                 sql.append_syntax("(");
-                expression.to_sql(sql);
-                operator.to_sql(sql);
+                expression.to_sql(sql, dialect)?;
+                operator.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
             }
             Expression::FunctionCall { function, args } => {
-                function.to_sql(sql);
+                function.to_sql(sql, dialect)?;
                 sql.append_syntax("(");
                 for (index, arg) in args.iter().enumerate() {
-                    arg.to_sql(sql);
+                    arg.to_sql(sql, dialect)?;
                     if index < (args.len() - 1) {
                         sql.append_syntax(", ");
                     }
@@ -461,23 +542,26 @@ impl Expression {
             }
             Expression::JoinExpressions(expressions) => {
                 for (index, expression) in expressions.iter().enumerate() {
-                    expression.to_sql(sql);
+                    expression.to_sql(sql, dialect)?;
                     if index < (expressions.len() - 1) {
                         sql.append_syntax("");
                     }
                 }
             }
             Expression::SafeOffSet { offset } => {
-                sql.append_syntax(format!("[SAFE_OFFSET({offset})]").as_str());
+                let (before, after) = dialect.array_offset_syntax();
+                sql.append_syntax(before);
+                sql.append_syntax(&offset.to_string());
+                sql.append_syntax(after);
             }
             Expression::Exists { select } => {
                 sql.append_syntax("EXISTS ");
                 sql.append_syntax("(");
-                select.to_sql(sql);
+                select.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
             }
             Expression::JsonBuildObject(map) => {
-                sql.append_syntax("JSON_OBJECT");
+                sql.append_syntax(dialect.json_function(JsonFunction::BuildObject)?);
                 sql.append_syntax("(");
 
                 for (index, (label, item)) in map.iter().enumerate() {
@@ -485,7 +569,7 @@ impl Expression {
                     sql.append_syntax(label);
                     sql.append_syntax("'");
                     sql.append_syntax(", ");
-                    item.to_sql(sql);
+                    item.to_sql(sql, dialect)?;
 
                     if index < (map.len() - 1) {
                         sql.append_syntax(", ");
@@ -495,31 +579,32 @@ impl Expression {
                 sql.append_syntax(")");
             }
             Expression::RowToJson(select) => {
-                sql.append_syntax("row_to_json");
+                sql.append_syntax(dialect.json_function(JsonFunction::RowToJson)?);
                 sql.append_syntax("(");
-                select.to_sql(sql);
+                select.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
             }
             Expression::Count(count_type) => {
                 sql.append_syntax("COUNT");
                 sql.append_syntax("(");
-                count_type.to_sql(sql);
+                count_type.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
             }
             Expression::ArrayConstructor(elements) => {
-                sql.append_syntax("ARRAY[");
+                let (before, after) = dialect.array_literal_syntax();
+                sql.append_syntax(before);
                 for (index, element) in elements.iter().enumerate() {
-                    element.to_sql(sql);
+                    element.to_sql(sql, dialect)?;
 
                     if index < (elements.len() - 1) {
                         sql.append_syntax(", ");
                     }
                 }
-                sql.append_syntax("]");
+                sql.append_syntax(after);
             }
             Expression::CorrelatedSubSelect(select) => {
                 sql.append_syntax("(");
-                select.to_sql(sql);
+                select.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
             }
             Expression::NestedFieldSelect {
@@ -527,32 +612,33 @@ impl Expression {
                 nested_field,
             } => {
                 sql.append_syntax("(");
-                expression.to_sql(sql);
+                expression.to_sql(sql, dialect)?;
                 sql.append_syntax(")");
                 sql.append_syntax(".");
-                nested_field.to_sql(sql);
+                nested_field.to_sql(sql, dialect)?;
             } // Expression::JsonQuery(target, path) => {
               //     sql.append_syntax("JSON_QUERY");
               //     sql.append_syntax("(");
-              //     target.to_sql(sql);
+              //     target.to_sql(sql, dialect)?;
               //     sql.append_syntax(", ");
-              //     path.to_sql(sql);
+              //     path.to_sql(sql, dialect)?;
               //     sql.append_syntax(")")
               // }
               // Expression::JsonValue(target, path) => {
               //     sql.append_syntax("JSON_VALUE");
               //     sql.append_syntax("(");
-              //     target.to_sql(sql);
+              //     target.to_sql(sql, dialect)?;
               //     sql.append_syntax(", ");
-              //     path.to_sql(sql);
+              //     path.to_sql(sql, dialect)?;
               //     sql.append_syntax(")")
               // }
         }
+        Ok(())
     }
 }
 
 // impl JsonPath {
-//     pub fn to_sql(&self, sql: &mut SQL) {
+//     pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
 //         sql.append_syntax("'$");
 //         for ColumnAlias {
 //             name: path_item, ..
@@ -566,133 +652,172 @@ impl Expression {
 // }
 
 impl UnaryOperator {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, _dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
             UnaryOperator::IsNull => sql.append_syntax(" IS NULL "),
         }
+        Ok(())
     }
 }
 
 impl BinaryOperator {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, _dialect: &dyn Dialect) -> Result<(), Error> {
         sql.append_syntax(" ");
         sql.append_syntax(&self.0);
         sql.append_syntax(" ");
+        Ok(())
     }
 }
 
 impl BinaryArrayOperator {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, _dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
             BinaryArrayOperator::In => sql.append_syntax(" IN "),
         }
+        Ok(())
     }
 }
 
 impl NestedField {
-    pub fn to_sql(&self, sql: &mut SQL) {
-        sql.append_identifier(&self.0);
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        dialect.append_identifier(sql, &self.0);
+        Ok(())
     }
 }
 
 impl Function {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
             Function::Coalesce => sql.append_syntax("coalesce"),
-            Function::JsonAgg => sql.append_syntax("json_agg"),
-            Function::JsonbPopulateRecord => sql.append_syntax("jsonb_populate_record"),
-            Function::ArrayAgg => sql.append_syntax("ARRAY_AGG"),
-            Function::Unnest => sql.append_syntax("unnest"),
+            Function::JsonAgg => {
+                sql.append_syntax(dialect.json_function(JsonFunction::ArrayAggregate)?);
+            }
+            Function::ArrayAgg => {
+                sql.append_syntax(dialect.array_function(ArrayFunction::Aggregate)?);
+            }
+            Function::ParseJson => sql.append_syntax(dialect.json_function(JsonFunction::Parse)?),
+            Function::JsonValue => {
+                sql.append_syntax(dialect.json_function(JsonFunction::ExtractScalar)?);
+            }
+            Function::JsonQueryArray => {
+                sql.append_syntax(dialect.json_function(JsonFunction::ExtractArray)?);
+            }
+            Function::Unnest => sql.append_syntax(dialect.array_function(ArrayFunction::Unnest)?),
             Function::Unknown(name) => sql.append_syntax(name),
             Function::SafeOffSet(index) => {
-                sql.append_syntax(format!("[SAFE_OFFSET({index})]").as_str());
+                let (before, after) = dialect.array_offset_syntax();
+                sql.append_syntax(before);
+                sql.append_syntax(index);
+                sql.append_syntax(after);
+            }
+            Function::Routine { schema, name } => {
+                schema.to_sql(sql, dialect)?;
+                sql.append_syntax(".");
+                name.to_sql(sql, dialect)?;
             }
         }
+        Ok(())
     }
 }
 
 impl CountType {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
             CountType::Star => sql.append_syntax("*"),
-            CountType::Simple(column) => column.to_sql(sql),
+            CountType::Simple(column) => column.to_sql(sql, dialect)?,
             CountType::Distinct(column) => {
                 sql.append_syntax("DISTINCT ");
-                column.to_sql(sql);
+                column.to_sql(sql, dialect)?;
             }
         }
+        Ok(())
     }
 }
 
 impl Value {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match &self {
-            Value::EmptyJsonArray => sql.append_syntax("'[]'"),
+            Value::EmptyJsonArray => sql.append_syntax(dialect.empty_json_array()),
             Value::Int8(i) => sql.append_syntax(format!("{i}").as_str()),
-            Value::Float8(n) => sql.append_syntax(format!("{n}").as_str()),
-            Value::Character(s) | Value::String(s) => sql.append_param(Param::String(s.clone())),
-            Value::Variable(v) => sql.append_param(Param::Variable(v.clone())),
-            Value::Bool(true) => sql.append_syntax("true"),
-            Value::Bool(false) => sql.append_syntax("false"),
+            Value::Float8(n) => dialect.append_scalar_literal(sql, ScalarLiteral::Float64(*n)),
+            Value::Character(s) | Value::String(s) => {
+                dialect.append_param(sql, Param::String(s.clone()))
+            }
+            Value::Variable(v) => dialect.append_param(sql, Param::Variable(v.clone())),
+            Value::Literal { index, encoding } => dialect.append_param(
+                sql,
+                Param::Literal {
+                    index: *index,
+                    encoding: *encoding,
+                },
+            ),
+            Value::Bool(b) => dialect.append_scalar_literal(sql, ScalarLiteral::Bool(*b)),
             Value::Null => sql.append_syntax("null"),
-            Value::JsonValue(v) => sql.append_param(Param::Value(v.clone())),
+            Value::JsonValue(v) => dialect.append_param(sql, Param::Value(v.clone())),
             Value::Array(items) => {
-                sql.append_syntax("ARRAY [");
+                let (before, after) = dialect.array_literal_syntax();
+                sql.append_syntax(before);
                 for (index, item) in items.iter().enumerate() {
-                    item.to_sql(sql);
+                    item.to_sql(sql, dialect)?;
                     if index < (items.len() - 1) {
                         sql.append_syntax(", ");
                     }
                 }
-                sql.append_syntax("]");
+                sql.append_syntax(after);
             }
         }
+        Ok(())
     }
 }
 
 impl MutationValueExpression {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match &self {
-            MutationValueExpression::Expression(expression) => expression.to_sql(sql),
+            MutationValueExpression::Expression(expression) => expression.to_sql(sql, dialect)?,
             MutationValueExpression::Default => sql.append_syntax("DEFAULT"),
         }
+        Ok(())
     }
 }
 
 impl ScalarType {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match &self {
             ScalarType::BaseType(scalar_type_name) => {
-                scalar_type_name.to_sql(sql);
+                scalar_type_name.to_sql(sql, dialect)?;
             }
             ScalarType::ArrayType(scalar_type_name) => {
-                scalar_type_name.to_sql(sql);
-                sql.append_syntax("[]");
+                let (before, after) = dialect.array_type_syntax();
+                sql.append_syntax(before);
+                scalar_type_name.to_sql(sql, dialect)?;
+                sql.append_syntax(after);
             }
         };
+        Ok(())
     }
 }
 
 impl ScalarTypeName {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match &self {
             ScalarTypeName::Qualified {
                 schema_name,
                 type_name,
             } => {
-                schema_name.to_sql(sql);
+                schema_name.to_sql(sql, dialect)?;
                 sql.append_syntax(".");
-                sql.append_identifier(type_name);
+                dialect.append_identifier(sql, type_name);
             }
             ScalarTypeName::Unqualified(type_name) => {
-                sql.append_identifier(type_name);
+                dialect.append_identifier(sql, dialect.type_name(type_name));
             }
         };
+        Ok(())
     }
 }
 
 impl Limit {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, _dialect: &dyn Dialect) -> Result<(), Error> {
         match self.limit {
             None => (),
             Some(limit) => {
@@ -707,39 +832,64 @@ impl Limit {
                 sql.append_syntax(format!("{offset}").as_str());
             }
         };
+        Ok(())
     }
 }
 
 // names
 impl TableReference {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
+            // wildcard tables must be quoted as a whole: `project.dataset.events_*`.
+            TableReference::DBTable { schema, table } if table.0.ends_with('*') => {
+                dialect.append_quoted_identifier(sql, &format!("{}.{}", schema.0, table.0));
+            }
             TableReference::DBTable { schema, table } => {
-                sql.append_identifier(&schema.0);
+                schema.to_sql(sql, dialect)?;
                 sql.append_syntax(".");
-                sql.append_identifier(&table.0);
+                table.to_sql(sql, dialect)?;
             }
-            TableReference::AliasedTable(alias) => alias.to_sql(sql),
+            TableReference::AliasedTable(alias) => alias.to_sql(sql, dialect)?,
         };
+        Ok(())
     }
 }
 
 impl SchemaName {
-    pub fn to_sql(&self, sql: &mut SQL) {
-        sql.append_identifier(&self.0);
+    /// Schema names are `project.dataset` paths. Dataset names cannot contain dots,
+    /// so we quote the project and the dataset separately.
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        match self.0.rsplit_once('.') {
+            None => dialect.append_identifier(sql, &self.0),
+            Some((project, dataset)) => {
+                dialect.append_identifier(sql, project);
+                sql.append_syntax(".");
+                dialect.append_identifier(sql, dataset);
+            }
+        }
+        Ok(())
     }
 }
 
 impl TableName {
-    pub fn to_sql(&self, sql: &mut SQL) {
-        sql.append_identifier(&self.0);
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        dialect.append_identifier(sql, &self.0);
+        Ok(())
+    }
+}
+
+impl RoutineName {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        dialect.append_identifier(sql, &self.0);
+        Ok(())
     }
 }
 
 impl TableAlias {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         let name = self.to_aliased_string();
-        sql.append_identifier(&name);
+        dialect.append_identifier(sql, &name);
+        Ok(())
     }
 
     pub fn to_aliased_string(&self) -> String {
@@ -748,61 +898,66 @@ impl TableAlias {
 }
 
 impl ColumnReference {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
             ColumnReference::TableColumn { table, name } => {
-                table.to_sql(sql);
+                table.to_sql(sql, dialect)?;
                 sql.append_syntax(".");
-                sql.append_identifier(&name.0.to_string());
+                name.to_sql(sql, dialect)?;
             }
             ColumnReference::AliasedColumn { table, column } => {
-                table.to_sql(sql);
+                table.to_sql(sql, dialect)?;
                 sql.append_syntax(".");
-                column.to_sql(sql);
+                column.to_sql(sql, dialect)?;
             }
         };
+        Ok(())
     }
 }
 
 impl ColumnName {
-    pub fn to_sql(&self, sql: &mut SQL) {
-        sql.append_identifier(&self.0);
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        dialect.append_identifier(sql, &self.0);
+        Ok(())
     }
 }
 
 impl ColumnAlias {
-    pub fn to_sql(&self, sql: &mut SQL) {
-        let name = self.name.to_string();
-        sql.append_identifier(&name);
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        dialect.append_identifier(sql, &self.name);
+        Ok(())
     }
 }
 
 impl OrderBy {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
         if !self.elements.is_empty() {
             sql.append_syntax(" ORDER BY ");
             for (index, order_by_item) in self.elements.iter().enumerate() {
-                order_by_item.to_sql(sql);
+                order_by_item.to_sql(sql, dialect)?;
                 if index < (self.elements.len() - 1) {
                     sql.append_syntax(", ");
                 }
             }
         }
+        Ok(())
     }
 }
 
 impl OrderByElement {
-    pub fn to_sql(&self, sql: &mut SQL) {
-        self.target.to_sql(sql);
-        self.direction.to_sql(sql);
+    pub fn to_sql(&self, sql: &mut SQL, dialect: &dyn Dialect) -> Result<(), Error> {
+        self.target.to_sql(sql, dialect)?;
+        self.direction.to_sql(sql, dialect)?;
+        Ok(())
     }
 }
 
 impl OrderByDirection {
-    pub fn to_sql(&self, sql: &mut SQL) {
+    pub fn to_sql(&self, sql: &mut SQL, _dialect: &dyn Dialect) -> Result<(), Error> {
         match self {
             OrderByDirection::Asc => sql.append_syntax(" ASC "),
             OrderByDirection::Desc => sql.append_syntax(" DESC "),
         }
+        Ok(())
     }
 }
@@ -0,0 +1,631 @@
+//! SQL dialects: the parts of the SQL syntax which differ between databases.
+//!
+//! Every `to_sql` function in [`super::convert`] takes a dialect, which decides how
+//! identifiers, parameters, JSON and array functions, joins and type names are written, and
+//! rejects the AST nodes it cannot render.
+
+use super::string::{Param, SQL};
+
+/// An error raised when rendering a SQL AST.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    #[error("{construct} is not supported by the {dialect} SQL dialect.")]
+    Unsupported {
+        dialect: &'static str,
+        construct: &'static str,
+    },
+}
+
+/// Functions constructing or deconstructing JSON values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonFunction {
+    /// Build a JSON object from labels and values.
+    BuildObject,
+    /// Aggregate values into a JSON array.
+    ArrayAggregate,
+    /// Convert a row into a JSON object.
+    RowToJson,
+    /// Parse a string into a JSON value.
+    Parse,
+    /// Extract a scalar from a JSON value, as a string.
+    ExtractScalar,
+    /// Extract an array from a JSON value, as an array of JSON values.
+    ExtractArray,
+}
+
+/// Functions constructing or deconstructing arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayFunction {
+    /// Aggregate values into an array.
+    Aggregate,
+    /// Build an array from the rows of a subquery.
+    FromSubquery,
+    /// Turn an array into a table of its elements.
+    Unnest,
+}
+
+/// A number or boolean literal, which is written in the SQL rather than bound as a parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalarLiteral {
+    Float64(f64),
+    Bool(bool),
+}
+
+impl std::fmt::Display for ScalarLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScalarLiteral::Float64(number) => write!(f, "{number}"),
+            ScalarLiteral::Bool(boolean) => write!(f, "{boolean}"),
+        }
+    }
+}
+
+/// The parts of the SQL syntax which differ between databases.
+pub trait Dialect {
+    /// The name of the dialect, used in error messages.
+    fn name(&self) -> &'static str;
+
+    /// Append a SQL identifier like a column or a table name, quoting it if it cannot be
+    /// written as-is.
+    fn append_identifier(&self, sql: &mut SQL, identifier: &str);
+
+    /// Append a SQL identifier, always quoting it.
+    fn append_quoted_identifier(&self, sql: &mut SQL, identifier: &str);
+
+    /// Append the placeholder of a parameter, and record the parameter so we can bind it
+    /// when we run the query.
+    fn append_param(&self, sql: &mut SQL, param: Param);
+
+    /// Append a number or boolean literal. It is written as it is by default.
+    fn append_scalar_literal(&self, sql: &mut SQL, literal: ScalarLiteral) {
+        sql.append_syntax(&literal.to_string());
+    }
+
+    /// The name of a JSON function, if the dialect has one.
+    fn json_function(&self, function: JsonFunction) -> Result<&'static str, Error>;
+
+    /// A literal empty JSON array.
+    fn empty_json_array(&self) -> &'static str;
+
+    /// The name of an array function, if the dialect has one.
+    fn array_function(&self, function: ArrayFunction) -> Result<&'static str, Error>;
+
+    /// The syntax surrounding the elements of an array literal.
+    fn array_literal_syntax(&self) -> (&'static str, &'static str);
+
+    /// The syntax surrounding the position of an element of an array, following the array.
+    /// Positions out of the bounds of the array give null.
+    fn array_offset_syntax(&self) -> (&'static str, &'static str);
+
+    /// The keyword introducing the rows returned by an insert, update or delete, if the
+    /// dialect has one.
+    fn returning_keyword(&self) -> Result<&'static str, Error>;
+
+    /// A join condition which is always true, for joins which do not filter rows.
+    fn true_join_condition(&self) -> &'static str;
+
+    /// The name of one of our scalar types in this dialect.
+    fn type_name<'a>(&self, type_name: &'a str) -> &'a str;
+
+    /// The syntax surrounding the element type of an array type.
+    fn array_type_syntax(&self) -> (&'static str, &'static str);
+}
+
+/// GoogleSQL, the SQL dialect of BigQuery.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BigQuery;
+
+impl BigQuery {
+    fn unsupported(construct: &'static str) -> Error {
+        Error::Unsupported {
+            dialect: "BigQuery",
+            construct,
+        }
+    }
+}
+
+impl Dialect for BigQuery {
+    fn name(&self) -> &'static str {
+        "BigQuery"
+    }
+
+    /// Identifiers which are not plain names (reserved keywords, names with hyphens,
+    /// unicode, ...) are quoted.
+    fn append_identifier(&self, sql: &mut SQL, identifier: &str) {
+        if is_unquoted_identifier(identifier) {
+            sql.append_syntax(identifier);
+        } else {
+            self.append_quoted_identifier(sql, identifier);
+        }
+    }
+
+    /// Identifiers are surrounded by backticks, escaping backticks, backslashes and
+    /// control characters.
+    fn append_quoted_identifier(&self, sql: &mut SQL, identifier: &str) {
+        let mut quoted = String::with_capacity(identifier.len() + 2);
+        quoted.push('`');
+        for character in identifier.chars() {
+            match character {
+                '`' => quoted.push_str("\\`"),
+                '\\' => quoted.push_str("\\\\"),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                character if character.is_control() => {
+                    quoted.push_str(format!("\\u{:04x}", u32::from(character)).as_str());
+                }
+                character => quoted.push(character),
+            }
+        }
+        quoted.push('`');
+        sql.append_syntax(&quoted);
+    }
+
+    /// Parameters are named by their position: `@param1`, `@param2`, and so on.
+    fn append_param(&self, sql: &mut SQL, param: Param) {
+        let position = sql.push_param(param);
+        sql.append_syntax(format!("@param{position}").as_str());
+    }
+
+    fn json_function(&self, function: JsonFunction) -> Result<&'static str, Error> {
+        match function {
+            JsonFunction::BuildObject => Ok("JSON_OBJECT"),
+            JsonFunction::ArrayAggregate => Err(Self::unsupported("json_agg")),
+            JsonFunction::RowToJson => Err(Self::unsupported("row_to_json")),
+            JsonFunction::Parse => Ok("PARSE_JSON"),
+            JsonFunction::ExtractScalar => Ok("JSON_VALUE"),
+            JsonFunction::ExtractArray => Ok("JSON_QUERY_ARRAY"),
+        }
+    }
+
+    fn empty_json_array(&self) -> &'static str {
+        "JSON '[]'"
+    }
+
+    fn array_function(&self, function: ArrayFunction) -> Result<&'static str, Error> {
+        match function {
+            ArrayFunction::Aggregate => Ok("ARRAY_AGG"),
+            ArrayFunction::FromSubquery => Ok("ARRAY"),
+            ArrayFunction::Unnest => Ok("UNNEST"),
+        }
+    }
+
+    fn array_literal_syntax(&self) -> (&'static str, &'static str) {
+        ("ARRAY[", "]")
+    }
+
+    fn array_offset_syntax(&self) -> (&'static str, &'static str) {
+        ("[SAFE_OFFSET(", ")]")
+    }
+
+    /// Inserts, updates and deletes do not return rows: the rows they affect must be queried
+    /// separately.
+    fn returning_keyword(&self) -> Result<&'static str, Error> {
+        Err(Self::unsupported("RETURNING"))
+    }
+
+    fn true_join_condition(&self) -> &'static str {
+        "true"
+    }
+
+    /// See `scalar_type_name` in the configuration crate for the names we give to
+    /// BigQuery types.
+    fn type_name<'a>(&self, type_name: &'a str) -> &'a str {
+        match type_name {
+            "boolean" => "BOOL",
+            "smallint" | "integer" | "bigint" => "INT64",
+            "numeric" => "NUMERIC",
+            "float" | "real" | "double precision" => "FLOAT64",
+            "string" | "text" | "character" | "uuid" => "STRING",
+            "json" | "jsonb" => "JSON",
+            "date" => "DATE",
+            "time" | "timetz" => "TIME",
+            "timestamp" | "timestamptz" => "TIMESTAMP",
+            _ => type_name,
+        }
+    }
+
+    fn array_type_syntax(&self) -> (&'static str, &'static str) {
+        ("ARRAY<", ">")
+    }
+}
+
+/// A dialect which binds number and boolean literals as parameters, instead of writing them
+/// in the SQL, so that the SQL holds none of the values of a request, e.g. to log it. Each
+/// parameter is cast to the type BigQuery gives the literal it replaces.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParameterisedLiterals<D>(pub D);
+
+impl<D: Dialect> Dialect for ParameterisedLiterals<D> {
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn append_identifier(&self, sql: &mut SQL, identifier: &str) {
+        self.0.append_identifier(sql, identifier);
+    }
+
+    fn append_quoted_identifier(&self, sql: &mut SQL, identifier: &str) {
+        self.0.append_quoted_identifier(sql, identifier);
+    }
+
+    fn append_param(&self, sql: &mut SQL, param: Param) {
+        self.0.append_param(sql, param);
+    }
+
+    /// Literals are bound as strings, and cast back to their type: `CAST(@param1 AS BOOL)`.
+    /// A number written without a fraction, like `1`, is an INT64 literal.
+    fn append_scalar_literal(&self, sql: &mut SQL, literal: ScalarLiteral) {
+        let type_name = match literal {
+            ScalarLiteral::Float64(_) if literal.to_string().parse::<i64>().is_ok() => {
+                self.type_name("bigint")
+            }
+            ScalarLiteral::Float64(_) => self.type_name("float"),
+            ScalarLiteral::Bool(_) => self.type_name("boolean"),
+        };
+        sql.append_syntax("CAST(");
+        self.append_param(sql, Param::String(literal.to_string()));
+        sql.append_syntax(" AS ");
+        sql.append_syntax(type_name);
+        sql.append_syntax(")");
+    }
+
+    fn json_function(&self, function: JsonFunction) -> Result<&'static str, Error> {
+        self.0.json_function(function)
+    }
+
+    fn empty_json_array(&self) -> &'static str {
+        self.0.empty_json_array()
+    }
+
+    fn array_function(&self, function: ArrayFunction) -> Result<&'static str, Error> {
+        self.0.array_function(function)
+    }
+
+    fn array_literal_syntax(&self) -> (&'static str, &'static str) {
+        self.0.array_literal_syntax()
+    }
+
+    fn array_offset_syntax(&self) -> (&'static str, &'static str) {
+        self.0.array_offset_syntax()
+    }
+
+    fn returning_keyword(&self) -> Result<&'static str, Error> {
+        self.0.returning_keyword()
+    }
+
+    fn true_join_condition(&self) -> &'static str {
+        self.0.true_join_condition()
+    }
+
+    fn type_name<'a>(&self, type_name: &'a str) -> &'a str {
+        self.0.type_name(type_name)
+    }
+
+    fn array_type_syntax(&self) -> (&'static str, &'static str) {
+        self.0.array_type_syntax()
+    }
+}
+
+/// Reserved keywords of GoogleSQL, which must be quoted when used as identifiers.
+/// See <https://cloud.google.com/bigquery/docs/reference/standard-sql/lexical#reserved_keywords>.
+const RESERVED_KEYWORDS: &[&str] = &[
+    "ALL",
+    "AND",
+    "ANY",
+    "ARRAY",
+    "AS",
+    "ASC",
+    "ASSERT_ROWS_MODIFIED",
+    "AT",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CAST",
+    "COLLATE",
+    "CONTAINS",
+    "CREATE",
+    "CROSS",
+    "CUBE",
+    "CURRENT",
+    "DEFAULT",
+    "DEFINE",
+    "DESC",
+    "DISTINCT",
+    "ELSE",
+    "END",
+    "ENUM",
+    "ESCAPE",
+    "EXCEPT",
+    "EXCLUDE",
+    "EXISTS",
+    "EXTRACT",
+    "FALSE",
+    "FETCH",
+    "FOLLOWING",
+    "FOR",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "GROUPING",
+    "GROUPS",
+    "HASH",
+    "HAVING",
+    "IF",
+    "IGNORE",
+    "IN",
+    "INNER",
+    "INTERSECT",
+    "INTERVAL",
+    "INTO",
+    "IS",
+    "JOIN",
+    "LATERAL",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "LOOKUP",
+    "MERGE",
+    "NATURAL",
+    "NEW",
+    "NO",
+    "NOT",
+    "NULL",
+    "NULLS",
+    "OF",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "PRECEDING",
+    "PROTO",
+    "QUALIFY",
+    "RANGE",
+    "RECURSIVE",
+    "RESPECT",
+    "RIGHT",
+    "ROLLUP",
+    "ROWS",
+    "SELECT",
+    "SET",
+    "SOME",
+    "STRUCT",
+    "TABLESAMPLE",
+    "THEN",
+    "TO",
+    "TREAT",
+    "TRUE",
+    "UNBOUNDED",
+    "UNION",
+    "UNNEST",
+    "USING",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+    "WITHIN",
+];
+
+/// Can this identifier be written without quotes? It must start with a letter or an
+/// underscore, contain only letters, digits and underscores, and not be a reserved keyword.
+fn is_unquoted_identifier(identifier: &str) -> bool {
+    let mut characters = identifier.chars();
+    let starts_well = characters
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_');
+    starts_well
+        && characters.all(|character| character.is_ascii_alphanumeric() || character == '_')
+        && !RESERVED_KEYWORDS
+            .iter()
+            .any(|keyword| keyword.eq_ignore_ascii_case(identifier))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ast;
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Read back an identifier the way GoogleSQL would.
+    fn unquote(sql: &str) -> Option<String> {
+        let Some(quoted) = sql.strip_prefix('`') else {
+            return Some(sql.to_string());
+        };
+        let mut identifier = String::new();
+        let mut characters = quoted.chars();
+        loop {
+            match characters.next()? {
+                '`' => return characters.next().is_none().then_some(identifier),
+                '\\' => match characters.next()? {
+                    'n' => identifier.push('\n'),
+                    'r' => identifier.push('\r'),
+                    't' => identifier.push('\t'),
+                    'u' => {
+                        let code: String = characters.by_ref().take(4).collect();
+                        identifier.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                    }
+                    escaped => identifier.push(escaped),
+                },
+                character => identifier.push(character),
+            }
+        }
+    }
+
+    fn identifier_sql(identifier: &str) -> String {
+        let mut sql = SQL::new();
+        BigQuery.append_identifier(&mut sql, identifier);
+        sql.sql
+    }
+
+    #[test]
+    fn parameters_are_named_by_position() {
+        let mut sql = SQL::new();
+        BigQuery.append_param(&mut sql, Param::String("a".to_string()));
+        sql.append_syntax(", ");
+        BigQuery.append_param(&mut sql, Param::String("b".to_string()));
+        assert_eq!(sql.sql, "@param1, @param2");
+        assert_eq!(sql.params.len(), 2);
+    }
+
+    #[test]
+    fn scalar_literals_are_written_or_bound() {
+        let mut sql = SQL::new();
+        BigQuery.append_scalar_literal(&mut sql, ScalarLiteral::Float64(1.5));
+        sql.append_syntax(", ");
+        BigQuery.append_scalar_literal(&mut sql, ScalarLiteral::Bool(true));
+        assert_eq!(sql.sql, "1.5, true");
+        assert!(sql.params.is_empty());
+
+        let mut sql = SQL::new();
+        let dialect = ParameterisedLiterals(BigQuery);
+        dialect.append_scalar_literal(&mut sql, ScalarLiteral::Float64(1.5));
+        sql.append_syntax(", ");
+        dialect.append_scalar_literal(&mut sql, ScalarLiteral::Bool(true));
+        assert_eq!(sql.sql, "CAST(@param1 AS FLOAT64), CAST(@param2 AS BOOL)");
+        assert_eq!(
+            sql.params,
+            vec![
+                Param::String("1.5".to_string()),
+                Param::String("true".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn integral_literals_are_bound_as_int64() {
+        let mut sql = SQL::new();
+        BigQuery.append_scalar_literal(&mut sql, ScalarLiteral::Float64(1.0));
+        assert_eq!(sql.sql, "1");
+
+        let mut sql = SQL::new();
+        let dialect = ParameterisedLiterals(BigQuery);
+        dialect.append_scalar_literal(&mut sql, ScalarLiteral::Float64(1.0));
+        sql.append_syntax(", ");
+        dialect.append_scalar_literal(&mut sql, ScalarLiteral::Float64(-2.0));
+        assert_eq!(sql.sql, "CAST(@param1 AS INT64), CAST(@param2 AS INT64)");
+        assert_eq!(
+            sql.params,
+            vec![
+                Param::String("1".to_string()),
+                Param::String("-2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn postgres_json_functions_are_rejected() {
+        assert_eq!(
+            BigQuery.json_function(JsonFunction::RowToJson),
+            Err(Error::Unsupported {
+                dialect: "BigQuery",
+                construct: "row_to_json",
+            })
+        );
+        assert_eq!(
+            BigQuery.json_function(JsonFunction::BuildObject),
+            Ok("JSON_OBJECT")
+        );
+    }
+
+    #[test]
+    fn returning_is_rejected() {
+        assert_eq!(
+            BigQuery.returning_keyword(),
+            Err(Error::Unsupported {
+                dialect: "BigQuery",
+                construct: "RETURNING",
+            })
+        );
+        assert_eq!(
+            ParameterisedLiterals(BigQuery).returning_keyword(),
+            BigQuery.returning_keyword()
+        );
+    }
+
+    #[test]
+    fn returning_clauses_are_not_rendered() {
+        let returning = ast::Returning(ast::SelectList::SelectStar);
+        assert_eq!(
+            returning.to_sql(&mut SQL::new(), &BigQuery),
+            Err(Error::Unsupported {
+                dialect: "BigQuery",
+                construct: "RETURNING",
+            })
+        );
+    }
+
+    #[test]
+    fn array_literals_are_written_alike() {
+        let mut value_sql = SQL::new();
+        ast::Value::Array(vec![ast::Value::Int8(1), ast::Value::Int8(2)])
+            .to_sql(&mut value_sql, &BigQuery)
+            .unwrap();
+        let mut expression_sql = SQL::new();
+        ast::Expression::ArrayConstructor(vec![
+            ast::Expression::Value(ast::Value::Int8(1)),
+            ast::Expression::Value(ast::Value::Int8(2)),
+        ])
+        .to_sql(&mut expression_sql, &BigQuery)
+        .unwrap();
+        assert_eq!(value_sql.sql, "ARRAY[1, 2]");
+        assert_eq!(expression_sql.sql, value_sql.sql);
+    }
+
+    #[test]
+    fn plain_identifiers_are_not_quoted() {
+        assert_eq!(identifier_sql("albums_0"), "albums_0");
+        assert_eq!(identifier_sql("_TABLE_SUFFIX"), "_TABLE_SUFFIX");
+    }
+
+    #[test]
+    fn special_identifiers_are_quoted() {
+        assert_eq!(identifier_sql("my-project"), "`my-project`");
+        assert_eq!(identifier_sql("select"), "`select`");
+        assert_eq!(identifier_sql("Ünïcode"), "`Ünïcode`");
+        assert_eq!(identifier_sql("1st"), "`1st`");
+        assert_eq!(identifier_sql(""), "``");
+        assert_eq!(identifier_sql("a`b\\c\nd"), "`a\\`b\\\\c\\nd`");
+    }
+
+    proptest! {
+        #[test]
+        fn identifiers_round_trip(identifier in any::<String>()) {
+            prop_assert_eq!(unquote(&identifier_sql(&identifier)), Some(identifier));
+        }
+
+        #[test]
+        fn quoted_identifiers_cannot_escape_their_quotes(identifier in any::<String>()) {
+            let sql = identifier_sql(&identifier);
+            // count the backticks which are not escaped by a backslash.
+            let mut unescaped_backticks = 0;
+            let mut characters = sql.chars();
+            while let Some(character) = characters.next() {
+                match character {
+                    '\\' => {
+                        characters.next();
+                    }
+                    '`' => unescaped_backticks += 1,
+                    _ => {}
+                }
+            }
+            prop_assert!(unescaped_backticks == 0 || unescaped_backticks == 2);
+            prop_assert!(!sql.contains('\n') && !sql.contains('\r'));
+        }
+
+        #[test]
+        fn unquoted_identifiers_are_plain(identifier in "[A-Za-z_][A-Za-z0-9_]{0,20}") {
+            let sql = identifier_sql(&identifier);
+            let expected = if RESERVED_KEYWORDS
+                .iter()
+                .any(|keyword| keyword.eq_ignore_ascii_case(&identifier))
+            {
+                format!("`{identifier}`")
+            } else {
+                identifier.clone()
+            };
+            prop_assert_eq!(sql, expected);
+        }
+    }
+}
@@ -1,11 +1,12 @@
 //! Describe the SQL execution plan.
 
 use crate::sql;
+use crate::sql::dialect::{self, Dialect};
 use ndc_models as models;
 
 use std::collections::BTreeMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// Definition of an execution plan to be run against the database.
 pub struct ExecutionPlan<Query> {
     /// Run before the query. Should be a sql::ast in the future.
@@ -17,23 +18,69 @@ pub struct ExecutionPlan<Query> {
 }
 
 /// The query we want to run with some additional information.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Query {
     /// The root field name of the top-most collection.
     pub root_field: models::CollectionName,
     /// foreach variables.
     pub variables: Option<Vec<BTreeMap<models::VariableName, serde_json::Value>>>,
+    /// The values of the request parameters, bound by name (`@name`) as strings.
+    pub request_parameters: BTreeMap<String, Option<String>>,
+    /// The literals lifted out of the request, bound by their index. The query of a plan
+    /// with lifted literals can run with the literals of any request of the same shape.
+    pub literals: Vec<serde_json::Value>,
+    /// The query.
+    pub query: sql::ast::Select,
+    /// The same query returning the root rows as typed columns, when their fields are all
+    /// plain columns. The rows are then decoded according to the schema of the result
+    /// instead of being rendered as JSON by BigQuery.
+    pub typed_rows: Option<TypedRows>,
+}
+
+/// A query returning the rows of a `RowSet` as typed columns.
+#[derive(Debug, Clone)]
+pub struct TypedRows {
+    /// The field of each column, in order.
+    pub fields: Vec<models::FieldName>,
     /// The query.
     pub query: sql::ast::Select,
 }
 
+impl TypedRows {
+    /// Extract the query as SQL.
+    pub fn query_sql(&self, dialect: &dyn Dialect) -> Result<sql::string::SQL, dialect::Error> {
+        select_to_sql(&self.query, dialect)
+    }
+}
+
 impl Query {
     /// Extract the query component as SQL.
-    pub fn query_sql(&self) -> sql::string::SQL {
-        select_to_sql(&self.query)
+    pub fn query_sql(&self, dialect: &dyn Dialect) -> Result<sql::string::SQL, dialect::Error> {
+        select_to_sql(&self.query, dialect)
     }
-    pub fn explain_query_sql(&self) -> sql::string::SQL {
-        explain_to_sql(&sql::ast::Explain::Select(&self.query))
+    pub fn explain_query_sql(
+        &self,
+        dialect: &dyn Dialect,
+    ) -> Result<sql::string::SQL, dialect::Error> {
+        explain_to_sql(&sql::ast::Explain::Select(&self.query), dialect)
+    }
+}
+
+/// A stored procedure call we want to run.
+#[derive(Debug)]
+pub struct Mutation {
+    /// The name of the procedure, used as the operation's name in the response.
+    pub procedure: models::ProcedureName,
+    /// The CALL statement.
+    pub call: sql::ast::Call,
+}
+
+impl Mutation {
+    /// Extract the CALL statement as SQL.
+    pub fn call_sql(&self, dialect: &dyn Dialect) -> Result<sql::string::SQL, dialect::Error> {
+        let mut sql = sql::string::SQL::new();
+        self.call.to_sql(&mut sql, dialect)?;
+        Ok(sql)
     }
 }
 
@@ -47,31 +94,51 @@ impl Query {
 //     }
 // }
 
-pub fn select_to_sql(select: &sql::ast::Select) -> sql::string::SQL {
+pub fn select_to_sql(
+    select: &sql::ast::Select,
+    dialect: &dyn Dialect,
+) -> Result<sql::string::SQL, dialect::Error> {
     let mut sql = sql::string::SQL::new();
-    select.to_sql(&mut sql);
-    sql
+    select.to_sql(&mut sql, dialect)?;
+    Ok(sql)
 }
 
-pub fn explain_to_sql(explain: &sql::ast::Explain) -> sql::string::SQL {
+pub fn explain_to_sql(
+    explain: &sql::ast::Explain,
+    dialect: &dyn Dialect,
+) -> Result<sql::string::SQL, dialect::Error> {
     let mut sql = sql::string::SQL::new();
-    explain.to_sql(&mut sql);
-    sql
+    explain.to_sql(&mut sql, dialect)?;
+    Ok(sql)
 }
 
 /// A simple query execution plan with only a root field and a query.
 pub fn simple_query_execution_plan(
     variables: Option<Vec<BTreeMap<models::VariableName, serde_json::Value>>>,
+    request_parameters: BTreeMap<String, Option<String>>,
     root_field: models::CollectionName,
     query: sql::ast::Select,
+    typed_rows: Option<TypedRows>,
 ) -> ExecutionPlan<Query> {
     ExecutionPlan {
         pre: vec![],
         query: Query {
             root_field,
             variables,
+            request_parameters,
+            literals: vec![],
             query,
+            typed_rows,
         },
         post: vec![],
     }
 }
+
+/// A mutation execution plan running the procedure calls in order.
+pub fn simple_mutations_execution_plan(mutations: Vec<Mutation>) -> ExecutionPlan<Vec<Mutation>> {
+    ExecutionPlan {
+        pre: vec![],
+        query: mutations,
+        post: vec![],
+    }
+}
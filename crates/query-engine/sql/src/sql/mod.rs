@@ -2,6 +2,7 @@
 
 pub mod ast;
 pub mod convert;
+pub mod dialect;
 pub mod execution_plan;
 pub mod helpers;
 pub mod rewrites;
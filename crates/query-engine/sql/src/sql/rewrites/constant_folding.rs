@@ -89,6 +89,26 @@ fn normalize_from(from: From) -> From {
             alias,
             select: Box::new(normalize_select(*select)),
         },
+        From::Table {
+            reference,
+            alias,
+            system_time,
+        } => From::Table {
+            reference,
+            alias,
+            system_time: system_time.map(normalize_expr),
+        },
+        From::TableFunction {
+            schema,
+            function,
+            arguments,
+            alias,
+        } => From::TableFunction {
+            schema,
+            function,
+            arguments: arguments.into_iter().map(normalize_expr).collect(),
+            alias,
+        },
         from => from,
     }
 }
@@ -199,6 +219,16 @@ pub fn normalize_expr(expr: Expression) -> Expression {
             right: right.into_iter().map(normalize_expr).collect(),
         },
         // Apply inner
+        Expression::Between {
+            expression,
+            low,
+            high,
+        } => Expression::Between {
+            expression: Box::new(normalize_expr(*expression)),
+            low: Box::new(normalize_expr(*low)),
+            high: Box::new(normalize_expr(*high)),
+        },
+        // Apply inner
         Expression::UnaryOperation {
             expression,
             operator,
@@ -227,6 +257,15 @@ pub fn normalize_expr(expr: Expression) -> Expression {
             r#type: scalar_type,
         },
         // Apply inner
+        Expression::JsonSubscript { expression, field } => Expression::JsonSubscript {
+            expression: Box::new(normalize_expr(*expression)),
+            field: Box::new(normalize_expr(*field)),
+        },
+        // Apply inner
+        Expression::ArraySubquery(select) => {
+            Expression::ArraySubquery(Box::new(normalize_select(*select)))
+        }
+        // Apply inner
         Expression::ArrayConstructor(array) => {
             Expression::ArrayConstructor(array.into_iter().map(normalize_expr).collect())
         }
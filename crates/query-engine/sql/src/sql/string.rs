@@ -1,7 +1,7 @@
 //! Type definitions of a low-level SQL string representation.
 
 /// A low-level builder for SQL.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SQL {
     pub sql: String,
     pub params: Vec<Param>,
@@ -22,14 +22,32 @@ pub enum Param {
     Value(serde_json::Value),
     /// A variable name to look up in the `variables` field in a `QueryRequest`.
     Variable(String),
+    /// A literal lifted out of a `QueryRequest`, by its index in the literals of the query.
+    Literal {
+        index: usize,
+        encoding: LiteralEncoding,
+    },
+}
+
+/// How a literal lifted out of a request is bound as a parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiteralEncoding {
+    /// A string, bound as it is.
+    String,
+    /// A number, bound as a float.
+    Float64,
+    /// A boolean.
+    Bool,
+    /// Any value, bound as its JSON text.
+    Json,
 }
 
 /// A DDL statement.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DDL(pub SQL);
 
 /// A statement.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Statement(pub SQL);
 
 impl SQL {
@@ -43,20 +61,11 @@ impl SQL {
     pub fn append_syntax(&mut self, sql: &str) {
         self.sql.push_str(sql);
     }
-    /// Append a SQL identifier like a column or a table name, which will be
-    /// inserted surrounded by quotes
-    pub fn append_identifier(&mut self, sql: &String) {
-        // todo: sanitize
-        self.sql.push_str(sql.to_string().as_str());
-    }
-    /// Append a parameter to a parameterized query. Will be represented as $1, $2, and so on,
-    /// in the sql query text, and will be inserted to the `params` vector, so we can
-    /// bind them later when we run the query.
-    pub fn append_param(&mut self, param: Param) {
-        // we want the postgres param to start from 1
-        // so we first push the param and then check the length of the vector.
+    /// Append a parameter to a parameterized query, and return its position, starting
+    /// from 1. The parameter's placeholder is written by the SQL dialect, so we can
+    /// bind the parameters later when we run the query.
+    pub fn push_param(&mut self, param: Param) -> usize {
         self.params.push(param);
-        self.sql
-            .push_str(format!("@param{}", self.params.len()).as_str());
+        self.params.len()
     }
 }
@@ -54,6 +54,11 @@ pub enum Error {
         field_name: models::FieldName,
         actual_type: Type,
     },
+    PartitionFilterRequired {
+        collection: models::CollectionName,
+        partition_column: String,
+    },
+    InvalidRequestParameterName(String),
 }
 
 /// Capabilities we don't currently support.
@@ -199,6 +204,21 @@ impl std::fmt::Display for Error {
                     "Nested field '{field_name}' not of array type. Actual type: {actual_type:?}"
                 )
             }
+            Error::PartitionFilterRequired {
+                collection,
+                partition_column,
+            } => {
+                write!(
+                    f,
+                    "Queries on collection '{collection}' must filter on the partition column '{partition_column}'."
+                )
+            }
+            Error::InvalidRequestParameterName(name) => {
+                write!(
+                    f,
+                    "Invalid request parameter name '{name}'. Names must be identifiers, other than 'param' followed by digits."
+                )
+            }
         }
     }
 }
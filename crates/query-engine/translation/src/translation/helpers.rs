@@ -5,9 +5,34 @@ use std::collections::BTreeMap;
 use ndc_models as models;
 
 use super::error::Error;
+use super::query;
 use query_engine_metadata::metadata;
 use query_engine_sql::sql;
 
+/// The name of the argument table collections accept for time-travel reads.
+pub const AS_OF_ARGUMENT: &str = "as_of";
+
+/// The scalar type of the `as_of` argument.
+pub const TIMESTAMP_SCALAR_TYPE: &str = "timestamp";
+
+/// The names of the arguments wildcard table collections accept to restrict
+/// the shards they read, by `_TABLE_SUFFIX`.
+pub const SUFFIX_FROM_ARGUMENT: &str = "suffix_from";
+pub const SUFFIX_TO_ARGUMENT: &str = "suffix_to";
+
+/// The scalar type of the `suffix_from` and `suffix_to` arguments.
+pub const STRING_SCALAR_TYPE: &str = "string";
+
+/// The scalar type of procedure results.
+pub const JSON_SCALAR_TYPE: &str = "json";
+
+/// The name of the argument the engine forwards headers in, as an object of header names to
+/// values. Request parameters can be taken from these headers.
+pub const HEADERS_ARGUMENT: &str = "headers";
+
+/// The column holding the result of a function, as required by the ndc spec.
+pub const FUNCTION_RESULT_COLUMN: &str = "__value";
+
 #[derive(Debug)]
 /// Static information from the query and metadata.
 pub struct Env<'request> {
@@ -22,6 +47,16 @@ pub struct Env<'request> {
 pub struct State {
     native_queries: NativeQueries,
     global_table_index: TableAliasIndex,
+    lifted_literals: LiftedLiterals,
+}
+
+#[derive(Debug, Default)]
+/// The literals lifted out of the request, which the markers in the request stand for.
+struct LiftedLiterals {
+    literals: Vec<serde_json::Value>,
+    /// Whether some literals were translated as constants rather than as parameters, in
+    /// which case the plan only holds for these literals.
+    pinned: bool,
 }
 
 #[derive(Debug)]
@@ -88,6 +123,14 @@ pub enum CollectionInfo<'env> {
         name: &'env models::CollectionName,
         info: &'env metadata::NativeQueryInfo,
     },
+    TableFunction {
+        name: &'env models::CollectionName,
+        info: &'env metadata::TableFunctionInfo,
+    },
+    ScalarFunction {
+        name: &'env models::CollectionName,
+        info: &'env metadata::ScalarFunctionInfo,
+    },
 }
 
 #[derive(Debug)]
@@ -101,6 +144,14 @@ pub enum FieldsInfo<'env> {
         name: &'env models::CollectionName,
         info: &'env metadata::NativeQueryInfo,
     },
+    TableFunction {
+        name: &'env models::CollectionName,
+        info: &'env metadata::TableFunctionInfo,
+    },
+    ScalarFunction {
+        name: &'env models::CollectionName,
+        info: &'env metadata::ScalarFunctionInfo,
+    },
 }
 
 impl<'a> From<&'a CollectionInfo<'a>> for FieldsInfo<'a> {
@@ -108,6 +159,12 @@ impl<'a> From<&'a CollectionInfo<'a>> for FieldsInfo<'a> {
         match value {
             CollectionInfo::Table { name, info } => FieldsInfo::Table { name, info },
             CollectionInfo::NativeQuery { name, info } => FieldsInfo::NativeQuery { name, info },
+            CollectionInfo::TableFunction { name, info } => {
+                FieldsInfo::TableFunction { name, info }
+            }
+            CollectionInfo::ScalarFunction { name, info } => {
+                FieldsInfo::ScalarFunction { name, info }
+            }
         }
     }
 }
@@ -198,6 +255,26 @@ impl<'request> Env<'request> {
                         name: type_name,
                         info: nq,
                     })
+            })
+            .or_else(|| {
+                self.metadata
+                    .routines
+                    .table_functions
+                    .get(type_name)
+                    .map(|tf| FieldsInfo::TableFunction {
+                        name: type_name,
+                        info: tf,
+                    })
+            })
+            .or_else(|| {
+                self.metadata
+                    .routines
+                    .scalar_functions
+                    .get(type_name.as_str())
+                    .map(|sf| FieldsInfo::ScalarFunction {
+                        name: type_name,
+                        info: sf,
+                    })
             });
 
         info.ok_or(Error::CollectionNotFound(type_name.as_str().into()))
@@ -244,6 +321,26 @@ impl<'request> Env<'request> {
                         name: collection_name,
                         info: nq,
                     })
+                    .or_else(|| {
+                        self.metadata
+                            .routines
+                            .table_functions
+                            .get(collection_name)
+                            .map(|tf| CollectionInfo::TableFunction {
+                                name: collection_name,
+                                info: tf,
+                            })
+                    })
+                    .or_else(|| {
+                        self.metadata
+                            .routines
+                            .scalar_functions
+                            .get(collection_name.as_str())
+                            .map(|sf| CollectionInfo::ScalarFunction {
+                                name: collection_name,
+                                info: sf,
+                            })
+                    })
                     .ok_or(Error::CollectionNotFound(collection_name.clone()))
             }
         }
@@ -262,6 +359,18 @@ impl<'request> Env<'request> {
             .ok_or(Error::ProcedureNotFound(procedure_name.clone()))
     }
 
+    /// Lookup a stored procedure's information in the metadata.
+    pub fn lookup_procedure(
+        &self,
+        procedure_name: &models::ProcedureName,
+    ) -> Result<&'request metadata::ProcedureInfo, Error> {
+        self.metadata
+            .routines
+            .procedures
+            .get(procedure_name)
+            .ok_or(Error::ProcedureNotFound(procedure_name.clone()))
+    }
+
     pub fn lookup_relationship(
         &self,
         name: &models::RelationshipName,
@@ -346,6 +455,29 @@ impl FieldsInfo<'_> {
                 .ok_or_else(|| {
                     Error::ColumnNotFoundInCollection(column_name.clone(), name.as_str().into())
                 }),
+            FieldsInfo::TableFunction { name, info } => info
+                .columns
+                .get(column_name)
+                .map(|column_info| ColumnInfo {
+                    name: sql::ast::ColumnName(column_info.name.clone()),
+                    r#type: column_info.r#type.clone(),
+                })
+                .ok_or_else(|| {
+                    Error::ColumnNotFoundInCollection(column_name.clone(), (*name).clone())
+                }),
+            FieldsInfo::ScalarFunction { name, info } => {
+                if column_name.as_str() == FUNCTION_RESULT_COLUMN {
+                    Ok(ColumnInfo {
+                        name: sql::ast::ColumnName(FUNCTION_RESULT_COLUMN.to_string()),
+                        r#type: info.return_type.clone(),
+                    })
+                } else {
+                    Err(Error::ColumnNotFoundInCollection(
+                        column_name.clone(),
+                        (*name).clone(),
+                    ))
+                }
+            }
         }
     }
 }
@@ -362,6 +494,7 @@ impl Default for State {
         State {
             native_queries: NativeQueries::new(),
             global_table_index: TableAliasIndex(0),
+            lifted_literals: LiftedLiterals::default(),
         }
     }
 }
@@ -372,6 +505,66 @@ impl State {
         State::default()
     }
 
+    /// Build a new state for a request whose literals were lifted.
+    pub fn with_lifted_literals(literals: Vec<serde_json::Value>) -> State {
+        State {
+            lifted_literals: LiftedLiterals {
+                literals,
+                pinned: false,
+            },
+            ..State::default()
+        }
+    }
+
+    /// The index and value of the literal a value is the marker of, if it is one.
+    pub fn lifted_literal<'a>(
+        &'a self,
+        value: &serde_json::Value,
+    ) -> Option<(usize, &'a serde_json::Value)> {
+        let index = query::literals::marker_index(value)?;
+        self.lifted_literals
+            .literals
+            .get(index)
+            .map(|literal| (index, literal))
+    }
+
+    /// Replace the markers in a value with the literals they stand for, for values which are
+    /// translated as constants. The plan then only holds for these literals.
+    pub fn restore_lifted_literals(&mut self, value: &serde_json::Value) -> serde_json::Value {
+        if let Some((_, literal)) = self.lifted_literal(value) {
+            let literal = literal.clone();
+            self.lifted_literals.pinned = true;
+            return literal;
+        }
+        match value {
+            serde_json::Value::Array(values) => serde_json::Value::Array(
+                values
+                    .iter()
+                    .map(|value| self.restore_lifted_literals(value))
+                    .collect(),
+            ),
+            serde_json::Value::Object(object) => serde_json::Value::Object(
+                object
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.restore_lifted_literals(value)))
+                    .collect(),
+            ),
+            _ => value.clone(),
+        }
+    }
+
+    /// Whether literals were lifted out of the request.
+    pub fn has_lifted_literals(&self) -> bool {
+        !self.lifted_literals.literals.is_empty()
+    }
+
+    /// Take the literals lifted out of the request, and whether the plan holds for any
+    /// literals rather than only for these.
+    pub fn take_lifted_literals(&mut self) -> (Vec<serde_json::Value>, bool) {
+        let lifted_literals = std::mem::take(&mut self.lifted_literals);
+        (lifted_literals.literals, !lifted_literals.pinned)
+    }
+
     /// When variables are passed to the query, create an alias for the variables table and
     /// a from clause.
     pub fn make_variables_table(
@@ -469,7 +662,8 @@ impl TableAliasIndex {
     pub fn make_table_alias(&mut self, name: String) -> sql::ast::TableAlias {
         sql::ast::TableAlias {
             unique_index: self.next_global_table_index().0,
-            name,
+            // wildcard collection names (`events_*`) are not valid in aliases.
+            name: name.replace('*', ""),
         }
     }
 }
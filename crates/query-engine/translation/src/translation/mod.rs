@@ -2,4 +2,5 @@
 
 pub mod error;
 pub mod helpers;
+pub mod mutation;
 pub mod query;
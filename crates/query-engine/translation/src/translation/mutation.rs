@@ -0,0 +1,79 @@
+//! Translate an incoming `MutationRequest`.
+//!
+//! Mutations are calls to stored procedures:
+//!
+//! ```sql
+//! CALL dataset.archive_orders(cast(@param1 as date))
+//! ```
+
+use std::collections::BTreeMap;
+
+use ndc_models as models;
+
+use crate::translation::error::Error;
+use crate::translation::helpers::{Env, State};
+use crate::translation::query::routines;
+use query_engine_metadata::metadata;
+use query_engine_sql::sql;
+
+/// Translate the incoming MutationRequest to an ExecutionPlan (SQL) to be run against the database.
+pub fn translate(
+    metadata: &metadata::Metadata,
+    mutation_request: models::MutationRequest,
+) -> Result<sql::execution_plan::ExecutionPlan<Vec<sql::execution_plan::Mutation>>, Error> {
+    let env = Env::new(metadata, mutation_request.collection_relationships, None);
+
+    let mutations = mutation_request
+        .operations
+        .into_iter()
+        .map(|operation| match operation {
+            models::MutationOperation::Procedure {
+                name,
+                arguments,
+                fields,
+            } => translate_procedure(&env, name, &arguments, fields.as_ref()),
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(sql::execution_plan::simple_mutations_execution_plan(
+        mutations,
+    ))
+}
+
+/// Translate a procedure operation to a CALL statement.
+fn translate_procedure(
+    env: &Env,
+    procedure: models::ProcedureName,
+    arguments: &BTreeMap<models::ArgumentName, serde_json::Value>,
+    fields: Option<&models::NestedField>,
+) -> Result<sql::execution_plan::Mutation, Error> {
+    let info = env.lookup_procedure(&procedure)?;
+
+    // the result of a procedure is plain json, so there is nothing to select from.
+    if fields.is_some() {
+        return Err(Error::UnexpectedStructure(format!(
+            "field selection on the result of procedure '{procedure}'"
+        )));
+    }
+
+    let arguments = arguments
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.clone(),
+                models::Argument::Literal {
+                    value: value.clone(),
+                },
+            )
+        })
+        .collect();
+
+    let mut state = State::new();
+    let call = sql::ast::Call {
+        schema: sql::ast::SchemaName(info.schema_name.clone()),
+        procedure: sql::ast::RoutineName(info.procedure_name.clone()),
+        arguments: routines::translate_arguments(env, &mut state, &info.arguments, &arguments)?,
+    };
+
+    Ok(sql::execution_plan::Mutation { procedure, call })
+}
@@ -4,14 +4,19 @@ use indexmap::IndexMap;
 
 use ndc_sdk::models;
 
+use super::fields;
 use crate::translation::error::Error;
+use crate::translation::helpers::{Env, TableNameAndReference};
+use query_engine_metadata::metadata;
 use query_engine_sql::sql;
 
 /// Translate any aggregates we should include in the query into our SQL AST.
 pub fn translate(
-    table: &sql::ast::TableReference,
+    env: &Env,
+    current_table: &TableNameAndReference,
     aggregates: &IndexMap<models::FieldName, models::Aggregate>,
 ) -> Result<Vec<(sql::ast::ColumnAlias, sql::ast::Expression)>, Error> {
+    let table = &current_table.reference;
     aggregates
         .into_iter()
         .map(|(alias, aggregation)| {
@@ -42,15 +47,24 @@ pub fn translate(
                     column,
                     function,
                     field_path: _,
-                } => sql::ast::Expression::FunctionCall {
-                    function: sql::ast::Function::Unknown(function.to_string()),
-                    args: vec![sql::ast::Expression::ColumnReference(
-                        sql::ast::ColumnReference::AliasedColumn {
-                            table: table.clone(),
-                            column: sql::helpers::make_column_alias(column.to_string()),
-                        },
-                    )],
-                },
+                } => {
+                    let expression = sql::ast::Expression::FunctionCall {
+                        function: sql::ast::Function::Unknown(function.to_string()),
+                        args: vec![sql::ast::Expression::ColumnReference(
+                            sql::ast::ColumnReference::AliasedColumn {
+                                table: table.clone(),
+                                column: sql::helpers::make_column_alias(column.to_string()),
+                            },
+                        )],
+                    };
+                    // the result is represented like the other values of its type.
+                    let return_type = aggregate_return_type(env, current_table, column, function)?;
+                    fields::wrap_in_type_representation(
+                        expression,
+                        return_type
+                            .and_then(|return_type| env.lookup_type_representation(&return_type)),
+                    )
+                }
                 models::Aggregate::StarCount {} => {
                     sql::ast::Expression::Count(sql::ast::CountType::Star)
                 }
@@ -62,3 +76,23 @@ pub fn translate(
         })
         .collect::<Result<Vec<_>, Error>>()
 }
+
+/// The scalar type returned by an aggregate function applied to a column, if we know it.
+fn aggregate_return_type(
+    env: &Env,
+    current_table: &TableNameAndReference,
+    column: &models::FieldName,
+    function: &models::AggregateFunctionName,
+) -> Result<Option<models::ScalarTypeName>, Error> {
+    let column_info = env
+        .lookup_collection(&current_table.name)?
+        .lookup_column(column)?;
+    let metadata::Type::ScalarType(scalar_type) = column_info.r#type else {
+        return Ok(None);
+    };
+    Ok(env
+        .lookup_scalar_type(&scalar_type)?
+        .aggregate_functions
+        .get(function)
+        .map(|aggregate_function| aggregate_function.return_type.as_str().into()))
+}
@@ -178,7 +178,7 @@ fn wrap_array_in_type_representation(
 /// Certain type representations require that we provide a different json representation
 /// than what postgres will return.
 /// For columns of those type representation, we wrap the result in a cast.
-fn wrap_in_type_representation(
+pub(crate) fn wrap_in_type_representation(
     expression: sql::ast::Expression,
     column_type_representation: Option<&TypeRepresentation>,
 ) -> sql::ast::Expression {
@@ -205,7 +205,7 @@ fn get_type_representation_cast_type(
         // In these situations, we expect to cast the expression according
         // to the type representation.
         TypeRepresentation::Int64AsString | TypeRepresentation::BigDecimalAsString => {
-            Some(sql::helpers::text_type_name())
+            Some(sql::helpers::string_type_name())
         }
 
         // In these situations the type representation should be the same as
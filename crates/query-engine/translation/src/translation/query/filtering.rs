@@ -47,6 +47,40 @@ pub fn translate_expression(
     Ok(filter)
 }
 
+/// Check whether a boolean expression is guaranteed to filter on one of the given columns of
+/// the current collection, i.e. whether every row it admits has passed a comparison on them.
+/// Only comparisons of the columns themselves count: a negated comparison, or a comparison of
+/// a field nested in a column, does not.
+///
+/// This is used to detect queries which lack a predicate on a partition column.
+pub fn filters_on_column(predicate: &models::Expression, columns: &[&models::FieldName]) -> bool {
+    match predicate {
+        models::Expression::And { expressions } => expressions
+            .iter()
+            .any(|expression| filters_on_column(expression, columns)),
+        models::Expression::Or { expressions } => {
+            !expressions.is_empty()
+                && expressions
+                    .iter()
+                    .all(|expression| filters_on_column(expression, columns))
+        }
+        models::Expression::UnaryComparisonOperator { column, .. }
+        | models::Expression::BinaryComparisonOperator { column, .. } => match column {
+            models::ComparisonTarget::Column {
+                name,
+                path,
+                field_path,
+            } => {
+                path.is_empty()
+                    && field_path.as_deref().unwrap_or_default().is_empty()
+                    && columns.contains(&name)
+            }
+            models::ComparisonTarget::RootCollectionColumn { .. } => false,
+        },
+        models::Expression::Not { .. } | models::Expression::Exists { .. } => false,
+    }
+}
+
 /// Translate a boolean expression to a SQL expression and also provide all of the joins necessary
 /// for the execution.
 pub fn translate_expression_with_joins(
@@ -725,6 +759,7 @@ fn make_unnest_subquery(
     let subquery_from = sql::ast::From::Unnest {
         expression,
         column: sql::helpers::make_column_alias("value".to_string()),
+        offset: None,
         alias: subquery_alias,
     };
     let mut subquery = sql::helpers::simple_select(vec![sql::helpers::make_column(
@@ -0,0 +1,88 @@
+//! Lift the literals out of a request, so that the plan of a request can be reused for other
+//! requests of the same shape.
+//!
+//! The scalar values of comparisons are replaced by markers naming their index and their kind.
+//! A request whose literals were lifted is the shape of the request: requests which differ only
+//! by the values of their comparisons have the same shape. When a request whose literals were
+//! lifted is translated, each marker is translated to a parameter bound to its literal.
+
+use ndc_models as models;
+
+/// The prefix of the markers replacing lifted literals. It starts with a NUL character, which
+/// requests do not contain in practice.
+const MARKER_PREFIX: &str = "\u{0}literal:";
+
+/// Replace the scalar values of the comparisons of a request with markers, and return the
+/// values, in the order of their markers. The elements of arrays are replaced one by one, as
+/// `_in` comparisons translate each of them. Null values are left in place, as they are
+/// translated differently. Returns nothing if the request already contains a marker.
+pub fn lift(request: &mut models::QueryRequest) -> Option<Vec<serde_json::Value>> {
+    let mut literals = vec![];
+    lift_from_query(&mut request.query, &mut literals).then_some(literals)
+}
+
+/// The index of the literal a value is the marker of, if it is one.
+pub(crate) fn marker_index(value: &serde_json::Value) -> Option<usize> {
+    let (index, _kind) = value
+        .as_str()?
+        .strip_prefix(MARKER_PREFIX)?
+        .split_once(':')?;
+    index.parse().ok()
+}
+
+fn lift_from_query(query: &mut models::Query, literals: &mut Vec<serde_json::Value>) -> bool {
+    let lifted_from_fields = query
+        .fields
+        .iter_mut()
+        .flat_map(|fields| fields.values_mut())
+        .all(|field| match field {
+            models::Field::Relationship { query, .. } => lift_from_query(query, literals),
+            models::Field::Column { .. } => true,
+        });
+    lifted_from_fields
+        && query
+            .predicate
+            .as_mut()
+            .map_or(true, |predicate| lift_from_expression(predicate, literals))
+}
+
+fn lift_from_expression(
+    expression: &mut models::Expression,
+    literals: &mut Vec<serde_json::Value>,
+) -> bool {
+    match expression {
+        models::Expression::And { expressions } | models::Expression::Or { expressions } => {
+            expressions
+                .iter_mut()
+                .all(|expression| lift_from_expression(expression, literals))
+        }
+        models::Expression::Not { expression } => lift_from_expression(expression, literals),
+        models::Expression::Exists { predicate, .. } => predicate
+            .as_mut()
+            .map_or(true, |predicate| lift_from_expression(predicate, literals)),
+        models::Expression::UnaryComparisonOperator { .. } => true,
+        models::Expression::BinaryComparisonOperator { value, .. } => match value {
+            models::ComparisonValue::Scalar { value } => lift_value(value, literals),
+            models::ComparisonValue::Column { .. } | models::ComparisonValue::Variable { .. } => {
+                true
+            }
+        },
+    }
+}
+
+/// Replace a scalar value with a marker, and the elements of an array of values with markers.
+fn lift_value(value: &mut serde_json::Value, literals: &mut Vec<serde_json::Value>) -> bool {
+    let kind = match value {
+        serde_json::Value::String(string) if string.starts_with(MARKER_PREFIX) => return false,
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Array(values) => {
+            return values.iter_mut().all(|value| lift_value(value, literals))
+        }
+        serde_json::Value::Null | serde_json::Value::Object(_) => return true,
+    };
+    let marker = serde_json::Value::String(format!("{MARKER_PREFIX}{}:{kind}", literals.len()));
+    literals.push(std::mem::replace(value, marker));
+    true
+}
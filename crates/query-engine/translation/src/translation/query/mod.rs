@@ -3,9 +3,12 @@
 mod aggregates;
 pub mod fields;
 pub mod filtering;
+pub mod literals;
 pub mod native_queries;
 pub mod relationships;
+pub mod request_parameters;
 pub mod root;
+pub mod routines;
 mod sorting;
 pub mod values;
 
@@ -21,7 +24,45 @@ pub fn translate(
     metadata: &metadata::Metadata,
     query_request: models::QueryRequest,
 ) -> Result<sql::execution_plan::ExecutionPlan<sql::execution_plan::Query>, Error> {
-    let mut state = State::new();
+    translate_with_state(metadata, query_request, State::new()).map(|(plan, _)| plan)
+}
+
+/// Translate a QueryRequest whose literals were lifted by `literals::lift`. The literals are
+/// bound as parameters, so that the plan can run with the literals of any request of the same
+/// shape. Also returns whether it can: it can not when some literals had to be translated as
+/// constants.
+pub fn translate_lifted(
+    metadata: &metadata::Metadata,
+    query_request: models::QueryRequest,
+    literals: Vec<serde_json::Value>,
+) -> Result<
+    (
+        sql::execution_plan::ExecutionPlan<sql::execution_plan::Query>,
+        bool,
+    ),
+    Error,
+> {
+    translate_with_state(
+        metadata,
+        query_request,
+        State::with_lifted_literals(literals),
+    )
+}
+
+fn translate_with_state(
+    metadata: &metadata::Metadata,
+    query_request: models::QueryRequest,
+    mut state: State,
+) -> Result<
+    (
+        sql::execution_plan::ExecutionPlan<sql::execution_plan::Query>,
+        bool,
+    ),
+    Error,
+> {
+    let request_parameters =
+        request_parameters::resolve(&metadata.request_parameters, &query_request.arguments)?;
+
     let variables_from = state.make_variables_table(&query_request.variables);
     let variables_table_ref = variables_from.clone().map(|(_, table_ref)| table_ref);
     let env = Env::new(
@@ -31,6 +72,9 @@ pub fn translate(
         variables_table_ref,
     );
 
+    // fail early rather than letting BigQuery reject the query.
+    root::check_partition_filter(&env, &query_request.collection, &query_request.query)?;
+
     let (returns_field, select_set) = root::translate_query(
         &env,
         &mut state,
@@ -42,9 +86,17 @@ pub fn translate(
         &query_request.query,
     )?;
 
+    // when the rows are made of plain columns, they can also be selected as typed columns.
+    let typed_rows = match &select_set {
+        sql::helpers::SelectSet::Rows(row_select) if query_request.variables.is_none() => {
+            typed_row_fields(&query_request.query).map(|fields| (fields, row_select.clone()))
+        }
+        _ => None,
+    };
+
     // form a single JSON item shaped `{ rows: [], aggregates: {} }`
     // that matches the models::RowSet type
-    let json_select = sql::helpers::select_rowset(
+    let mut json_select = sql::helpers::select_rowset(
         (
             state.make_table_alias("universe".to_string()),
             sql::helpers::make_column_alias("universe".to_string()),
@@ -59,26 +111,69 @@ pub fn translate(
         ),
         &variables_from,
         // &state.make_table_alias("universe_agg".to_string()),
-        // native queries if there are any
-        // sql::ast::With {
-        //     common_table_expressions: {
-        //         let (ctes, mut global_table_index) = native_queries::translate(&env, state)?;
-        //         // wrap ctes in another cte to guard against mutations in queries
-        //         ctes.into_iter()
-        //             .map(|cte| native_queries::wrap_cte_in_cte(&mut global_table_index, cte))
-        //             .collect()
-        //     },
-        // },
         select_set,
         &returns_field,
     );
 
+    let (literals, reusable) = state.take_lifted_literals();
+
+    // native queries are defined as common table expressions. With variables, the rowset is
+    // selected once for each set of variables, in a correlated subquery: the native queries
+    // which refer to the variables are defined in it, and the others once for the whole query.
+    let (native_queries, _) = native_queries::translate(&env, state)?;
+    let with = sql::ast::With {
+        common_table_expressions: native_queries.global,
+    };
+
+    json_select.with = sql::ast::With {
+        common_table_expressions: native_queries.per_variable_set,
+    };
+    if let Some(variables) = variables_from {
+        json_select = sql::helpers::select_rowset_for_each_variable_set(
+            json_select,
+            sql::helpers::make_column_alias("universe".to_string()),
+            variables,
+        );
+    }
+    json_select
+        .with
+        .common_table_expressions
+        .splice(0..0, with.common_table_expressions.iter().cloned());
+
     // normalize ast
     let json_select = sql::rewrites::constant_folding::normalize_select(json_select);
 
-    Ok(sql::execution_plan::simple_query_execution_plan(
+    let typed_rows = typed_rows.map(|(fields, mut row_select)| {
+        row_select.with = with;
+        sql::execution_plan::TypedRows {
+            fields,
+            query: sql::rewrites::constant_folding::normalize_select(row_select),
+        }
+    });
+
+    let mut plan = sql::execution_plan::simple_query_execution_plan(
         query_request.variables,
+        request_parameters,
         query_request.collection,
         json_select,
-    ))
+        typed_rows,
+    );
+    plan.query.literals = literals;
+    Ok((plan, reusable))
+}
+
+/// The fields of the rows requested by a query, if they are all plain columns.
+fn typed_row_fields(query: &models::Query) -> Option<Vec<models::FieldName>> {
+    let fields = query.fields.as_ref().filter(|fields| !fields.is_empty())?;
+    fields
+        .iter()
+        .map(|(name, field)| match field {
+            models::Field::Column {
+                fields: None,
+                arguments,
+                ..
+            } if arguments.is_empty() => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
 }
@@ -9,12 +9,23 @@ use crate::translation::helpers::{Env, State, TableAliasIndex};
 use query_engine_metadata::metadata;
 use query_engine_sql::sql;
 
+/// The CTEs of the native queries of a query.
+pub struct CommonTableExpressions {
+    /// The native queries which do not refer to variables, defined once for the whole query.
+    pub global: Vec<sql::ast::CommonTableExpression>,
+    /// The native queries which refer to variables, defined for each set of variables.
+    pub per_variable_set: Vec<sql::ast::CommonTableExpression>,
+}
+
 /// Translate native queries collected in State by the translation proccess into CTEs.
 pub fn translate(
     env: &Env,
     state: State,
-) -> Result<(Vec<sql::ast::CommonTableExpression>, TableAliasIndex), Error> {
-    let mut ctes = vec![];
+) -> Result<(CommonTableExpressions, TableAliasIndex), Error> {
+    let mut ctes = CommonTableExpressions {
+        global: vec![],
+        per_variable_set: vec![],
+    };
     let variables_table = env.get_variables_table();
     let (native_queries, global_table_index) = state.get_native_queries_and_global_index();
 
@@ -31,6 +42,7 @@ pub fn translate(
 
     // for each found table expression
     for native_query in native_queries {
+        let mut refers_to_variables = false;
         // convert metadata representation to sql::ast representation
         let sql: Vec<sql::ast::RawSql> = native_query
             .info
@@ -41,6 +53,20 @@ pub fn translate(
             .into_iter()
             .map(|part| match part {
                 metadata::NativeQueryPart::Text(text) => Ok(sql::ast::RawSql::RawText(text)),
+                // request parameters are bound by name to the whole query.
+                metadata::NativeQueryPart::Parameter(param)
+                    if !native_query
+                        .info
+                        .arguments
+                        .contains_key(models::ArgumentName::ref_cast(&param))
+                        && env
+                            .metadata
+                            .request_parameters
+                            .0
+                            .contains_key(param.as_str()) =>
+                {
+                    Ok(sql::ast::RawSql::RawText(format!("@{param}")))
+                }
                 metadata::NativeQueryPart::Parameter(param) => {
                     let typ = match native_query
                         .info
@@ -64,13 +90,16 @@ pub fn translate(
                             ),
                             models::Argument::Variable { name } => match &variables_table {
                                 Err(err) => Err(err.clone()),
-                                Ok(variables_table) => values::translate_variable(
-                                    env,
-                                    &mut translation_state,
-                                    variables_table.clone(),
-                                    name,
-                                    &typ,
-                                ),
+                                Ok(variables_table) => {
+                                    refers_to_variables = true;
+                                    values::translate_variable(
+                                        env,
+                                        &mut translation_state,
+                                        variables_table.clone(),
+                                        name,
+                                        &typ,
+                                    )
+                                }
                             },
                         },
                     }?;
@@ -80,11 +109,16 @@ pub fn translate(
             .collect::<Result<Vec<sql::ast::RawSql>, Error>>()?;
 
         // add a cte
-        ctes.push(sql::ast::CommonTableExpression {
+        let cte = sql::ast::CommonTableExpression {
             alias: native_query.alias,
             column_names: None,
             select: sql::ast::CTExpr::RawSql(sql),
-        });
+        };
+        if refers_to_variables {
+            ctes.per_variable_set.push(cte);
+        } else {
+            ctes.global.push(cte);
+        }
     }
 
     Ok((ctes, global_table_index))
@@ -108,6 +142,7 @@ pub fn wrap_cte_in_cte(
         let mut select = sql::helpers::star_select(sql::ast::From::Table {
             reference: sql::ast::TableReference::AliasedTable(cte.alias.clone()),
             alias: nested_cte_alias,
+            system_time: None,
         });
         select.with = sql::ast::With {
             common_table_expressions: vec![cte],
@@ -0,0 +1,95 @@
+//! Resolve the request parameters of a query request: values taken from the arguments and the
+//! forwarded headers of the request, which are bound as named query parameters.
+
+use std::collections::BTreeMap;
+
+use ndc_models as models;
+
+use crate::translation::error::Error;
+use crate::translation::helpers::{HEADERS_ARGUMENT, JSON_SCALAR_TYPE, STRING_SCALAR_TYPE};
+use query_engine_metadata::metadata;
+
+/// Resolve the value of each request parameter from the arguments of the queried collection.
+/// A missing or null argument or header resolves to NULL, so that a query filtering on the
+/// parameter returns no rows rather than every row.
+pub fn resolve(
+    request_parameters: &metadata::RequestParameters,
+    arguments: &BTreeMap<models::ArgumentName, models::Argument>,
+) -> Result<BTreeMap<String, Option<String>>, Error> {
+    request_parameters
+        .0
+        .iter()
+        .map(|(name, parameter)| {
+            check_name(name)?;
+            let value = match parameter {
+                metadata::RequestParameter::Argument(argument) => {
+                    scalar_value(literal_value(arguments.get(argument))?)?
+                }
+                metadata::RequestParameter::Header(header) => {
+                    header_value(literal_value(arguments.get(HEADERS_ARGUMENT))?, header)?
+                }
+            };
+            Ok((name.clone(), value))
+        })
+        .collect()
+}
+
+/// Request parameters are bound by name, so their names must be valid query parameter names,
+/// and must not clash with the positional parameters we generate (`@param1`, `@param2`, ...).
+fn check_name(name: &str) -> Result<(), Error> {
+    let is_identifier = name
+        .chars()
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_');
+    let is_positional = name
+        .strip_prefix("param")
+        .is_some_and(|index| !index.is_empty() && index.chars().all(|char| char.is_ascii_digit()));
+    if is_identifier && !is_positional {
+        Ok(())
+    } else {
+        Err(Error::InvalidRequestParameterName(name.to_string()))
+    }
+}
+
+/// The value of an argument. The request parameters are the same for every set of variables,
+/// so they cannot be taken from variables.
+fn literal_value(argument: Option<&models::Argument>) -> Result<&serde_json::Value, Error> {
+    match argument {
+        None => Ok(&serde_json::Value::Null),
+        Some(models::Argument::Literal { value }) => Ok(value),
+        Some(models::Argument::Variable { .. }) => Err(Error::NotImplementedYet(
+            "request parameters taken from variables".to_string(),
+        )),
+    }
+}
+
+/// The value of a header, looked up case-insensitively in the forwarded headers.
+fn header_value(headers: &serde_json::Value, header: &str) -> Result<Option<String>, Error> {
+    match headers {
+        serde_json::Value::Null => Ok(None),
+        serde_json::Value::Object(headers) => headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(header))
+            .map_or(Ok(None), |(_, value)| scalar_value(value)),
+        _ => Err(Error::TypeMismatch(
+            headers.clone(),
+            JSON_SCALAR_TYPE.into(),
+        )),
+    }
+}
+
+/// Request parameters are bound as strings.
+fn scalar_value(value: &serde_json::Value) -> Result<Option<String>, Error> {
+    match value {
+        serde_json::Value::Null => Ok(None),
+        serde_json::Value::String(string) => Ok(Some(string.clone())),
+        serde_json::Value::Bool(_) | serde_json::Value::Number(_) => Ok(Some(value.to_string())),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => Err(Error::TypeMismatch(
+            value.clone(),
+            STRING_SCALAR_TYPE.into(),
+        )),
+    }
+}
@@ -11,13 +11,22 @@ use super::aggregates;
 use super::fields;
 use super::filtering;
 use super::relationships;
+use super::routines;
 use super::sorting;
+use super::values;
 use crate::translation::error::Error;
 use crate::translation::helpers::{
-    CollectionInfo, Env, RootAndCurrentTables, State, TableNameAndReference,
+    CollectionInfo, Env, RootAndCurrentTables, State, TableNameAndReference, AS_OF_ARGUMENT,
+    STRING_SCALAR_TYPE, SUFFIX_FROM_ARGUMENT, SUFFIX_TO_ARGUMENT, TIMESTAMP_SCALAR_TYPE,
 };
+use query_engine_metadata::metadata;
 use query_engine_sql::sql;
 
+/// The pseudo-column ingestion-time partitioned tables are partitioned by.
+const PARTITION_TIME_PSEUDO_COLUMN: &str = "_PARTITIONTIME";
+/// The date of the `_PARTITIONTIME` pseudo-column of ingestion-time partitioned tables.
+const PARTITION_DATE_PSEUDO_COLUMN: &str = "_PARTITIONDATE";
+
 /// Translate a query to sql ast.
 /// We return a select set with a SQL query for the two components - the rows and the aggregates.
 pub fn translate_query(
@@ -99,8 +108,7 @@ fn translate_aggregate_select(
             };
 
             // create all aggregate columns
-            let aggregate_columns =
-                aggregates::translate(&current_table.reference, aggregate_fields)?;
+            let aggregate_columns = aggregates::translate(env, &current_table, aggregate_fields)?;
 
             // construct a simple select with the table name, alias, and selected columns.
             let mut columns_select = sql::helpers::simple_select(aggregate_columns);
@@ -253,6 +261,105 @@ pub fn translate_query_part(
     Ok(())
 }
 
+/// Tables created with `require_partition_filter` reject queries which do not filter on their
+/// partition column. Check for such a filter on every collection a query reads before we hand
+/// it to BigQuery, so that it fails early with a clear error: the root collection, and the
+/// targets of its relationship fields and exists sub-queries, each filtered by its own predicate.
+pub fn check_partition_filter(
+    env: &Env,
+    collection_name: &models::CollectionName,
+    query: &models::Query,
+) -> Result<(), Error> {
+    check_collection_partition_filter(env, collection_name, query.predicate.as_ref())?;
+    if let Some(predicate) = &query.predicate {
+        check_exists_partition_filters(env, predicate)?;
+    }
+    for (_, field) in query.fields.iter().flatten() {
+        if let models::Field::Relationship {
+            query,
+            relationship,
+            ..
+        } = field
+        {
+            let relationship = env.lookup_relationship(relationship)?;
+            check_partition_filter(env, &relationship.target_collection, query)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check the partition filter of the collections read by the exists sub-queries of a predicate.
+fn check_exists_partition_filters(env: &Env, predicate: &models::Expression) -> Result<(), Error> {
+    match predicate {
+        models::Expression::And { expressions } | models::Expression::Or { expressions } => {
+            expressions
+                .iter()
+                .try_for_each(|expression| check_exists_partition_filters(env, expression))
+        }
+        models::Expression::Not { expression } => check_exists_partition_filters(env, expression),
+        models::Expression::Exists {
+            in_collection,
+            predicate,
+        } => {
+            // an exists without a predicate does not read its collection.
+            let Some(predicate) = predicate.as_deref() else {
+                return Ok(());
+            };
+            let collection_name = match in_collection {
+                models::ExistsInCollection::Related { relationship, .. } => {
+                    &env.lookup_relationship(relationship)?.target_collection
+                }
+                models::ExistsInCollection::Unrelated { collection, .. } => collection,
+                models::ExistsInCollection::NestedCollection { .. } => return Ok(()),
+            };
+            check_collection_partition_filter(env, collection_name, Some(predicate))?;
+            check_exists_partition_filters(env, predicate)
+        }
+        models::Expression::UnaryComparisonOperator { .. }
+        | models::Expression::BinaryComparisonOperator { .. } => Ok(()),
+    }
+}
+
+/// Check that a predicate on a collection filters on its partition column, if the collection
+/// requires it.
+///
+/// Ingestion-time partitioned tables are partitioned by the `_PARTITIONTIME` pseudo-column,
+/// which may be filtered through `_PARTITIONDATE` as well.
+fn check_collection_partition_filter(
+    env: &Env,
+    collection_name: &models::CollectionName,
+    predicate: Option<&models::Expression>,
+) -> Result<(), Error> {
+    let Ok(CollectionInfo::Table { info, .. }) = env.lookup_collection(collection_name) else {
+        return Ok(());
+    };
+    let partition_column = match &info.partition_column {
+        Some(partition_column) if info.require_partition_filter => partition_column,
+        _ => return Ok(()),
+    };
+    let partition_columns = if partition_column == PARTITION_TIME_PSEUDO_COLUMN {
+        vec![PARTITION_TIME_PSEUDO_COLUMN, PARTITION_DATE_PSEUDO_COLUMN]
+    } else {
+        vec![partition_column.as_str()]
+    };
+
+    // the partition column may be exposed under a different field name.
+    let fields: Vec<&models::FieldName> = info
+        .columns
+        .iter()
+        .filter(|(_, column_info)| partition_columns.contains(&column_info.name.as_str()))
+        .map(|(field_name, _)| field_name)
+        .collect();
+
+    match predicate {
+        Some(predicate) if filtering::filters_on_column(predicate, &fields) => Ok(()),
+        _ => Err(Error::PartitionFilterRequired {
+            collection: collection_name.clone(),
+            partition_column: partition_column.clone(),
+        }),
+    }
+}
+
 /// Create a from clause from a collection name and its reference.
 pub fn make_from_clause_and_reference(
     collection_name: &models::CollectionName,
@@ -267,7 +374,7 @@ pub fn make_from_clause_and_reference(
     };
     // find the table according to the metadata.
     let collection_info = env.lookup_collection(collection_name)?;
-    let from_clause = make_from_clause(state, &collection_alias, &collection_info, arguments);
+    let from_clause = make_from_clause(env, state, &collection_alias, &collection_info, arguments)?;
 
     let collection_alias_name = sql::ast::TableReference::AliasedTable(collection_alias);
     let current_table = TableNameAndReference {
@@ -280,29 +387,185 @@ pub fn make_from_clause_and_reference(
 /// Build a FROM clause from a collection info and an alias.
 /// Will add a Native Query to the 'State' if the collection is a native query.
 fn make_from_clause(
+    env: &Env,
     state: &mut State,
     current_table_alias: &sql::ast::TableAlias,
     collection_info: &CollectionInfo,
     arguments: &BTreeMap<models::ArgumentName, models::Argument>,
-) -> sql::ast::From {
+) -> Result<sql::ast::From, Error> {
     match collection_info {
         CollectionInfo::Table { info, .. } => {
             let db_table = sql::ast::TableReference::DBTable {
                 schema: sql::ast::SchemaName(info.schema_name.clone()),
                 table: sql::ast::TableName(info.table_name.clone()),
             };
-            sql::ast::From::Table {
+            if info.is_wildcard {
+                return make_wildcard_from_clause(
+                    env,
+                    state,
+                    current_table_alias,
+                    db_table,
+                    arguments,
+                );
+            }
+            let system_time = match arguments.get(AS_OF_ARGUMENT) {
+                None => None,
+                Some(argument) => translate_as_of_argument(env, state, argument)?,
+            };
+            Ok(sql::ast::From::Table {
                 reference: db_table,
                 alias: current_table_alias.clone(),
-            }
+                system_time,
+            })
         }
         CollectionInfo::NativeQuery { name, info } => {
             let aliased_table = state.insert_native_query(name, (*info).clone(), arguments.clone());
-            sql::ast::From::Table {
+            Ok(sql::ast::From::Table {
                 reference: aliased_table,
                 alias: current_table_alias.clone(),
-            }
+                system_time: None,
+            })
         }
+        CollectionInfo::TableFunction { info, .. } => routines::make_table_function_from_clause(
+            env,
+            state,
+            current_table_alias,
+            info,
+            arguments,
+        ),
+        CollectionInfo::ScalarFunction { info, .. } => routines::make_scalar_function_from_clause(
+            env,
+            state,
+            current_table_alias,
+            info,
+            arguments,
+        ),
+    }
+}
+
+/// Translate the `as_of` argument of a table collection to a timestamp expression
+/// for use in a `FOR SYSTEM_TIME AS OF` clause. A null literal means "read the current state".
+fn translate_as_of_argument(
+    env: &Env,
+    state: &mut State,
+    argument: &models::Argument,
+) -> Result<Option<sql::ast::Expression>, Error> {
+    match argument {
+        models::Argument::Literal {
+            value: serde_json::Value::Null,
+        } => Ok(None),
+        models::Argument::Literal {
+            value: serde_json::Value::String(timestamp),
+        } => Ok(Some(sql::ast::Expression::Cast {
+            expression: Box::new(sql::ast::Expression::Value(sql::ast::Value::String(
+                timestamp.clone(),
+            ))),
+            r#type: sql::helpers::timestamp_type(),
+        })),
+        models::Argument::Literal { value } => Err(Error::TypeMismatch(
+            value.clone(),
+            TIMESTAMP_SCALAR_TYPE.into(),
+        )),
+        models::Argument::Variable { name } => values::translate_variable(
+            env,
+            state,
+            env.get_variables_table()?,
+            name,
+            &metadata::Type::ScalarType(TIMESTAMP_SCALAR_TYPE.into()),
+        )
+        .map(Some),
+    }
+}
+
+/// Build a FROM clause for a wildcard table, restricting the shards we read according to the
+/// `suffix_from` and `suffix_to` arguments:
+///
+/// ```sql
+/// FROM (
+///   SELECT * FROM `project.dataset.events_*` AS events_1
+///   WHERE events_1._TABLE_SUFFIX BETWEEN @param1 AND @param2
+/// ) AS events_0
+/// ```
+fn make_wildcard_from_clause(
+    env: &Env,
+    state: &mut State,
+    current_table_alias: &sql::ast::TableAlias,
+    db_table: sql::ast::TableReference,
+    arguments: &BTreeMap<models::ArgumentName, models::Argument>,
+) -> Result<sql::ast::From, Error> {
+    let shards_alias = state.make_table_alias(current_table_alias.name.clone());
+    let table_suffix = Box::new(sql::ast::Expression::ColumnReference(
+        sql::ast::ColumnReference::TableColumn {
+            table: sql::ast::TableReference::AliasedTable(shards_alias.clone()),
+            name: sql::ast::ColumnName("_TABLE_SUFFIX".to_string()),
+        },
+    ));
+
+    let suffix_from =
+        translate_table_suffix_argument(env, state, arguments.get(SUFFIX_FROM_ARGUMENT))?;
+    let suffix_to = translate_table_suffix_argument(env, state, arguments.get(SUFFIX_TO_ARGUMENT))?;
+
+    let filter = match (suffix_from, suffix_to) {
+        (None, None) => sql::helpers::true_expr(),
+        (Some(low), None) => sql::ast::Expression::BinaryOperation {
+            left: table_suffix,
+            operator: sql::ast::BinaryOperator(">=".to_string()),
+            right: Box::new(low),
+        },
+        (None, Some(high)) => sql::ast::Expression::BinaryOperation {
+            left: table_suffix,
+            operator: sql::ast::BinaryOperator("<=".to_string()),
+            right: Box::new(high),
+        },
+        (Some(low), Some(high)) => sql::ast::Expression::Between {
+            expression: table_suffix,
+            low: Box::new(low),
+            high: Box::new(high),
+        },
+    };
+
+    let mut shards_select = sql::helpers::star_select(sql::ast::From::Table {
+        reference: db_table,
+        alias: shards_alias,
+        system_time: None,
+    });
+    shards_select.where_ = sql::ast::Where(filter);
+
+    Ok(sql::ast::From::Select {
+        select: Box::new(shards_select),
+        alias: current_table_alias.clone(),
+    })
+}
+
+/// Translate a `suffix_from` or `suffix_to` argument of a wildcard table collection.
+/// A missing argument or a null literal means the range is unbounded on that side.
+fn translate_table_suffix_argument(
+    env: &Env,
+    state: &mut State,
+    argument: Option<&models::Argument>,
+) -> Result<Option<sql::ast::Expression>, Error> {
+    match argument {
+        None
+        | Some(models::Argument::Literal {
+            value: serde_json::Value::Null,
+        }) => Ok(None),
+        Some(models::Argument::Literal {
+            value: serde_json::Value::String(suffix),
+        }) => Ok(Some(sql::ast::Expression::Value(sql::ast::Value::String(
+            suffix.clone(),
+        )))),
+        Some(models::Argument::Literal { value }) => Err(Error::TypeMismatch(
+            value.clone(),
+            STRING_SCALAR_TYPE.into(),
+        )),
+        Some(models::Argument::Variable { name }) => values::translate_variable(
+            env,
+            state,
+            env.get_variables_table()?,
+            name,
+            &metadata::Type::ScalarType(STRING_SCALAR_TYPE.into()),
+        )
+        .map(Some),
     }
 }
 
@@ -347,6 +610,7 @@ fn make_reference_and_from_clause(
             let from_clause = sql::ast::From::Table {
                 reference: reference.clone(),
                 alias: table_alias.clone(),
+                system_time: None,
             };
             let reference = sql::ast::TableReference::AliasedTable(table_alias);
             Ok((
@@ -0,0 +1,92 @@
+//! Handle routine (table function, scalar function and procedure) calls.
+
+use std::collections::BTreeMap;
+
+use ndc_models as models;
+
+use super::values;
+use crate::translation::error::Error;
+use crate::translation::helpers::{Env, State, FUNCTION_RESULT_COLUMN};
+use query_engine_metadata::metadata;
+use query_engine_sql::sql;
+
+/// Build a FROM clause calling a table function:
+///
+/// ```sql
+/// FROM dataset.top_customers(@param1) AS top_customers_0
+/// ```
+pub fn make_table_function_from_clause(
+    env: &Env,
+    state: &mut State,
+    alias: &sql::ast::TableAlias,
+    info: &metadata::TableFunctionInfo,
+    arguments: &BTreeMap<models::ArgumentName, models::Argument>,
+) -> Result<sql::ast::From, Error> {
+    Ok(sql::ast::From::TableFunction {
+        schema: sql::ast::SchemaName(info.schema_name.clone()),
+        function: sql::ast::RoutineName(info.function_name.clone()),
+        arguments: translate_arguments(env, state, &info.arguments, arguments)?,
+        alias: alias.clone(),
+    })
+}
+
+/// Build a FROM clause calling a scalar function, returning a single row with its result
+/// in the `__value` column:
+///
+/// ```sql
+/// FROM (SELECT dataset.add_tax(@param1) AS __value) AS add_tax_0
+/// ```
+pub fn make_scalar_function_from_clause(
+    env: &Env,
+    state: &mut State,
+    alias: &sql::ast::TableAlias,
+    info: &metadata::ScalarFunctionInfo,
+    arguments: &BTreeMap<models::ArgumentName, models::Argument>,
+) -> Result<sql::ast::From, Error> {
+    let call = sql::ast::Expression::FunctionCall {
+        function: sql::ast::Function::Routine {
+            schema: sql::ast::SchemaName(info.schema_name.clone()),
+            name: sql::ast::RoutineName(info.function_name.clone()),
+        },
+        args: translate_arguments(env, state, &info.arguments, arguments)?,
+    };
+    let select = sql::helpers::simple_select(vec![(
+        sql::helpers::make_column_alias(FUNCTION_RESULT_COLUMN.to_string()),
+        call,
+    )]);
+    Ok(sql::ast::From::Select {
+        select: Box::new(select),
+        alias: alias.clone(),
+    })
+}
+
+/// Translate the arguments of a routine call, in the order the routine expects them.
+/// Any argument may be null, so a missing argument is passed as `NULL`.
+pub(crate) fn translate_arguments(
+    env: &Env,
+    state: &mut State,
+    parameters: &[metadata::RoutineArgument],
+    arguments: &BTreeMap<models::ArgumentName, models::Argument>,
+) -> Result<Vec<sql::ast::Expression>, Error> {
+    parameters
+        .iter()
+        .map(|parameter| match arguments.get(&parameter.name) {
+            None => values::translate_json_value(
+                env,
+                state,
+                &serde_json::Value::Null,
+                &parameter.r#type,
+            ),
+            Some(models::Argument::Literal { value }) => {
+                values::translate_json_value(env, state, value, &parameter.r#type)
+            }
+            Some(models::Argument::Variable { name }) => values::translate_variable(
+                env,
+                state,
+                env.get_variables_table()?,
+                name,
+                &parameter.r#type,
+            ),
+        })
+        .collect()
+}
@@ -4,8 +4,9 @@ use crate::translation::{error::Error, helpers::Env, helpers::State};
 use ndc_models as models;
 use query_engine_metadata::metadata::database;
 use query_engine_sql::sql;
-use query_engine_sql::sql::ast::{ColumnReference, Expression, Value};
+use query_engine_sql::sql::ast::{ColumnReference, Expression, Function, Value};
 use query_engine_sql::sql::helpers::simple_select;
+use query_engine_sql::sql::string::LiteralEncoding;
 
 /// Convert a JSON value into a SQL value.
 pub fn translate_json_value(
@@ -14,38 +15,106 @@ pub fn translate_json_value(
     value: &serde_json::Value,
     r#type: &database::Type,
 ) -> Result<sql::ast::Expression, Error> {
+    if let Some((index, literal)) = state.lifted_literal(value) {
+        let literal = literal.clone();
+        return translate_lifted_literal(env, state, index, &literal, r#type);
+    }
+    // Literals inside values translated as constants are translated as constants too.
+    let restored;
+    let value = if state.has_lifted_literals() {
+        restored = state.restore_lifted_literals(value);
+        &restored
+    } else {
+        value
+    };
+
+    // JSON values are passed as they are, whatever their shape.
+    if is_json_type(env, r#type)? && !value.is_null() {
+        return Ok(parse_json(json_string(value)));
+    }
+
     match (value, r#type) {
         (serde_json::Value::Null, _) => Ok(Expression::Cast {
             expression: Box::new(Expression::Value(Value::Null)),
             r#type: type_to_ast_scalar_type(env, r#type)?,
         }),
-        (serde_json::Value::Bool(b), _) => Ok(Expression::Value(Value::Bool(*b))),
-        (serde_json::Value::Number(n), _) => {
+        (serde_json::Value::Bool(b), database::Type::ScalarType(_)) => {
+            Ok(Expression::Value(Value::Bool(*b)))
+        }
+        (serde_json::Value::Number(n), database::Type::ScalarType(_)) => {
             let lit = n
                 .as_f64()
                 .ok_or(Error::UnableToDeserializeNumberAsF64(n.clone()))?;
             Ok(Expression::Value(Value::Float8(lit)))
         }
-        (serde_json::Value::String(str), _) => Ok(Expression::Cast {
+        (serde_json::Value::String(str), database::Type::ScalarType(_)) => Ok(Expression::Cast {
             expression: Box::new(Expression::Value(Value::String(str.clone()))),
             r#type: type_to_ast_scalar_type(env, r#type)?,
         }),
-        (serde_json::Value::Array(_), database::Type::ArrayType(_)) => {
-            let value_expression =
-                sql::ast::Expression::Value(sql::ast::Value::JsonValue(value.clone()));
-            translate_projected_variable(env, state, r#type, value_expression)
-        }
 
-        // If the type is not congruent with the value constructor we simply pass the json value
-        // raw and cast to the specified type. This allows users to consume any json values,
-        // treating them either as actual json or as any type that has a cast from json defined.
-        _ => Ok(sql::ast::Expression::Cast {
-            expression: Box::new(sql::ast::Expression::Cast {
-                expression: Box::new(Expression::Value(Value::JsonValue(value.clone()))),
-                r#type: sql::helpers::jsonb_type(),
-            }),
+        // Arrays, and values which are not congruent with their type, are passed as JSON
+        // and converted to the specified type. This allows users to consume any json values,
+        // as long as they can be converted to the type.
+        _ => translate_projected_variable(env, state, r#type, parse_json(json_string(value))),
+    }
+}
+
+/// Translate a literal lifted out of the request to a parameter, bound according to how it
+/// is used, the same way `translate_json_value` translates literals.
+fn translate_lifted_literal(
+    env: &Env,
+    state: &mut State,
+    index: usize,
+    literal: &serde_json::Value,
+    r#type: &database::Type,
+) -> Result<sql::ast::Expression, Error> {
+    let parameter = |encoding| Expression::Value(Value::Literal { index, encoding });
+
+    if is_json_type(env, r#type)? {
+        return Ok(parse_json(parameter(LiteralEncoding::Json)));
+    }
+
+    match (literal, r#type) {
+        (serde_json::Value::Bool(_), database::Type::ScalarType(_)) => {
+            Ok(parameter(LiteralEncoding::Bool))
+        }
+        (serde_json::Value::Number(_), database::Type::ScalarType(_)) => {
+            Ok(parameter(LiteralEncoding::Float64))
+        }
+        (serde_json::Value::String(_), database::Type::ScalarType(_)) => Ok(Expression::Cast {
+            expression: Box::new(parameter(LiteralEncoding::String)),
             r#type: type_to_ast_scalar_type(env, r#type)?,
         }),
+        _ => translate_projected_variable(
+            env,
+            state,
+            r#type,
+            parse_json(parameter(LiteralEncoding::Json)),
+        ),
+    }
+}
+
+/// The JSON text of a value, passed as a string parameter.
+fn json_string(value: &serde_json::Value) -> Expression {
+    Expression::Value(Value::String(value.to_string()))
+}
+
+/// Parse a JSON value passed as a string parameter: `PARSE_JSON(@param1)`.
+fn parse_json(json: Expression) -> Expression {
+    Expression::FunctionCall {
+        function: Function::ParseJson,
+        args: vec![json],
+    }
+}
+
+/// Is this the JSON scalar type?
+fn is_json_type(env: &Env, typ: &database::Type) -> Result<bool, Error> {
+    match typ {
+        database::Type::ArrayType(_) => Ok(false),
+        database::Type::ScalarType(t) => Ok(matches!(
+            env.lookup_scalar_type(t)?.type_name.as_str(),
+            "json" | "jsonb"
+        )),
     }
 }
 
@@ -107,12 +176,11 @@ pub fn translate_variable(
         column: sql::helpers::make_column_alias(sql::helpers::VARIABLES_FIELD.to_string()),
     });
 
-    // We use the binop '->' to project (as jsonb) the value of a variable from the data column of
-    // the variable table.
-    let projected_variable_exp = sql::ast::Expression::BinaryOperation {
-        left: Box::new(variables_reference),
-        operator: sql::ast::BinaryOperator("->".to_string()),
-        right: Box::new(sql::ast::Expression::Value(sql::ast::Value::String(
+    // We use the JSON subscript operator to project the value of a variable from the
+    // (JSON) data column of the variable table.
+    let projected_variable_exp = sql::ast::Expression::JsonSubscript {
+        expression: Box::new(variables_reference),
+        field: Box::new(sql::ast::Expression::Value(sql::ast::Value::String(
             variable.to_string(),
         ))),
     };
@@ -120,14 +188,17 @@ pub fn translate_variable(
     translate_projected_variable(env, state, r#type, projected_variable_exp)
 }
 
-/// Produce a SQL expression that translates an expression of Postgres type 'jsonb' into a given
+/// Produce a SQL expression that translates an expression of type 'JSON' into a given
 /// type.
 ///
-/// For scalar types and object types this is a simple operation, since we can rely on builtin
-/// functions.
+/// For scalar types this is a simple operation, since we can rely on builtin
+/// functions: the value is extracted as a string, and cast to the type. A JSON null, or
+/// an object or array where a scalar is expected, has no scalar value and becomes NULL,
+/// while a scalar which cannot be converted to the type fails the query.
 ///
-/// Arrays are more complex since there isn't a builtin function that handles array
-/// types.
+/// Arrays are more complex since we need to convert each of their elements, keeping
+/// their order. A JSON null, or anything else than an array, becomes an empty array.
+/// BigQuery arrays cannot hold NULL, so an array with a null element fails the query.
 pub fn translate_projected_variable(
     env: &Env,
     state: &mut State,
@@ -137,55 +208,66 @@ pub fn translate_projected_variable(
     let result = match r#type {
         // We translate projection of array types into the following sql:
         // ```
-        // ( SELECT
-        //     array_agg(
-        //       jsonb_populate_record(cast(null as <type>), "array"."element")
-        //     ) AS "element"
-        //   FROM
-        //    jsonb_array_elements((<variable_table> -> <label>)) AS "array"("element")
+        // ARRAY(
+        //   SELECT <convert array_1.element>
+        //   FROM (
+        //     SELECT element, off
+        //     FROM UNNEST(JSON_QUERY_ARRAY(<exp>)) AS element WITH OFFSET AS off
+        //   ) AS array_1
+        //   ORDER BY array_1.off
         // )
         // ```
         database::Type::ArrayType(type_name) => {
+            if let database::Type::ArrayType(_) = **type_name {
+                return Err(Error::NestedArrayTypesNotSupported);
+            }
+
             let array_table = state.make_table_alias("array".to_string());
             let element_column = sql::helpers::make_column_alias("element".to_string());
+            let offset_column = sql::helpers::make_column_alias("off".to_string());
 
-            let from_arr = sql::ast::From::JsonbArrayElements {
-                expression: exp,
+            let from_arr = sql::ast::From::Unnest {
+                expression: sql::ast::Expression::FunctionCall {
+                    function: Function::JsonQueryArray,
+                    args: vec![exp],
+                },
                 alias: array_table.clone(),
                 column: element_column.clone(),
+                offset: Some(offset_column.clone()),
             };
 
             let element_expression =
                 sql::ast::Expression::ColumnReference(ColumnReference::AliasedColumn {
-                    table: sql::ast::TableReference::AliasedTable(array_table),
+                    table: sql::ast::TableReference::AliasedTable(array_table.clone()),
                     column: element_column.clone(),
                 });
 
             let converted_element_exp =
                 translate_projected_variable(env, state, type_name, element_expression)?;
 
-            let mut result_select = simple_select(vec![(
-                element_column,
-                sql::ast::Expression::FunctionCall {
-                    function: sql::ast::Function::Unknown("array_agg".to_string()),
-                    args: vec![converted_element_exp],
-                },
-            )]);
+            let mut result_select = simple_select(vec![(element_column, converted_element_exp)]);
 
             result_select.from = Some(from_arr);
+            // the elements of a subquery have no order of their own.
+            result_select.order_by = sql::ast::OrderBy {
+                elements: vec![sql::ast::OrderByElement {
+                    target: sql::ast::Expression::ColumnReference(ColumnReference::AliasedColumn {
+                        table: sql::ast::TableReference::AliasedTable(array_table),
+                        column: offset_column,
+                    }),
+                    direction: sql::ast::OrderByDirection::Asc,
+                }],
+            };
 
-            sql::ast::Expression::CorrelatedSubSelect(Box::new(result_select))
+            sql::ast::Expression::ArraySubquery(Box::new(result_select))
         }
+        // JSON values need no conversion.
+        database::Type::ScalarType(_) if is_json_type(env, r#type)? => exp,
+        // Other scalars are extracted as strings and converted to their type.
         database::Type::ScalarType(_) => sql::ast::Expression::Cast {
-            expression: Box::new(sql::ast::Expression::BinaryOperation {
-                left: Box::new(exp),
-                operator: sql::ast::BinaryOperator("#>>".to_string()),
-                right: Box::new(sql::ast::Expression::Cast {
-                    expression: Box::new(sql::ast::Expression::Value(sql::ast::Value::Array(
-                        vec![],
-                    ))),
-                    r#type: sql::ast::ScalarType::ArrayType(sql::helpers::text_type_name()),
-                }),
+            expression: Box::new(sql::ast::Expression::FunctionCall {
+                function: Function::JsonValue,
+                args: vec![exp],
             }),
             r#type: type_to_ast_scalar_type(env, r#type)?,
         },
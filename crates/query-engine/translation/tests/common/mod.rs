@@ -1,6 +1,7 @@
 use std::fs;
 
 use query_engine_sql::sql;
+use query_engine_sql::sql::dialect::BigQuery;
 use query_engine_translation::translation;
 use std::path::PathBuf;
 
@@ -32,7 +33,7 @@ pub async fn test_translation(testname: &str) -> anyhow::Result<String> {
         serde_json::from_str(&fs::read_to_string(directory.join("request.json")).unwrap()).unwrap();
 
     let plan = translation::query::translate(&metadata, request)?;
-    let query = plan.query.query_sql();
+    let query = plan.query.query_sql(&BigQuery)?;
     let params: Vec<(usize, &sql::string::Param)> = query
         .params
         .iter()
@@ -46,5 +47,172 @@ pub async fn test_translation(testname: &str) -> anyhow::Result<String> {
         sqlformat::FormatOptions::default(),
     );
 
-    Ok(format!("{}\n\n{:?}", pretty, params))
+    if plan.query.request_parameters.is_empty() {
+        Ok(format!("{}\n\n{:?}", pretty, params))
+    } else {
+        Ok(format!(
+            "{}\n\n{:?}\n\n{:?}",
+            pretty, params, plan.query.request_parameters
+        ))
+    }
+}
+
+/// Translate a query, and return the fields and the SQL of the query selecting its rows as typed
+/// columns.
+pub async fn test_typed_rows_translation(testname: &str) -> anyhow::Result<String> {
+    let directory = PathBuf::from("tests/goldenfiles").join(testname);
+
+    let parsed_configuration = ndc_bigquery_configuration::parse_configuration(&directory).await?;
+    let configuration = ndc_bigquery_configuration::make_runtime_configuration(
+        parsed_configuration,
+        ndc_bigquery_configuration::environment::FixedEnvironment::from([
+            (
+                "HASURA_BIGQUERY_SERVICE_KEY".into(),
+                "the translation tests do not rely on a database connection".into(),
+            ),
+            (
+                "HASURA_BIGQUERY_PROJECT_ID".into(),
+                "the translation tests do not rely on a database connection".into(),
+            ),
+            (
+                "HASURA_BIGQUERY_DATASET_ID".into(),
+                "the translation tests do not rely on a database connection".into(),
+            ),
+        ]),
+    )?;
+    let metadata = configuration.metadata;
+
+    let request =
+        serde_json::from_str(&fs::read_to_string(directory.join("request.json")).unwrap()).unwrap();
+
+    let plan = translation::query::translate(&metadata, request)?;
+    let typed_rows = plan
+        .query
+        .typed_rows
+        .ok_or_else(|| anyhow::anyhow!("the rows cannot be selected as typed columns"))?;
+    let query = typed_rows.query_sql(&BigQuery)?;
+    let params: Vec<(usize, &sql::string::Param)> = query
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1, p))
+        .collect();
+
+    let pretty = sqlformat::format(
+        &query.sql,
+        &sqlformat::QueryParams::None,
+        sqlformat::FormatOptions::default(),
+    );
+    let fields: Vec<String> = typed_rows.fields.iter().map(ToString::to_string).collect();
+
+    Ok(format!(
+        "{}\n\n{}\n\n{:?}",
+        fields.join(", "),
+        pretty,
+        params
+    ))
+}
+
+/// Translate a query with its literals lifted, and return the SQL, the parameters, and the
+/// lifted literals they are bound to.
+pub async fn test_lifted_translation(testname: &str) -> anyhow::Result<String> {
+    let directory = PathBuf::from("tests/goldenfiles").join(testname);
+
+    let parsed_configuration = ndc_bigquery_configuration::parse_configuration(&directory).await?;
+    let configuration = ndc_bigquery_configuration::make_runtime_configuration(
+        parsed_configuration,
+        ndc_bigquery_configuration::environment::FixedEnvironment::from([
+            (
+                "HASURA_BIGQUERY_SERVICE_KEY".into(),
+                "the translation tests do not rely on a database connection".into(),
+            ),
+            (
+                "HASURA_BIGQUERY_PROJECT_ID".into(),
+                "the translation tests do not rely on a database connection".into(),
+            ),
+            (
+                "HASURA_BIGQUERY_DATASET_ID".into(),
+                "the translation tests do not rely on a database connection".into(),
+            ),
+        ]),
+    )?;
+    let metadata = configuration.metadata;
+
+    let mut request =
+        serde_json::from_str(&fs::read_to_string(directory.join("request.json")).unwrap()).unwrap();
+
+    let literals = translation::query::literals::lift(&mut request)
+        .ok_or_else(|| anyhow::anyhow!("the literals of the request cannot be lifted"))?;
+    let (plan, reusable) = translation::query::translate_lifted(&metadata, request, literals)?;
+    let query = plan.query.query_sql(&BigQuery)?;
+    let params: Vec<(usize, &sql::string::Param)> = query
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1, p))
+        .collect();
+
+    let pretty = sqlformat::format(
+        &query.sql,
+        &sqlformat::QueryParams::None,
+        sqlformat::FormatOptions::default(),
+    );
+
+    Ok(format!(
+        "{}\n\n{:?}\n\n{:?}\n\nreusable: {}",
+        pretty, params, plan.query.literals, reusable
+    ))
+}
+
+/// Translate a mutation request and return the SQL of each procedure call with its parameters.
+pub async fn test_mutation_translation(testname: &str) -> anyhow::Result<String> {
+    let directory = PathBuf::from("tests/goldenfiles/mutations").join(testname);
+
+    let parsed_configuration = ndc_bigquery_configuration::parse_configuration(&directory).await?;
+    let configuration = ndc_bigquery_configuration::make_runtime_configuration(
+        parsed_configuration,
+        ndc_bigquery_configuration::environment::FixedEnvironment::from([
+            (
+                "HASURA_BIGQUERY_SERVICE_KEY".into(),
+                "the translation tests do not rely on a database connection".into(),
+            ),
+            (
+                "HASURA_BIGQUERY_PROJECT_ID".into(),
+                "the translation tests do not rely on a database connection".into(),
+            ),
+            (
+                "HASURA_BIGQUERY_DATASET_ID".into(),
+                "the translation tests do not rely on a database connection".into(),
+            ),
+        ]),
+    )?;
+    let metadata = configuration.metadata;
+
+    let request =
+        serde_json::from_str(&fs::read_to_string(directory.join("request.json")).unwrap()).unwrap();
+
+    let plan = translation::mutation::translate(&metadata, request)?;
+
+    Ok(plan
+        .query
+        .iter()
+        .map(|mutation| {
+            let call = mutation.call_sql(&BigQuery)?;
+            let params: Vec<(usize, &sql::string::Param)> = call
+                .params
+                .iter()
+                .enumerate()
+                .map(|(i, p)| (i + 1, p))
+                .collect();
+
+            let pretty = sqlformat::format(
+                &call.sql,
+                &sqlformat::QueryParams::None,
+                sqlformat::FormatOptions::default(),
+            );
+
+            Ok(format!("{}\n\n{:?}", pretty, params))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .join("\n\n"))
 }
@@ -20,6 +20,36 @@ async fn it_converts_select_with_limit() {
     insta::assert_snapshot!(result);
 }
 
+#[tokio::test]
+async fn it_select_as_of() {
+    let result = common::test_translation("select_as_of").await.unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_native_query() {
+    let result = common::test_translation("select_native_query")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_native_query_request_parameters() {
+    let result = common::test_translation("select_native_query_request_parameters")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_native_query_variables() {
+    let result = common::test_translation("select_native_query_variables")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
 #[tokio::test]
 async fn it_select_where_string() {
     let result = common::test_translation("select_where_string")
@@ -28,6 +58,43 @@ async fn it_select_where_string() {
     insta::assert_snapshot!(result);
 }
 
+#[tokio::test]
+async fn it_select_where_variable() {
+    let result = common::test_translation("select_where_variable")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_lifts_literals() {
+    let result = common::test_lifted_translation("select_where_string")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_typed_rows() {
+    let result = common::test_typed_rows_translation("select_where_string")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_casts_columns_to_their_representation_in_json_and_typed_rows() {
+    let json = common::test_translation("select_bigint_as_string")
+        .await
+        .unwrap();
+    let typed_rows = common::test_typed_rows_translation("select_bigint_as_string")
+        .await
+        .unwrap();
+    let column = "cast(albums_0.AlbumId as STRING) AS AlbumId";
+    assert!(json.contains(column), "{json}");
+    assert!(typed_rows.contains(column), "{typed_rows}");
+}
+
 #[tokio::test]
 async fn it_select_where_not_null() {
     let result = common::test_translation("select_where_not_null")
@@ -50,6 +117,81 @@ async fn it_select_where_unrelated_exists() {
 //     insta::assert_snapshot!(result);
 // }
 
+#[tokio::test]
+async fn it_select_where_partition_column() {
+    let result = common::test_translation("select_where_partition_column")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_without_partition_filter() {
+    let result = common::test_translation("select_without_partition_filter")
+        .await
+        .unwrap_err()
+        .to_string();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_where_negated_partition_column() {
+    let result = common::test_translation("select_where_negated_partition_column")
+        .await
+        .unwrap_err()
+        .to_string();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_where_ingestion_time_partition() {
+    let result = common::test_translation("select_where_ingestion_time_partition")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_partitioned_relationship() {
+    let result = common::test_translation("select_partitioned_relationship")
+        .await
+        .unwrap_err()
+        .to_string();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_wildcard_table_suffix() {
+    let result = common::test_translation("select_wildcard_table_suffix")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_table_function() {
+    let result = common::test_translation("select_table_function")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_table_function_missing_argument() {
+    let result = common::test_translation("select_table_function_missing_argument")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
+#[tokio::test]
+async fn it_select_scalar_function() {
+    let result = common::test_translation("select_scalar_function")
+        .await
+        .unwrap();
+    insta::assert_snapshot!(result);
+}
+
 #[tokio::test]
 async fn it_aggregate_count_albums() {
     let result = common::test_translation("aggregate_count_albums")
@@ -195,9 +337,68 @@ mod types {
         insta::assert_snapshot!(result);
     }
 
+    #[tokio::test]
+    async fn select_routine_argument_types() {
+        let result = common::test_translation("values/select_routine_argument_types")
+            .await
+            .unwrap();
+        insta::assert_snapshot!(result);
+    }
+
+    /// A literal argument of each scalar type: numbers and booleans are written in the SQL,
+    /// other values are bound as strings and cast to their type, and JSON is parsed.
+    #[tokio::test]
+    async fn select_routine_argument_scalar_types() {
+        let result = common::test_translation("values/select_routine_argument_scalar_types")
+            .await
+            .unwrap();
+        insta::assert_snapshot!(result);
+    }
+
+    /// A literal array argument of each scalar type, bound as JSON and converted element by
+    /// element.
+    #[tokio::test]
+    async fn select_routine_argument_array_types() {
+        let result = common::test_translation("values/select_routine_argument_array_types")
+            .await
+            .unwrap();
+        insta::assert_snapshot!(result);
+    }
+
+    #[tokio::test]
+    async fn select_routine_argument_nested_array_type() {
+        let result = common::test_translation("values/select_routine_argument_nested_array_type")
+            .await
+            .unwrap_err()
+            .to_string();
+        insta::assert_snapshot!(result);
+    }
+
+    /// The sets of variables hold scalars, arrays and JSON, nulls, and values of the wrong
+    /// shape. They are bound as a parameter, so the SQL converting them is the same for all.
+    #[tokio::test]
+    async fn select_routine_argument_variables() {
+        let result = common::test_translation("values/select_routine_argument_variables")
+            .await
+            .unwrap();
+        insta::assert_snapshot!(result);
+    }
+
     // #[tokio::test]
     // async fn select_types_on_native_queries() {
     //     let result = common::test_translation("values/select_types_on_native_queries").await.unwrap();
     //     insta::assert_snapshot!(result);
     // }
 }
+
+mod mutations {
+    use crate::common;
+
+    #[tokio::test]
+    async fn call_procedure() {
+        let result = common::test_mutation_translation("call_procedure")
+            .await
+            .unwrap();
+        insta::assert_snapshot!(result);
+    }
+}